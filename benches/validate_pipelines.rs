@@ -0,0 +1,47 @@
+//! Validating a deployment's worth of pipeline files, against the bundled
+//! schema (parsed once and shared) and against the same schema supplied at
+//! runtime (parsed for every file).
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use harmony_dsl::{validate, validate_with_schema, SchemaKind, PIPELINE_SCHEMA};
+
+/// `count` similar pipeline files, each routing one endpoint to one backend.
+fn pipelines(count: usize) -> Vec<String> {
+    (0..count)
+        .map(|n| {
+            format!(
+                "[pipelines.api_{n}]\ndescription = \"API {n}\"\nnetworks = [\"default\"]\n\
+                 endpoints = [\"api_{n}\"]\nbackends = [\"upstream_{n}\"]\n\n\
+                 [endpoints.api_{n}]\nservice = \"http\"\n\n\
+                 [endpoints.api_{n}.options]\npath_prefix = \"/api/{n}\"\n\n\
+                 [backends.upstream_{n}]\nservice = \"http\"\n\n\
+                 [backends.upstream_{n}.options]\nbase_url = \"http://upstream-{n}:8080\"\n"
+            )
+        })
+        .collect()
+}
+
+fn validate_pipelines(c: &mut Criterion) {
+    let mut group = c.benchmark_group("validate_pipelines");
+    for count in [10, 100] {
+        let files = pipelines(count);
+        group.bench_with_input(BenchmarkId::new("bundled", count), &files, |b, files| {
+            b.iter(|| {
+                for file in files {
+                    let _ = validate(SchemaKind::Pipeline, file);
+                }
+            });
+        });
+        group.bench_with_input(BenchmarkId::new("runtime", count), &files, |b, files| {
+            b.iter(|| {
+                for file in files {
+                    let _ = validate_with_schema(PIPELINE_SCHEMA, file);
+                }
+            });
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, validate_pipelines);
+criterion_main!(benches);
@@ -0,0 +1,219 @@
+//! `harmony-validate`: validate Harmony config files from the shell.
+//!
+//! ```text
+//! harmony-validate [--schema KIND] [--format terminal|json] PATH
+//! ```
+//!
+//! `PATH` is a single file or a deployment directory (see
+//! [`validate_directory`]). A file's schema is taken from `--schema`, or
+//! from its place in the deployment layout: `config.toml`, `pipelines/*.toml`
+//! or `mesh/*.toml`. Exits with 0 when there are no errors, 1 when there
+//! are, and 2 when the arguments or the file itself are unusable.
+
+use std::fs;
+use std::io::{self, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use harmony_dsl::{
+    render_json, render_terminal, validate_directory, validate_report, DirectoryReport, ErrorCode,
+    FileReport, SchemaKind, Severity, ValidationError, ValidationErrorKind, ValidationReport,
+};
+
+const USAGE: &str = "usage: harmony-validate [--schema KIND] [--format terminal|json] PATH";
+
+#[derive(Clone, Copy, PartialEq)]
+enum Format {
+    Terminal,
+    Json,
+}
+
+struct Args {
+    path: PathBuf,
+    schema: Option<SchemaKind>,
+    format: Format,
+}
+
+fn main() -> ExitCode {
+    if std::env::args().any(|arg| arg == "-h" || arg == "--help") {
+        println!("{USAGE}");
+        return ExitCode::SUCCESS;
+    }
+    let args = match parse_args(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(message) => {
+            eprintln!("harmony-validate: {message}\n{USAGE}");
+            return ExitCode::from(2);
+        }
+    };
+    let report = if args.path.is_dir() {
+        if args.schema.is_some() {
+            eprintln!("harmony-validate: --schema applies to single files only\n{USAGE}");
+            return ExitCode::from(2);
+        }
+        validate_directory(&args.path)
+    } else {
+        match validate_file(&args.path, args.schema) {
+            Ok(report) => report,
+            Err(message) => {
+                eprintln!("harmony-validate: {message}");
+                return ExitCode::from(2);
+            }
+        }
+    };
+
+    let result = match args.format {
+        Format::Terminal => print_terminal(&report),
+        Format::Json => print_json(&report),
+    };
+    if let Err(err) = result {
+        eprintln!("harmony-validate: {err}");
+        return ExitCode::from(2);
+    }
+    if report.is_ok() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Args, String> {
+    let mut path = None;
+    let mut schema = None;
+    let mut format = Format::Terminal;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--schema" => {
+                let name = args.next().ok_or("--schema needs a schema kind")?;
+                schema = Some(name.parse().map_err(|err| format!("{err}"))?);
+            }
+            "--format" => {
+                format = match args.next().as_deref() {
+                    Some("terminal") => Format::Terminal,
+                    Some("json") => Format::Json,
+                    _ => return Err("--format must be `terminal` or `json`".to_owned()),
+                };
+            }
+            flag if flag.starts_with('-') => return Err(format!("unknown option `{flag}`")),
+            _ if path.is_some() => return Err("expected a single PATH".to_owned()),
+            _ => path = Some(PathBuf::from(arg)),
+        }
+    }
+    Ok(Args {
+        path: path.ok_or("missing PATH")?,
+        schema,
+        format,
+    })
+}
+
+/// Validate one file as a [`DirectoryReport`] of one, so files and
+/// directories print alike.
+fn validate_file(path: &Path, schema: Option<SchemaKind>) -> Result<DirectoryReport, String> {
+    let kind = match schema.or_else(|| detect(path)) {
+        Some(kind) => kind,
+        None => {
+            return Err(format!(
+                "cannot tell the schema of `{}` from its name; pass --schema",
+                path.display()
+            ))
+        }
+    };
+    let source = fs::read_to_string(path).map_err(|err| format!("{}: {err}", path.display()))?;
+    let mut report = DirectoryReport::default();
+    report.files.push(FileReport {
+        path: path.to_owned(),
+        kind,
+        errors: validate_report(kind, &source).into_diagnostics(),
+    });
+    Ok(report)
+}
+
+/// The schema of a file by the deployment directory convention.
+fn detect(path: &Path) -> Option<SchemaKind> {
+    if path.extension()? != "toml" {
+        return None;
+    }
+    if path.file_name()? == "config.toml" {
+        return Some(SchemaKind::Config);
+    }
+    match path.parent()?.file_name()?.to_str()? {
+        "pipelines" => Some(SchemaKind::Pipeline),
+        "mesh" => Some(SchemaKind::Mesh),
+        "remote_ingress" => Some(SchemaKind::RemoteIngress),
+        _ => None,
+    }
+}
+
+fn print_terminal(report: &DirectoryReport) -> io::Result<()> {
+    let mut stdout = io::stdout();
+    let mut first = true;
+    for file in &report.files {
+        if file.errors.is_empty() {
+            continue;
+        }
+        let source = fs::read_to_string(&file.path).unwrap_or_default();
+        let errors: Vec<ValidationError> = file
+            .errors
+            .iter()
+            .map(|error| ValidationError {
+                file: Some(file.path.clone()),
+                ..error.clone()
+            })
+            .collect();
+        if !first {
+            writeln!(stdout)?;
+        }
+        first = false;
+        let color = stdout.is_terminal();
+        render_terminal(&source, &errors, &mut stdout, color)?;
+    }
+    for path in &report.missing {
+        if !first {
+            writeln!(stdout)?;
+        }
+        first = false;
+        writeln!(stdout, "error: missing required file {}", path.display())?;
+    }
+
+    let diagnostics = report.files.iter().flat_map(|file| &file.errors);
+    let errors =
+        diagnostics.clone().filter(|error| error.is_error()).count() + report.missing.len();
+    let warnings = diagnostics
+        .filter(|error| error.severity == Severity::Warning)
+        .count();
+    if !first {
+        writeln!(stdout)?;
+    }
+    writeln!(
+        stdout,
+        "{} checked: {}, {}",
+        plural(report.files.len(), "file"),
+        plural(errors, "error"),
+        plural(warnings, "warning")
+    )
+}
+
+/// [`render_json`] of every diagnostic, with missing required files as
+/// [`ValidationErrorKind::Io`] errors.
+fn print_json(report: &DirectoryReport) -> io::Result<()> {
+    let mut diagnostics = report.report().into_diagnostics();
+    diagnostics.extend(report.missing.iter().map(|path| ValidationError {
+        path: String::new(),
+        kind: ValidationErrorKind::Io,
+        code: ErrorCode::Io,
+        message: "missing required file".to_owned(),
+        span: None,
+        severity: Severity::Error,
+        file: Some(path.clone()),
+        fix: None,
+    }));
+    let json = render_json(&ValidationReport::new(diagnostics));
+    writeln!(io::stdout(), "{json}")
+}
+
+fn plural(count: usize, noun: &str) -> String {
+    match count {
+        1 => format!("1 {noun}"),
+        n => format!("{n} {noun}s"),
+    }
+}
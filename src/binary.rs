@@ -0,0 +1,335 @@
+//! A compact binary form of a parsed [`Schema`], to skip TOML parsing at
+//! startup.
+//!
+//! [`Schema::to_bytes`] encodes a schema with `postcard`, and
+//! [`Schema::from_bytes`] decodes it again, recompiling its patterns. The
+//! encoding is tied to the version of this crate: bytes written by another
+//! version are rejected rather than misread, so a cache keyed on anything
+//! else still needs a fallback to [`parse_schema`](crate::parse_schema).
+//!
+//! A build script can embed the bytes with [`write_schema_bytes`]:
+//!
+//! ```no_run
+//! // build.rs
+//! # fn main() -> std::io::Result<()> {
+//! let out_dir = std::env::var("OUT_DIR").unwrap();
+//! harmony_dsl::write_schema_bytes(harmony_dsl::SchemaKind::Config, out_dir.as_ref())?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! ```ignore
+//! // main.rs
+//! static CONFIG: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/config-schema.bin"));
+//! let schema = harmony_dsl::Schema::from_bytes(CONFIG)?;
+//! ```
+
+use alloc::collections::BTreeMap;
+#[cfg(feature = "std")]
+use std::path::{Path, PathBuf};
+
+use semver::Version;
+use serde::{Deserialize, Serialize};
+use toml::{Table, Value};
+
+use crate::prelude::*;
+use crate::schema::{
+    Condition, FieldRule, FieldType, Pattern, Schema, SchemaParseError, WeightSum,
+};
+
+/// The encoded form of a [`Schema`]. Values are kept as TOML text, since
+/// `postcard` cannot decode self-describing types such as [`Value`].
+#[derive(Serialize, Deserialize)]
+struct SchemaBytes {
+    /// The version of this crate that wrote the bytes.
+    crate_version: String,
+    version: Option<String>,
+    fields: Vec<RuleBytes>,
+    enumerations: BTreeMap<String, Vec<String>>,
+    protocol_compatibility: BTreeMap<String, Vec<String>>,
+    middleware_order: BTreeMap<String, Vec<String>>,
+    middleware_versions: BTreeMap<String, Vec<String>>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct RuleBytes {
+    path: String,
+    ty: FieldType,
+    required: bool,
+    description: Option<String>,
+    default: Option<String>,
+    example: Option<String>,
+    item_type: Option<FieldType>,
+    allowed_values: Option<Vec<String>>,
+    min: Option<i64>,
+    max: Option<i64>,
+    min_items: Option<usize>,
+    max_items: Option<usize>,
+    pattern: Option<String>,
+    validator: Option<String>,
+    non_empty: bool,
+    secret: bool,
+    security_relevant: bool,
+    additional_keys: bool,
+    required_if: Option<ConditionBytes>,
+    deprecation: Option<String>,
+    replacement: Option<String>,
+    since: Option<String>,
+    until: Option<String>,
+    one_of: Option<Vec<String>>,
+    all_or_none: Option<Vec<String>>,
+    weights: Option<WeightSum>,
+    extension: Option<ConditionBytes>,
+    table: String,
+}
+
+#[derive(Serialize, Deserialize)]
+enum ConditionBytes {
+    Equals { field: String, value: String },
+    Exists { field: String },
+    Missing { field: String },
+}
+
+impl Schema {
+    /// This schema in a compact binary form, for [`Schema::from_bytes`].
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let schema = SchemaBytes {
+            crate_version: env!("CARGO_PKG_VERSION").to_owned(),
+            version: self.version.as_ref().map(Version::to_string),
+            fields: self.fields.iter().map(RuleBytes::from).collect(),
+            enumerations: self.enumerations.clone(),
+            protocol_compatibility: self.protocol_compatibility.clone(),
+            middleware_order: self.middleware_order.clone(),
+            middleware_versions: self.middleware_versions.clone(),
+        };
+        postcard::to_allocvec(&schema).unwrap_or_default()
+    }
+
+    /// Decode a schema written by [`Schema::to_bytes`] with the same version
+    /// of this crate. Other input fails with
+    /// [`SchemaParseError::InvalidBytes`].
+    pub fn from_bytes(bytes: &[u8]) -> Result<Schema, SchemaParseError> {
+        let invalid = |message: &str| SchemaParseError::InvalidBytes(message.to_owned());
+        let schema: SchemaBytes =
+            postcard::from_bytes(bytes).map_err(|_| invalid("not an encoded schema"))?;
+        if schema.crate_version != env!("CARGO_PKG_VERSION") {
+            return Err(invalid(&format!(
+                "encoded by harmony-dsl {}, not {}",
+                schema.crate_version,
+                env!("CARGO_PKG_VERSION")
+            )));
+        }
+        Ok(Schema {
+            version: schema
+                .version
+                .map(|version| Version::parse(&version))
+                .transpose()
+                .map_err(|_| invalid("the schema has an invalid version"))?,
+            fields: schema
+                .fields
+                .into_iter()
+                .map(FieldRule::try_from)
+                .collect::<Result<_, _>>()?,
+            enumerations: schema.enumerations,
+            protocol_compatibility: schema.protocol_compatibility,
+            middleware_order: schema.middleware_order,
+            middleware_versions: schema.middleware_versions,
+        })
+    }
+}
+
+/// Write the bundled schema for `kind`, encoded by [`Schema::to_bytes`], to
+/// `<dir>/<kind>-schema.bin` (e.g. `config-schema.bin`), returning the path.
+/// Meant for build scripts that `include_bytes!` the result.
+#[cfg(feature = "std")]
+pub fn write_schema_bytes(kind: crate::SchemaKind, dir: &Path) -> std::io::Result<PathBuf> {
+    let path = dir.join(format!("{}-schema.bin", kind.name()));
+    std::fs::write(&path, crate::schema::bundled_schema(kind).to_bytes())?;
+    Ok(path)
+}
+
+impl From<&FieldRule> for RuleBytes {
+    fn from(rule: &FieldRule) -> Self {
+        RuleBytes {
+            path: rule.path.clone(),
+            ty: rule.ty,
+            required: rule.required,
+            description: rule.description.clone(),
+            default: rule.default.as_ref().map(Value::to_string),
+            example: rule.example.as_ref().map(Value::to_string),
+            item_type: rule.item_type,
+            allowed_values: rule.allowed_values.clone(),
+            min: rule.min,
+            max: rule.max,
+            min_items: rule.min_items,
+            max_items: rule.max_items,
+            pattern: rule
+                .pattern
+                .as_ref()
+                .map(|pattern| pattern.as_str().to_owned()),
+            validator: rule.validator.clone(),
+            non_empty: rule.non_empty,
+            secret: rule.secret,
+            security_relevant: rule.security_relevant,
+            additional_keys: rule.additional_keys,
+            required_if: rule.required_if.as_ref().map(ConditionBytes::from),
+            deprecation: rule.deprecation.clone(),
+            replacement: rule.replacement.clone(),
+            since: rule.since.as_ref().map(Version::to_string),
+            until: rule.until.as_ref().map(Version::to_string),
+            one_of: rule.one_of.clone(),
+            all_or_none: rule.all_or_none.clone(),
+            weights: rule.weights,
+            extension: rule.extension.as_ref().map(ConditionBytes::from),
+            table: rule.table.clone(),
+        }
+    }
+}
+
+impl TryFrom<RuleBytes> for FieldRule {
+    type Error = SchemaParseError;
+
+    fn try_from(rule: RuleBytes) -> Result<Self, Self::Error> {
+        let version = |version: Option<String>| {
+            version
+                .map(|version| Version::parse(&version))
+                .transpose()
+                .map_err(|_| {
+                    SchemaParseError::InvalidBytes(format!(
+                        "`{}` has an invalid version",
+                        rule.path
+                    ))
+                })
+        };
+        let since = version(rule.since.clone())?;
+        let until = version(rule.until.clone())?;
+        Ok(FieldRule {
+            default: rule.default.map(|value| value_from(&value)).transpose()?,
+            example: rule.example.map(|value| value_from(&value)).transpose()?,
+            pattern: rule
+                .pattern
+                .map(|pattern| Pattern::compile(&rule.path, &pattern))
+                .transpose()?,
+            required_if: rule.required_if.map(Condition::try_from).transpose()?,
+            extension: rule.extension.map(Condition::try_from).transpose()?,
+            since,
+            until,
+            path: rule.path,
+            ty: rule.ty,
+            required: rule.required,
+            description: rule.description,
+            item_type: rule.item_type,
+            allowed_values: rule.allowed_values,
+            min: rule.min,
+            max: rule.max,
+            min_items: rule.min_items,
+            max_items: rule.max_items,
+            validator: rule.validator,
+            non_empty: rule.non_empty,
+            secret: rule.secret,
+            security_relevant: rule.security_relevant,
+            additional_keys: rule.additional_keys,
+            deprecation: rule.deprecation,
+            replacement: rule.replacement,
+            one_of: rule.one_of,
+            all_or_none: rule.all_or_none,
+            weights: rule.weights,
+            table: rule.table,
+        })
+    }
+}
+
+impl From<&Condition> for ConditionBytes {
+    fn from(condition: &Condition) -> Self {
+        match condition {
+            Condition::Equals { field, value } => ConditionBytes::Equals {
+                field: field.clone(),
+                value: value.to_string(),
+            },
+            Condition::Exists { field } => ConditionBytes::Exists {
+                field: field.clone(),
+            },
+            Condition::Missing { field } => ConditionBytes::Missing {
+                field: field.clone(),
+            },
+        }
+    }
+}
+
+impl TryFrom<ConditionBytes> for Condition {
+    type Error = SchemaParseError;
+
+    fn try_from(condition: ConditionBytes) -> Result<Self, Self::Error> {
+        Ok(match condition {
+            ConditionBytes::Equals { field, value } => Condition::Equals {
+                field,
+                value: value_from(&value)?,
+            },
+            ConditionBytes::Exists { field } => Condition::Exists { field },
+            ConditionBytes::Missing { field } => Condition::Missing { field },
+        })
+    }
+}
+
+/// A value from its TOML text, as written by [`Value::to_string`].
+fn value_from(text: &str) -> Result<Value, SchemaParseError> {
+    toml::from_str::<Table>(&format!("v = {text}"))
+        .ok()
+        .and_then(|mut table| table.remove("v"))
+        .ok_or_else(|| SchemaParseError::InvalidBytes(format!("`{text}` is not a TOML value")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::bundled_schema;
+    use crate::SchemaKind;
+
+    #[test]
+    fn schemas_round_trip_through_bytes() {
+        for kind in SchemaKind::all() {
+            let schema = bundled_schema(*kind).into_owned();
+            let bytes = schema.to_bytes();
+            assert!(bytes.len() < kind.source().len(), "{kind:?}");
+            assert_eq!(Schema::from_bytes(&bytes), Ok(schema), "{kind:?}");
+            assert!(matches!(
+                Schema::from_bytes(&bytes[..bytes.len() / 2]),
+                Err(SchemaParseError::InvalidBytes(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn bytes_from_another_version_are_rejected() {
+        let schema = SchemaBytes {
+            crate_version: "0.0.1".to_owned(),
+            version: None,
+            fields: Vec::new(),
+            enumerations: BTreeMap::new(),
+            protocol_compatibility: BTreeMap::new(),
+            middleware_order: BTreeMap::new(),
+            middleware_versions: BTreeMap::new(),
+        };
+        let bytes = postcard::to_allocvec(&schema).unwrap();
+        let err = Schema::from_bytes(&bytes).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "schema bytes are not usable: encoded by harmony-dsl 0.0.1, not {}",
+                env!("CARGO_PKG_VERSION")
+            )
+        );
+    }
+
+    #[cfg(all(feature = "std", feature = "schema-mesh"))]
+    #[test]
+    fn build_scripts_can_write_the_bundled_schemas() {
+        let dir = std::env::temp_dir().join(format!("harmony-dsl-{}-bytes", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = write_schema_bytes(SchemaKind::Mesh, &dir).unwrap();
+        assert!(path.ends_with("mesh-schema.bin"));
+        let schema = Schema::from_bytes(&std::fs::read(&path).unwrap()).unwrap();
+        assert_eq!(schema, *bundled_schema(SchemaKind::Mesh));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
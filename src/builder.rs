@@ -0,0 +1,216 @@
+//! Programmatic construction of gateway configs.
+//!
+//! Tools that generate configs can use [`ConfigBuilder`] instead of
+//! templating TOML by hand. The builder is assembled from the [`model`]
+//! structs, so it can only name fields the schema declares, and
+//! [`build`](ConfigBuilder::build) validates the result before returning it.
+//!
+//! [`model`]: crate::model
+
+use toml::{Table, Value};
+
+use crate::model::{
+    HttpListener, LogLevel, LoggingConfig, NetworkConfig, ProxySettings, ServiceRole, ServiceType,
+    StorageBackend, StorageConfig, StorageOptions,
+};
+use crate::prelude::*;
+use crate::validate::{validate_value, ValidationError};
+use crate::SchemaKind;
+
+/// Settings for the `s3` storage backend.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct S3Config {
+    pub bucket: String,
+    pub region: Option<String>,
+    pub access_key_id: Option<String>,
+    pub secret_access_key: Option<String>,
+    /// Custom endpoint for S3-compatible stores such as MinIO.
+    pub endpoint: Option<String>,
+}
+
+/// Builds a `config.toml` document one section at a time.
+///
+/// ```
+/// use harmony_dsl::model::HttpListener;
+/// use harmony_dsl::ConfigBuilder;
+///
+/// let toml = ConfigBuilder::new()
+///     .proxy_id("edge")
+///     .add_http_listener("default", HttpListener::new("0.0.0.0", 8080))
+///     .build()
+///     .unwrap();
+/// assert!(toml.contains("bind_port = 8080"));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ConfigBuilder {
+    proxy_id: Option<String>,
+    proxy_name: Option<String>,
+    networks: Vec<(String, NetworkConfig)>,
+    storage: Option<StorageConfig>,
+    logging: Option<LoggingConfig>,
+    services: Vec<(String, ServiceType)>,
+}
+
+impl ConfigBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set `proxy.id`.
+    pub fn proxy_id(mut self, id: &str) -> Self {
+        self.proxy_id = Some(id.to_owned());
+        self
+    }
+
+    /// Set `proxy.name`.
+    pub fn proxy_name(mut self, name: &str) -> Self {
+        self.proxy_name = Some(name.to_owned());
+        self
+    }
+
+    /// Add `[network.<name>]` with `listener` as its `tcp_config`.
+    pub fn add_http_listener(mut self, name: &str, listener: HttpListener) -> Self {
+        let network = NetworkConfig {
+            tcp_config: Some(listener),
+            ..defaults()
+        };
+        self.networks.push((name.to_owned(), network));
+        self
+    }
+
+    /// Store data on the local filesystem under `path`.
+    pub fn storage_filesystem(mut self, path: &str) -> Self {
+        self.storage = Some(StorageConfig {
+            backend: StorageBackend::Filesystem,
+            options: StorageOptions {
+                path: path.to_owned(),
+                ..StorageOptions::default()
+            },
+        });
+        self
+    }
+
+    /// Store data in an S3 bucket.
+    pub fn storage_s3(mut self, s3: S3Config) -> Self {
+        self.storage = Some(StorageConfig {
+            backend: StorageBackend::S3,
+            options: StorageOptions {
+                bucket: Some(s3.bucket),
+                region: s3.region,
+                access_key_id: s3.access_key_id,
+                secret_access_key: s3.secret_access_key,
+                endpoint: s3.endpoint,
+                ..StorageOptions::default()
+            },
+        });
+        self
+    }
+
+    /// Set `logging.log_level`.
+    pub fn log_level(mut self, level: LogLevel) -> Self {
+        self.logging
+            .get_or_insert_with(LoggingConfig::default)
+            .log_level = level;
+        self
+    }
+
+    /// Register `[services.<name>]` implemented by `module`.
+    pub fn add_service(mut self, name: &str, module: &str, roles: &[ServiceRole]) -> Self {
+        let service = ServiceType {
+            id: None,
+            module: module.to_owned(),
+            roles: roles.to_vec(),
+        };
+        self.services.push((name.to_owned(), service));
+        self
+    }
+
+    /// Render the config as TOML, returning the validation errors instead if
+    /// the result does not satisfy the schema.
+    pub fn build(self) -> Result<String, Vec<ValidationError>> {
+        let proxy = ProxySettings {
+            id: self.proxy_id,
+            name: self.proxy_name,
+            ..defaults()
+        };
+
+        let mut doc = Table::new();
+        doc.insert("proxy".to_owned(), section(&proxy));
+        if !self.networks.is_empty() {
+            doc.insert("network".to_owned(), named(&self.networks));
+        }
+        if let Some(logging) = &self.logging {
+            doc.insert("logging".to_owned(), section(logging));
+        }
+        if let Some(storage) = &self.storage {
+            doc.insert("storage".to_owned(), section(storage));
+        }
+        if !self.services.is_empty() {
+            doc.insert("services".to_owned(), named(&self.services));
+        }
+
+        let doc = Value::Table(doc);
+        validate_value(SchemaKind::Config, &doc)?;
+        Ok(toml::to_string(&doc).expect("config tables serialize to TOML"))
+    }
+}
+
+/// A model struct with every field at its schema default.
+fn defaults<T: serde::de::DeserializeOwned>() -> T {
+    toml::from_str("").expect("every field of the table has a default")
+}
+
+fn section<T: serde::Serialize>(value: &T) -> Value {
+    Value::try_from(value).expect("model structs serialize to TOML tables")
+}
+
+fn named<T: serde::Serialize>(entries: &[(String, T)]) -> Value {
+    let table = entries
+        .iter()
+        .map(|(name, value)| (name.clone(), section(value)))
+        .collect();
+    Value::Table(table)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::parse_config;
+    use crate::validate::ValidationErrorKind;
+
+    #[test]
+    fn builds_a_valid_config() {
+        let toml = ConfigBuilder::new()
+            .proxy_id("edge-1")
+            .add_http_listener("default", HttpListener::new("127.0.0.1", 9090))
+            .storage_s3(S3Config {
+                bucket: "harmony".to_owned(),
+                region: Some("ap-southeast-2".to_owned()),
+                ..S3Config::default()
+            })
+            .log_level(LogLevel::Info)
+            .add_service("http", "", &[ServiceRole::Endpoint, ServiceRole::Backend])
+            .build()
+            .unwrap();
+
+        let config = parse_config(&toml).unwrap();
+        assert_eq!(config.proxy.id.as_deref(), Some("edge-1"));
+        let listener = config.network["default"].tcp_config.as_ref().unwrap();
+        assert_eq!(listener.bind_port, 9090);
+        assert_eq!(config.storage.backend, StorageBackend::S3);
+        assert_eq!(config.storage.options.bucket.as_deref(), Some("harmony"));
+        assert_eq!(config.logging.log_level, LogLevel::Info);
+    }
+
+    #[test]
+    fn invalid_configs_are_rejected() {
+        let errors = ConfigBuilder::new()
+            .proxy_id("Edge GW")
+            .build()
+            .unwrap_err();
+        assert!(matches!(
+            errors[0].kind,
+            ValidationErrorKind::PatternMismatch { .. }
+        ));
+    }
+}
@@ -0,0 +1,229 @@
+//! Every kind of diagnostic in one pass.
+//!
+//! Validation, [lints](crate::lint) and style checks each report in their
+//! own type. [`check`] runs them all over a document and returns one list of
+//! [`Finding`]s, so an editor or CI step makes a single call and a single
+//! decision about what to show or fail on. The style checks need the
+//! `schema-config` feature; without it [`check`] reports validation and
+//! lints only.
+
+use core::fmt;
+
+#[cfg(feature = "schema-config")]
+use toml::Table;
+
+#[cfg(feature = "schema-config")]
+use crate::defaults::explicit_defaults;
+use crate::fix::Fix;
+use crate::lint::{lint_with, LintLevel, LintOptions};
+use crate::prelude::*;
+#[cfg(feature = "schema-config")]
+use crate::span::SpanIndex;
+use crate::validate::{validate_report_with, write_diagnostic, Severity, ValidationOptions};
+use crate::SchemaKind;
+
+/// One diagnostic from [`check`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Finding {
+    /// How serious the finding is, or [`Style`](FindingCategory::Style)
+    /// for a style check.
+    pub category: FindingCategory,
+    /// The [`ErrorCode`](crate::ErrorCode) string of a validation
+    /// diagnostic, e.g. `E_UNKNOWN_KEY`, or the code of a lint or style
+    /// check, e.g. `inline_secret`.
+    pub code: &'static str,
+    /// Dotted path to the offending value.
+    pub path: String,
+    /// Human-readable description of the problem.
+    pub message: String,
+    /// 1-based `(line, column)` of the value in the original TOML.
+    pub span: Option<(usize, usize)>,
+    /// An edit that resolves the finding, if it has an obvious remedy; see
+    /// [`apply_fixes`](crate::apply_fixes).
+    pub fix: Option<Fix>,
+}
+
+/// What kind of problem a [`Finding`] is, from most to least serious.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum FindingCategory {
+    /// The document is invalid.
+    Error,
+    /// The document is accepted, but something is likely a mistake or a
+    /// security risk.
+    Warning,
+    /// Worth knowing, e.g. that a security-relevant default applies.
+    Info,
+    /// The document can be written more plainly without changing what it
+    /// means.
+    Style,
+}
+
+/// Settings for [`check`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CheckOptions {
+    /// How the document is validated.
+    pub validation: ValidationOptions,
+    /// Which lints run.
+    pub lints: LintOptions,
+    /// Whether to run the style checks. On by default; without the
+    /// `schema-config` feature there are none to run.
+    pub style: bool,
+}
+
+impl Default for CheckOptions {
+    fn default() -> Self {
+        Self {
+            validation: ValidationOptions::default(),
+            lints: LintOptions::default(),
+            style: true,
+        }
+    }
+}
+
+/// Formats as `line:column: path: message [code]`.
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_diagnostic(f, None::<&str>, self.span, &self.path, &self.message)?;
+        write!(f, " [{}]", self.code)
+    }
+}
+
+/// Code of the style check for fields spelled out with their schema default.
+#[cfg(feature = "schema-config")]
+const EXPLICIT_DEFAULT: &str = "explicit_default";
+
+/// Validate, lint and style-check `toml_input`, a document of `kind`, and
+/// return every finding in document order.
+///
+/// Validation diagnostics keep their [`Severity`] as their category and
+/// their fixes; lints are warnings or infos by their [`LintLevel`]. The
+/// style checks flag fields set to exactly their schema default, which can
+/// be left out, unless they are security-relevant. Documents that are not
+/// valid TOML get only the validation error saying so.
+pub fn check(kind: SchemaKind, toml_input: &str, options: CheckOptions) -> Vec<Finding> {
+    let mut findings: Vec<Finding> = validate_report_with(kind, toml_input, options.validation)
+        .into_diagnostics()
+        .into_iter()
+        .map(|error| Finding {
+            category: match error.severity {
+                Severity::Error => FindingCategory::Error,
+                Severity::Warning => FindingCategory::Warning,
+                Severity::Info => FindingCategory::Info,
+            },
+            code: error.code.as_str(),
+            path: error.path,
+            message: error.message,
+            span: error.span,
+            fix: error.fix,
+        })
+        .collect();
+    findings.extend(
+        lint_with(kind, toml_input, options.lints)
+            .into_iter()
+            .map(|lint| Finding {
+                category: match lint.level {
+                    LintLevel::Warning => FindingCategory::Warning,
+                    LintLevel::Info => FindingCategory::Info,
+                },
+                code: lint.lint_code,
+                path: lint.path,
+                message: lint.message,
+                span: lint.span,
+                fix: None,
+            }),
+    );
+    #[cfg(feature = "schema-config")]
+    if options.style {
+        if let Ok(doc) = toml::from_str::<Table>(toml_input) {
+            let spans = SpanIndex::build(toml_input);
+            findings.extend(
+                explicit_defaults(kind, &doc)
+                    .into_iter()
+                    .map(|path| Finding {
+                        category: FindingCategory::Style,
+                        code: EXPLICIT_DEFAULT,
+                        message: format!("`{path}` is set to its default and can be left out"),
+                        span: spans.as_ref().and_then(|spans| spans.position(&path)),
+                        path,
+                        fix: None,
+                    }),
+            );
+        }
+    }
+    findings.sort_by(|a, b| (a.span, &a.path).cmp(&(b.span, &b.path)));
+    findings
+}
+
+#[cfg(all(test, any(feature = "schema-config", feature = "schema-pipeline")))]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(feature = "schema-config")]
+    fn findings_of_every_category_come_back_together() {
+        use crate::fix::FixAction;
+
+        let config = "[proxy]\nid = \"gw\"\n\n[logging]\nlog_levl = \"info\"\n\n\
+                      [network.default.tcp_config]\nbind_address = \"0.0.0.0\"\n\
+                      bind_port = 8080\nforce_https = false\n";
+        let findings = check(SchemaKind::Config, config, CheckOptions::default());
+        let found: Vec<_> = findings
+            .iter()
+            .map(|finding| (finding.category, finding.code, finding.path.as_str()))
+            .collect();
+        assert!(found.contains(&(FindingCategory::Error, "E_UNKNOWN_KEY", "logging.log_levl")));
+        assert!(found.contains(&(
+            FindingCategory::Warning,
+            "bind_all_without_tls",
+            "network.default.tcp_config.bind_address"
+        )));
+        assert!(found.contains(&(
+            FindingCategory::Style,
+            "explicit_default",
+            "network.default.tcp_config.bind_address"
+        )));
+        // Security-relevant fields are worth spelling out.
+        assert!(!found
+            .iter()
+            .any(|(_, code, path)| *code == "explicit_default"
+                && *path == "network.default.tcp_config.force_https"));
+
+        let typo = &findings[0];
+        assert_eq!(typo.path, "logging.log_levl");
+        assert_eq!(
+            typo.fix.as_ref().map(|fix| &fix.action),
+            Some(&FixAction::RenameKey {
+                to: "log_level".to_owned()
+            })
+        );
+        let style = findings
+            .iter()
+            .find(|finding| finding.category == FindingCategory::Style)
+            .unwrap();
+        assert_eq!(style.fix, None);
+        assert_eq!(style.span, Some((8, 16)));
+
+        let options = CheckOptions {
+            style: false,
+            ..CheckOptions::default()
+        };
+        assert!(check(SchemaKind::Config, config, options)
+            .iter()
+            .all(|finding| finding.category != FindingCategory::Style));
+    }
+
+    #[test]
+    #[cfg(feature = "schema-pipeline")]
+    fn pipelines_are_checked() {
+        let pipeline = "[pipelines.api]\nendpoints = [\"ep\"]\nbackends = [\"be\"]\nbogus = 1\n";
+        let findings = check(SchemaKind::Pipeline, pipeline, CheckOptions::default());
+        assert!(
+            findings
+                .iter()
+                .any(|finding| finding.category == FindingCategory::Error
+                    && finding.code == "E_UNKNOWN_KEY"
+                    && finding.path == "pipelines.api.bogus"),
+            "{findings:?}"
+        );
+    }
+}
@@ -0,0 +1,253 @@
+//! Checking the schema version a document declares.
+//!
+//! A document may set a top-level `schema_version = "1.11.0"` naming the
+//! schema it was written against. [`check_compatibility`] compares it to the
+//! bundled schema so the proxy can refuse configs from a future release.
+//! Documents that declare nothing can be classified with
+//! [`detect_schema_version`], which tries several schema versions.
+
+use semver::Version;
+use toml::{Table, Value};
+
+use crate::prelude::*;
+use crate::schema::{parse_schema, schema_version, SchemaParseError};
+use crate::validate::{report_against, ValidationOptions};
+use crate::SchemaKind;
+
+/// The top-level key holding a document's declared schema version. The
+/// validator accepts it in every kind of document.
+pub(crate) const VERSION_KEY: &str = "schema_version";
+
+/// The top-level key naming a fragment merged beneath a document when its
+/// deployment directory is validated. Like [`VERSION_KEY`], the validator
+/// accepts it in every kind of document.
+pub(crate) const INCLUDE_KEY: &str = "include";
+
+/// How a document's declared `schema_version` relates to the bundled schema.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Compatibility {
+    /// The document does not declare a `schema_version`.
+    Unversioned,
+    /// Same major version as the bundled schema, and not newer.
+    Compatible { declared: Version },
+    /// Written for an older major version, whose layout may have changed; see
+    /// [`migrate_config`](crate::migrate_config).
+    MajorMismatch {
+        declared: Version,
+        supported: Version,
+    },
+    /// Written for a newer schema than this crate bundles.
+    NewerThanSupported {
+        declared: Version,
+        supported: Version,
+    },
+    /// The document is not valid TOML, or its `schema_version` is not a
+    /// semver string.
+    Unreadable(String),
+}
+
+impl Compatibility {
+    /// Whether the document can be used with the bundled schema as is.
+    pub fn is_compatible(&self) -> bool {
+        matches!(
+            self,
+            Compatibility::Unversioned | Compatibility::Compatible { .. }
+        )
+    }
+}
+
+/// How well a document fits each candidate of [`detect_schema_version`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct DetectionResult {
+    /// The candidate the document has the fewest errors against, the newest
+    /// one on a tie. `None` if no candidate schema parses.
+    pub best: Option<Version>,
+    /// Each candidate in the order given, with the number of errors the
+    /// document has against it, or why its schema does not parse.
+    pub candidates: Vec<(Version, Result<usize, SchemaParseError>)>,
+}
+
+impl DetectionResult {
+    /// Whether the document is valid against the [`best`](Self::best)
+    /// candidate.
+    pub fn is_valid(&self) -> bool {
+        self.best.as_ref().is_some_and(|best| {
+            self.candidates
+                .iter()
+                .any(|(version, errors)| version == best && errors == &Ok(0))
+        })
+    }
+}
+
+/// Validate `toml_input` against each candidate schema, given as a version
+/// and the schema's source, and pick the one it fits best, e.g. to classify
+/// a legacy config that declares no `schema_version`.
+///
+/// Validation is the same as [`validate_with_schema`](crate::validate_with_schema);
+/// only errors count, not warnings.
+pub fn detect_schema_version(toml_input: &str, candidates: &[(Version, &str)]) -> DetectionResult {
+    let candidates: Vec<(Version, Result<usize, SchemaParseError>)> = candidates
+        .iter()
+        .map(|(version, schema_src)| {
+            let errors = parse_schema(schema_src).map(|schema| {
+                report_against(&schema, toml_input, ValidationOptions::default(), None)
+                    .errors()
+                    .count()
+            });
+            (version.clone(), errors)
+        })
+        .collect();
+    let best = candidates
+        .iter()
+        .filter_map(|(version, errors)| Some((errors.as_ref().ok()?, version)))
+        .min_by(|(a, a_version), (b, b_version)| a.cmp(b).then(b_version.cmp(a_version)))
+        .map(|(_, version)| version.clone());
+    DetectionResult { best, candidates }
+}
+
+/// Compare the `schema_version` declared by `toml_input` with the bundled
+/// schema for `kind`, using semver rules: any version up to the bundled one
+/// with the same major version is compatible.
+pub fn check_compatibility(kind: SchemaKind, toml_input: &str) -> Compatibility {
+    let doc: Table = match toml::from_str(toml_input) {
+        Ok(doc) => doc,
+        Err(err) => return Compatibility::Unreadable(err.message().to_owned()),
+    };
+    let declared = match doc.get(VERSION_KEY) {
+        None => return Compatibility::Unversioned,
+        Some(Value::String(version)) => match Version::parse(version) {
+            Ok(version) => version,
+            Err(err) => {
+                return Compatibility::Unreadable(format!(
+                    "`{VERSION_KEY}` is not a semver version: {err}"
+                ))
+            }
+        },
+        Some(_) => {
+            return Compatibility::Unreadable(format!("`{VERSION_KEY}` must be a string"));
+        }
+    };
+
+    let supported = schema_version(kind);
+    if declared > supported {
+        Compatibility::NewerThanSupported {
+            declared,
+            supported,
+        }
+    } else if declared.major != supported.major {
+        Compatibility::MajorMismatch {
+            declared,
+            supported,
+        }
+    } else {
+        Compatibility::Compatible { declared }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "schema-config")]
+    use crate::validate_config;
+
+    #[cfg(feature = "schema-config")]
+    fn check(declared: &str) -> Compatibility {
+        let input = format!("schema_version = \"{declared}\"\n\n[proxy]\nid = \"gw\"\n");
+        check_compatibility(SchemaKind::Config, &input)
+    }
+
+    #[test]
+    #[cfg(feature = "schema-config")]
+    fn declared_versions_are_compared_with_semver() {
+        let supported = schema_version(SchemaKind::Config);
+        assert_eq!(
+            check("1.10.2"),
+            Compatibility::Compatible {
+                declared: Version::new(1, 10, 2)
+            }
+        );
+        assert_eq!(
+            check(&supported.to_string()),
+            Compatibility::Compatible {
+                declared: supported.clone()
+            }
+        );
+        assert_eq!(
+            check("0.9.0"),
+            Compatibility::MajorMismatch {
+                declared: Version::new(0, 9, 0),
+                supported: supported.clone(),
+            }
+        );
+        assert_eq!(
+            check("1.99.0"),
+            Compatibility::NewerThanSupported {
+                declared: Version::new(1, 99, 0),
+                supported: supported.clone(),
+            }
+        );
+        assert!(matches!(
+            check("2.0.0"),
+            Compatibility::NewerThanSupported { .. }
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "schema-config")]
+    fn missing_or_malformed_versions() {
+        let unversioned = check_compatibility(SchemaKind::Config, "[proxy]\nid = \"gw\"\n");
+        assert_eq!(unversioned, Compatibility::Unversioned);
+        assert!(unversioned.is_compatible());
+
+        assert!(matches!(check("1.x"), Compatibility::Unreadable(_)));
+        assert!(matches!(
+            check_compatibility(SchemaKind::Config, "schema_version = 1\n"),
+            Compatibility::Unreadable(_)
+        ));
+    }
+
+    #[test]
+    fn the_best_fitting_candidate_is_detected() {
+        const V1: &str = "[schema]\nversion = \"1.0.0\"\n\n[[table]]\nname = \"proxy\"\n\n\
+                          [[table.field]]\nname = \"id\"\ntype = \"string\"\n\n\
+                          [[table.field]]\nname = \"legacy_mode\"\ntype = \"boolean\"\n";
+        const V2: &str = "[schema]\nversion = \"2.0.0\"\n\n[[table]]\nname = \"proxy\"\n\n\
+                          [[table.field]]\nname = \"id\"\ntype = \"string\"\n\n\
+                          [[table.field]]\nname = \"name\"\ntype = \"string\"\nrequired = true\n";
+        let (v1, v2) = (Version::new(1, 0, 0), Version::new(2, 0, 0));
+        let candidates = [
+            (v1.clone(), V1),
+            (v2.clone(), V2),
+            (Version::new(3, 0, 0), "[["),
+        ];
+
+        let legacy = "[proxy]\nid = \"gw\"\nlegacy_mode = true\n";
+        let detected = detect_schema_version(legacy, &candidates);
+        assert_eq!(detected.best, Some(v1.clone()));
+        assert!(detected.is_valid());
+        assert_eq!(detected.candidates[0], (v1.clone(), Ok(0)));
+        // `legacy_mode` is unknown and `name` is missing.
+        assert_eq!(detected.candidates[1], (v2.clone(), Ok(2)));
+        assert!(detected.candidates[2].1.is_err());
+
+        // Ties go to the newest version.
+        let mixed = "[proxy]\nid = \"gw\"\nname = \"gw\"\nlegacy_mode = true\n";
+        let tied = detect_schema_version(mixed, &candidates);
+        assert_eq!(tied.candidates[0].1, Ok(1));
+        assert_eq!(tied.candidates[1].1, Ok(1));
+        assert_eq!(tied.best, Some(v2));
+        assert!(!tied.is_valid());
+
+        assert_eq!(detect_schema_version(legacy, &[]).best, None);
+    }
+
+    #[test]
+    #[cfg(feature = "schema-config")]
+    fn the_version_key_is_accepted_by_the_validator() {
+        assert_eq!(
+            validate_config("schema_version = \"1.11.0\"\n\n[proxy]\nid = \"gw\"\n"),
+            Ok(())
+        );
+        assert!(validate_config("schema_version = 1\n\n[proxy]\nid = \"gw\"\n").is_err());
+    }
+}
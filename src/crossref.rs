@@ -0,0 +1,641 @@
+//! Checks that span more than one file.
+//!
+//! Per-file validation can't tell whether a pipeline's `service = "fhir"`
+//! names a service type the gateway actually registers; that requires reading
+//! the config and the pipelines together. Nor can it tell whether a
+//! pipeline's endpoints are defined in another pipeline file, or whether the
+//! ingresses and egresses a mesh links are defined by any pipeline, or
+//! whether the providers pipelines and meshes name are configured, or
+//! whether the proxies deployed from one bundle each have their own id.
+
+use alloc::collections::BTreeSet;
+use core::fmt;
+
+use toml::{Table, Value};
+
+use crate::prelude::*;
+use crate::span::SpanIndex;
+use crate::suggest::closest;
+use crate::validate::{element, parse_error, Severity, ValidationError, ValidationErrorKind};
+#[cfg(all(feature = "std", feature = "schema-config"))]
+use crate::{validate_report, SchemaKind, ValidationReport};
+
+/// A reference that could not be resolved across files.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CrossRefError {
+    /// Index into the `pipelines` slice of the file containing the problem, or
+    /// `None` when the problem is in the config file itself.
+    pub pipeline: Option<usize>,
+    /// The underlying error. Its `path` and `span` are relative to that file.
+    pub error: ValidationError,
+}
+
+/// Formats as `pipeline <index>: <error>`, or just the error for the config.
+impl fmt::Display for CrossRefError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(pipeline) = self.pipeline {
+            write!(f, "pipeline {pipeline}: ")?;
+        }
+        write!(f, "{}", self.error)
+    }
+}
+
+impl core::error::Error for CrossRefError {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+/// Validate references from pipeline files into the gateway config.
+///
+/// Every `service` named by `endpoints.*` and `backends.*` in the pipelines
+/// must be registered as a `services.*` table in `config`, and every
+/// `middleware.*.options.provider` must be a [provider](unknown_providers)
+/// the config defines.
+pub fn validate_cross_references(
+    config: &str,
+    pipelines: &[&str],
+) -> Result<(), Vec<CrossRefError>> {
+    let mut errors = Vec::new();
+
+    let config_doc = match parse(config) {
+        Ok(doc) => doc,
+        Err(error) => {
+            return Err(vec![CrossRefError {
+                pipeline: None,
+                error: *error,
+            }])
+        }
+    };
+    let services = table_names(&config_doc, "services");
+    let providers = providers(&config_doc);
+
+    for (index, source) in pipelines.iter().enumerate() {
+        let doc = match parse(source) {
+            Ok(doc) => doc,
+            Err(error) => {
+                errors.push(CrossRefError {
+                    pipeline: Some(index),
+                    error: *error,
+                });
+                continue;
+            }
+        };
+        let spans = SpanIndex::build(source);
+
+        for section in ["endpoints", "backends"] {
+            for (name, entry) in tables(&doc, section) {
+                let Some(service) = entry.get("service").and_then(Value::as_str) else {
+                    continue;
+                };
+                if services.contains(service) {
+                    continue;
+                }
+
+                let path = format!("{section}.{name}.service");
+                let suggestion = closest(service, services.iter().map(String::as_str));
+                let mut message =
+                    format!("service type `{service}` is not registered in the config");
+                if let Some(suggestion) = suggestion {
+                    message.push_str(&format!("; did you mean `{suggestion}`?"));
+                }
+                let span = spans.as_ref().and_then(|spans| spans.position(&path));
+                errors.push(CrossRefError {
+                    pipeline: Some(index),
+                    error: ValidationError::new(
+                        path,
+                        ValidationErrorKind::UnknownServiceType {
+                            reference: service.to_owned(),
+                            suggestion: suggestion.map(str::to_owned),
+                        },
+                        message,
+                    )
+                    .at(span),
+                });
+            }
+        }
+
+        for (name, middleware) in tables(&doc, "middleware") {
+            let Some(provider) = middleware
+                .get("options")
+                .and_then(|options| options.get("provider"))
+                .and_then(Value::as_str)
+            else {
+                continue;
+            };
+            let path = format!("middleware.{name}.options.provider");
+            if let Some(error) = unknown_provider(provider, &path, &providers, spans.as_ref()) {
+                errors.push(CrossRefError {
+                    pipeline: Some(index),
+                    error,
+                });
+            }
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+/// Find pipelines that no endpoint routes to.
+///
+/// A pipeline is reachable when its `endpoints` list names an `endpoints.*`
+/// table defined in any of `pipelines`. Each one that is not is reported as
+/// a [`Severity::Warning`] of kind
+/// [`UnreachablePipeline`](ValidationErrorKind::UnreachablePipeline).
+/// Files that fail to parse, and pipelines without an `endpoints` list, are
+/// left to per-file validation.
+pub fn unreachable_pipelines(pipelines: &[&str]) -> Vec<CrossRefError> {
+    let docs: Vec<Option<Table>> = pipelines
+        .iter()
+        .map(|source| toml::from_str(source).ok())
+        .collect();
+    let endpoints: BTreeSet<String> = docs
+        .iter()
+        .flatten()
+        .flat_map(|doc| table_names(doc, "endpoints"))
+        .collect();
+
+    let mut errors = Vec::new();
+    for (index, doc) in docs.iter().enumerate() {
+        let Some(doc) = doc else {
+            continue;
+        };
+        let spans = SpanIndex::build(pipelines[index]);
+        for (name, pipeline) in tables(doc, "pipelines") {
+            let Some(routes) = pipeline.get("endpoints").and_then(Value::as_array) else {
+                continue;
+            };
+            let mut routes = routes.iter().filter_map(Value::as_str).peekable();
+            if routes.peek().is_none() || routes.any(|route| endpoints.contains(route)) {
+                continue;
+            }
+
+            let path = format!("pipelines.{name}.endpoints");
+            let span = spans.as_ref().and_then(|spans| spans.position(&path));
+            errors.push(CrossRefError {
+                pipeline: Some(index),
+                error: ValidationError::new(
+                    path,
+                    ValidationErrorKind::UnreachablePipeline {
+                        pipeline: name.clone(),
+                    },
+                    format!(
+                        "pipeline `{name}` is unreachable: none of its endpoints are \
+                         defined in any pipeline file"
+                    ),
+                )
+                .at(span)
+                .with_severity(Severity::Warning),
+            });
+        }
+    }
+    errors
+}
+
+/// Find the local ingress and egress names in `meshes` that no pipeline in
+/// `pipelines` defines, each paired with the index of its mesh file.
+///
+/// A bare name in `mesh.*.ingress`, or one written `local.name.<name>`,
+/// must be a `pipelines.*.mesh.ingress.<name>` table or a
+/// `remote_ingress.<name>` entry in one of the `remote_ingresses`
+/// catalogues. An egress must be a `pipelines.*.mesh.egress.<name>` table.
+/// Each one that is not is reported as
+/// [`UnknownPipelineReference`](ValidationErrorKind::UnknownPipelineReference),
+/// with a suggestion when a defined name is similar. References resolved by
+/// a provider, such as `runbeam.id.<id>`, and files that fail to parse are
+/// not checked.
+pub fn unknown_pipeline_references(
+    meshes: &[&str],
+    pipelines: &[&str],
+    remote_ingresses: &[&str],
+) -> Vec<(usize, ValidationError)> {
+    let docs: Vec<Table> = pipelines
+        .iter()
+        .filter_map(|source| toml::from_str(source).ok())
+        .collect();
+    let defined = |direction: &str| -> BTreeSet<String> {
+        docs.iter()
+            .flat_map(|doc| tables(doc, "pipelines"))
+            .filter_map(|(_, pipeline)| pipeline.get("mesh")?.get(direction)?.as_table())
+            .flat_map(|points| points.keys().cloned())
+            .collect()
+    };
+    let mut ingresses = defined("ingress");
+    for source in remote_ingresses {
+        if let Ok(catalogue) = toml::from_str::<Table>(source) {
+            ingresses.extend(table_names(&catalogue, "remote_ingress"));
+        }
+    }
+    let defined = [("ingress", ingresses), ("egress", defined("egress"))];
+
+    let mut errors = Vec::new();
+    for (index, source) in meshes.iter().enumerate() {
+        let Ok(doc) = toml::from_str::<Table>(source) else {
+            continue;
+        };
+        let spans = SpanIndex::build(source);
+        for (name, mesh) in tables(&doc, "mesh") {
+            for (direction, names) in &defined {
+                let references = mesh.get(*direction).and_then(Value::as_array);
+                for (item, reference) in references.into_iter().flatten().enumerate() {
+                    let Some(reference) = reference.as_str() else {
+                        continue;
+                    };
+                    let local = match reference.strip_prefix("local.name.") {
+                        Some(local) => local,
+                        None if !reference.contains('.') => reference,
+                        None => continue,
+                    };
+                    if names.contains(local) {
+                        continue;
+                    }
+
+                    let path = element(&format!("mesh.{name}.{direction}"), item);
+                    let suggestion = closest(local, names.iter().map(String::as_str));
+                    let definers = match *direction {
+                        "ingress" => "any pipeline or remote ingress catalogue",
+                        _ => "any pipeline",
+                    };
+                    let mut message = format!("{direction} `{local}` is not defined by {definers}");
+                    if let Some(suggestion) = suggestion {
+                        message.push_str(&format!("; did you mean `{suggestion}`?"));
+                    }
+                    let span = spans.as_ref().and_then(|spans| spans.position(&path));
+                    errors.push((
+                        index,
+                        ValidationError::new(
+                            path,
+                            ValidationErrorKind::UnknownPipelineReference {
+                                reference: local.to_owned(),
+                                suggestion: suggestion.map(str::to_owned),
+                            },
+                            message,
+                        )
+                        .at(span),
+                    ));
+                }
+            }
+        }
+    }
+    errors
+}
+
+/// Check the providers meshes name against the ones `config` defines.
+///
+/// A provider is defined if it is `local`, which is implicit, a
+/// `provider.*` table, or `runbeam` while the deprecated `[runbeam]` table
+/// configures it. Each `mesh.*.provider`, and the provider of each
+/// `mesh.*.ingress` and `mesh.*.egress` reference resolved by one, such as
+/// `runbeam` in `runbeam.id.<id>`, that is not defined is reported as
+/// [`UnknownProvider`](ValidationErrorKind::UnknownProvider) with the index
+/// of its mesh file, suggesting a defined provider when one is similar.
+/// Files that fail to parse are left to per-file validation.
+pub fn unknown_providers(config: &str, meshes: &[&str]) -> Vec<(usize, ValidationError)> {
+    let Ok(config) = toml::from_str::<Table>(config) else {
+        return Vec::new();
+    };
+    let providers = providers(&config);
+
+    let mut errors = Vec::new();
+    for (index, source) in meshes.iter().enumerate() {
+        let Ok(doc) = toml::from_str::<Table>(source) else {
+            continue;
+        };
+        let spans = SpanIndex::build(source);
+        for (name, mesh) in tables(&doc, "mesh") {
+            let mut references = Vec::new();
+            if let Some(provider) = mesh.get("provider").and_then(Value::as_str) {
+                references.push((format!("mesh.{name}.provider"), provider));
+            }
+            for direction in ["ingress", "egress"] {
+                let items = mesh.get(direction).and_then(Value::as_array);
+                for (item, reference) in items.into_iter().flatten().enumerate() {
+                    if let Some((provider, _)) = reference.as_str().and_then(|r| r.split_once('.'))
+                    {
+                        let path = element(&format!("mesh.{name}.{direction}"), item);
+                        references.push((path, provider));
+                    }
+                }
+            }
+            for (path, provider) in references {
+                if let Some(error) = unknown_provider(provider, &path, &providers, spans.as_ref()) {
+                    errors.push((index, error));
+                }
+            }
+        }
+    }
+    errors
+}
+
+/// Validate the gateway configs of a deployment bundle, given as
+/// `(file name, contents)` pairs, and check that no two set the same
+/// `proxy.id`.
+///
+/// Every config is validated on its own, with [`ValidationError::file`] set
+/// to its name. Each config that repeats an earlier one's id is reported as
+/// [`DuplicateProxyId`](ValidationErrorKind::DuplicateProxyId) naming both
+/// files.
+#[cfg(all(feature = "std", feature = "schema-config"))]
+pub fn validate_bundle(configs: &[(&str, &str)]) -> Result<(), Vec<ValidationError>> {
+    use std::collections::btree_map::{BTreeMap, Entry};
+    use std::path::PathBuf;
+
+    let mut errors = Vec::new();
+    let mut ids: BTreeMap<String, &str> = BTreeMap::new();
+    for &(name, source) in configs {
+        let file = PathBuf::from(name);
+        let report = validate_report(SchemaKind::Config, source);
+        errors.extend(
+            report
+                .into_diagnostics()
+                .into_iter()
+                .map(|error| ValidationError {
+                    file: Some(file.clone()),
+                    ..error
+                }),
+        );
+
+        let Ok(doc) = toml::from_str::<Table>(source) else {
+            continue;
+        };
+        let id = doc.get("proxy").and_then(|proxy| proxy.get("id"));
+        let Some(id) = id.and_then(Value::as_str) else {
+            continue;
+        };
+        match ids.entry(id.to_owned()) {
+            Entry::Vacant(entry) => {
+                entry.insert(name);
+            }
+            Entry::Occupied(first) => {
+                let span = SpanIndex::build(source).and_then(|spans| spans.position("proxy.id"));
+                let mut error = ValidationError::new(
+                    "proxy.id",
+                    ValidationErrorKind::DuplicateProxyId {
+                        id: id.to_owned(),
+                        first_file: (*first.get()).to_owned(),
+                    },
+                    format!("proxy id `{id}` is already used by `{}`", first.get()),
+                )
+                .at(span);
+                error.file = Some(file.clone());
+                errors.push(error);
+            }
+        }
+    }
+    ValidationReport::new(errors).into_result()
+}
+
+fn parse(source: &str) -> Result<Table, Box<ValidationError>> {
+    toml::from_str(source).map_err(|err| Box::new(parse_error(source, &err)))
+}
+
+/// The `(name, table)` pairs of a wildcard section such as `services.*`.
+fn tables<'a>(doc: &'a Table, section: &str) -> impl Iterator<Item = (&'a String, &'a Table)> {
+    doc.get(section)
+        .and_then(Value::as_table)
+        .into_iter()
+        .flatten()
+        .filter_map(|(name, value)| value.as_table().map(|table| (name, table)))
+}
+
+/// The providers `config` defines, as described for [`unknown_providers`].
+fn providers(config: &Table) -> BTreeSet<String> {
+    let mut providers = table_names(config, "provider");
+    providers.insert("local".to_owned());
+    if config.get("runbeam").is_some_and(Value::is_table) {
+        providers.insert("runbeam".to_owned());
+    }
+    providers
+}
+
+/// An [`UnknownProvider`](ValidationErrorKind::UnknownProvider) error at
+/// `path` unless `provider` is one of `providers`.
+fn unknown_provider(
+    provider: &str,
+    path: &str,
+    providers: &BTreeSet<String>,
+    spans: Option<&SpanIndex<'_>>,
+) -> Option<ValidationError> {
+    if providers.contains(provider) {
+        return None;
+    }
+    let suggestion = closest(provider, providers.iter().map(String::as_str));
+    let mut message = format!("provider `{provider}` is not defined in the config");
+    match suggestion {
+        Some(suggestion) => message.push_str(&format!("; did you mean `{suggestion}`?")),
+        None => message.push_str(&format!("; define it in a `[provider.{provider}]` table")),
+    }
+    let span = spans.and_then(|spans| spans.position(path));
+    Some(
+        ValidationError::new(
+            path,
+            ValidationErrorKind::UnknownProvider {
+                reference: provider.to_owned(),
+                suggestion: suggestion.map(str::to_owned),
+            },
+            message,
+        )
+        .at(span),
+    )
+}
+
+fn table_names(doc: &Table, section: &str) -> BTreeSet<String> {
+    tables(doc, section).map(|(name, _)| name.clone()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"
+[proxy]
+id = "gateway"
+
+[services.http]
+module = ""
+
+[services.dicom]
+module = ""
+"#;
+
+    #[test]
+    fn registered_service_types_resolve() {
+        let pipeline = r#"
+[endpoints.api]
+service = "http"
+
+[backends.pacs]
+service = "dicom"
+"#;
+        assert_eq!(validate_cross_references(CONFIG, &[pipeline]), Ok(()));
+    }
+
+    #[test]
+    fn unknown_service_types_are_reported_with_a_suggestion() {
+        let ok = "[endpoints.api]\nservice = \"http\"\n";
+        let typo = "[backends.pacs]\nservice = \"dicon\"\n";
+        let errors = validate_cross_references(CONFIG, &[ok, typo]).unwrap_err();
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].pipeline, Some(1));
+        assert_eq!(errors[0].error.path, "backends.pacs.service");
+        assert_eq!(errors[0].error.span, Some((2, 11)));
+        assert_eq!(
+            errors[0].error.kind,
+            ValidationErrorKind::UnknownServiceType {
+                reference: "dicon".to_owned(),
+                suggestion: Some("dicom".to_owned()),
+            }
+        );
+    }
+
+    #[test]
+    fn referenced_providers_must_be_defined_in_the_config() {
+        let config = format!("{CONFIG}\n[provider.oidc]\napi = \"https://id.example.com\"\n");
+        let pipeline = |provider: &str| {
+            format!("[middleware.auth]\ntype = \"mesh_auth\"\n\n[middleware.auth.options]\nprovider = \"{provider}\"\n")
+        };
+        for defined in ["oidc", "local"] {
+            assert_eq!(
+                validate_cross_references(&config, &[&pipeline(defined)]),
+                Ok(())
+            );
+        }
+
+        let errors = validate_cross_references(&config, &[&pipeline("keycloak")]).unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].pipeline, Some(0));
+        assert_eq!(errors[0].error.path, "middleware.auth.options.provider");
+        assert_eq!(errors[0].error.span, Some((5, 12)));
+        assert_eq!(
+            errors[0].error.kind,
+            ValidationErrorKind::UnknownProvider {
+                reference: "keycloak".to_owned(),
+                suggestion: None,
+            }
+        );
+        assert_eq!(
+            errors[0].error.message,
+            "provider `keycloak` is not defined in the config; define it in a \
+             `[provider.keycloak]` table"
+        );
+
+        let mesh = "[mesh.hospital]\ntype = \"http\"\nprovider = \"runbeam\"\n\
+                    ingress = [\"local.name.imaging_in\", \"oidcc.id.7\"]\n";
+        let errors = unknown_providers(&config, &["[mesh.core]\nprovider = \"local\"\n", mesh]);
+        let found: Vec<_> = errors
+            .iter()
+            .map(|(file, error)| (*file, error.path.as_str(), error.kind.clone()))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                (
+                    1,
+                    "mesh.hospital.provider",
+                    ValidationErrorKind::UnknownProvider {
+                        reference: "runbeam".to_owned(),
+                        suggestion: None,
+                    }
+                ),
+                (
+                    1,
+                    "mesh.hospital.ingress[1]",
+                    ValidationErrorKind::UnknownProvider {
+                        reference: "oidcc".to_owned(),
+                        suggestion: Some("oidc".to_owned()),
+                    }
+                ),
+            ]
+        );
+        // The deprecated `[runbeam]` table still configures `runbeam`.
+        let legacy = format!("{config}\n[runbeam]\nenabled = true\n");
+        assert_eq!(unknown_providers(&legacy, &[mesh]).len(), 1);
+    }
+
+    #[test]
+    fn mesh_references_resolve_against_pipeline_ingresses() {
+        let pipeline = r#"
+[pipelines.imaging.mesh.ingress.imaging_in]
+type = "http"
+urls = ["https://gw.example.com/imaging"]
+
+[pipelines.imaging.mesh.egress.imaging_out]
+type = "http"
+"#;
+        let mesh = r#"
+[mesh.hospital]
+type = "http"
+provider = "local"
+ingress = ["imaging_in", "local.name.imaging-in", "runbeam.id.42", "partner_gw"]
+egress = ["imaging_out"]
+"#;
+        let catalogue = "[remote_ingress.partner_gw]\nurls = [\"https://partner.example.com\"]\n";
+        let errors = unknown_pipeline_references(&[mesh], &[pipeline], &[catalogue]);
+        assert_eq!(errors.len(), 1, "{errors:?}");
+        let (file, error) = &errors[0];
+        assert_eq!(*file, 0);
+        assert_eq!(error.path, "mesh.hospital.ingress[1]");
+        assert_eq!(error.span, Some((5, 26)));
+        assert_eq!(
+            error.kind,
+            ValidationErrorKind::UnknownPipelineReference {
+                reference: "imaging-in".to_owned(),
+                suggestion: Some("imaging_in".to_owned()),
+            }
+        );
+        assert_eq!(
+            error.message,
+            "ingress `imaging-in` is not defined by any pipeline or remote ingress catalogue; \
+             did you mean `imaging_in`?"
+        );
+    }
+
+    #[test]
+    fn unparsable_files_are_reported_not_skipped() {
+        let errors = validate_cross_references("[proxy", &[]).unwrap_err();
+        assert_eq!(errors[0].pipeline, None);
+        assert_eq!(errors[0].error.kind, ValidationErrorKind::InvalidToml);
+    }
+
+    #[cfg(all(feature = "std", feature = "schema-config"))]
+    #[test]
+    fn bundled_proxies_need_their_own_ids() {
+        let edge = "[proxy]\nid = \"edge\"\n";
+        let core = "[proxy]\nid = \"core\"\n";
+        assert_eq!(
+            validate_bundle(&[("edge.toml", edge), ("core.toml", core)]),
+            Ok(())
+        );
+
+        let copy = "# copied from edge.toml\n[proxy]\nid = \"edge\"\n";
+        let errors = validate_bundle(&[("edge.toml", edge), ("copy.toml", copy)]).unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(
+            errors[0].file.as_deref(),
+            Some(std::path::Path::new("copy.toml"))
+        );
+        assert_eq!(errors[0].path, "proxy.id");
+        assert_eq!(errors[0].span, Some((3, 6)));
+        assert_eq!(
+            errors[0].kind,
+            ValidationErrorKind::DuplicateProxyId {
+                id: "edge".to_owned(),
+                first_file: "edge.toml".to_owned(),
+            }
+        );
+        assert!(errors[0].message.contains("edge.toml"));
+
+        // Per-file errors are attributed to their file.
+        let broken = "[proxy]\nid = 7\n";
+        let errors = validate_bundle(&[("edge.toml", edge), ("broken.toml", broken)]).unwrap_err();
+        assert_eq!(
+            errors[0].file.as_deref(),
+            Some(std::path::Path::new("broken.toml"))
+        );
+    }
+}
@@ -0,0 +1,280 @@
+//! Filling in schema defaults.
+//!
+//! [`apply_defaults`] returns a validated document in which every field the
+//! schema gives a `default` is set, so runtime code can read it without
+//! handling absence. [`effective_overrides`] goes the other way, listing the
+//! fields a document sets to something other than their default.
+
+use toml::{Table, Value};
+
+use crate::model::ConfigError;
+use crate::prelude::*;
+use crate::schema::{bundled_schema, expand_references, Condition, FieldRule, Schema};
+use crate::validate::{instances, join, validate};
+use crate::SchemaKind;
+
+/// Validate `toml_input` against the bundled schema for `kind`, then set
+/// every absent field that declares a `default`.
+///
+/// Defaults are applied inside every instance of a wildcard table, but
+/// never create one. Missing tables are created to hold defaults unless
+/// their presence is meaningful: wildcard instances, deprecated tables, and
+/// tables a `required_if` tests for existence (e.g. `tcp_config`, whose
+/// presence enables the HTTP listener) are left absent.
+///
+/// Templated defaults such as `"{proxy.id}.log"` are filled in last, once
+/// the fields they refer to are known, and skipped where one of those is
+/// unset or not a string, number or boolean. Resolving `${NAME}`
+/// environment placeholders is out of scope: like those in the fields a
+/// document sets, any in a default are left as written for the runtime to
+/// expand at startup, and
+/// [`validate_env_placeholders`](crate::validate_env_placeholders) can check
+/// the result against an environment.
+pub fn apply_defaults(kind: SchemaKind, toml_input: &str) -> Result<Value, ConfigError> {
+    validate(kind, toml_input).map_err(|errors| ConfigError::Invalid(errors.into()))?;
+    let mut doc: Table = toml::from_str(toml_input)
+        .map_err(|err| ConfigError::Deserialize(err.message().to_owned()))?;
+    fill_defaults(kind, &mut doc);
+    Ok(Value::Table(doc))
+}
+
+/// The fields of `toml_input`, a document of `kind`, that are set to a value
+/// other than their schema `default`, as concrete dotted paths (e.g.
+/// `network.default.tcp_config.bind_port`) sorted by path, with their values.
+///
+/// Fields without a declared default are never listed, since there is
+/// nothing to differ from; neither is anything in a document that is not
+/// valid TOML. The document is not otherwise validated.
+pub fn effective_overrides(kind: SchemaKind, toml_input: &str) -> Vec<(String, Value)> {
+    let Ok(doc) = toml::from_str::<Table>(toml_input) else {
+        return Vec::new();
+    };
+    compare_defaults(kind, &doc, false)
+}
+
+/// The fields of `doc` set to exactly their schema `default`, which could
+/// be omitted, as concrete dotted paths sorted by path. Security-relevant
+/// fields are never listed, since spelling them out records the choice.
+pub(crate) fn explicit_defaults(kind: SchemaKind, doc: &Table) -> Vec<String> {
+    compare_defaults(kind, doc, true)
+        .into_iter()
+        .map(|(path, _)| path)
+        .collect()
+}
+
+/// The fields of `doc` whose value equals their default if `equal`, or
+/// differs from it if not, sorted by path.
+fn compare_defaults(kind: SchemaKind, doc: &Table, equal: bool) -> Vec<(String, Value)> {
+    let schema = bundled_schema(kind);
+    let mut found = Vec::new();
+    for rule in &schema.fields {
+        if equal && rule.security_relevant {
+            continue;
+        }
+        let Some(default) = resolved_default(rule, doc) else {
+            continue;
+        };
+        let default = &default;
+        let segments: Vec<&str> = rule.segments().collect();
+        let Some((key, parents)) = segments.split_last() else {
+            continue;
+        };
+        for (path, table) in instances(doc, parents, "") {
+            if let Some(value) = table.get(*key).filter(|value| (*value == default) == equal) {
+                found.push((join(&path, key), value.clone()));
+            }
+        }
+    }
+    found.sort_by(|(a, _), (b, _)| a.cmp(b));
+    found
+}
+
+/// Set every absent field of the already validated `doc` that declares a
+/// `default`, as described for [`apply_defaults`].
+pub(crate) fn fill_defaults(kind: SchemaKind, doc: &mut Table) {
+    let schema = bundled_schema(kind);
+    for rule in &schema.fields {
+        let Some(default) = &rule.default else {
+            continue;
+        };
+        if !rule.default_references().is_empty() {
+            continue;
+        }
+        let segments: Vec<&str> = rule.segments().collect();
+        fill(&schema, doc, &segments, 0, default);
+    }
+    // The bundled schemas parsed, so their templated defaults are acyclic.
+    for rule in schema.templated_defaults().unwrap_or_default() {
+        let Some(default) = resolved_default(rule, doc) else {
+            continue;
+        };
+        let segments: Vec<&str> = rule.segments().collect();
+        fill(&schema, doc, &segments, 0, &default);
+    }
+}
+
+/// The default of `rule`, with any references filled in from `doc`.
+pub(crate) fn resolved_default(rule: &FieldRule, doc: &Table) -> Option<Value> {
+    match rule.default.as_ref()? {
+        Value::String(template) => {
+            let resolved = expand_references(template, |path| {
+                let mut value = doc.get(path.split('.').next()?)?;
+                for key in path.split('.').skip(1) {
+                    value = value.get(key)?;
+                }
+                match value {
+                    Value::String(text) => Some(text.clone()),
+                    Value::Integer(_) | Value::Float(_) | Value::Boolean(_) => {
+                        Some(value.to_string())
+                    }
+                    _ => None,
+                }
+            })?;
+            Some(Value::String(resolved))
+        }
+        default => Some(default.clone()),
+    }
+}
+
+/// Set `segments[depth..]` beneath `table` to `default` where absent.
+fn fill(schema: &Schema, table: &mut Table, segments: &[&str], depth: usize, default: &Value) {
+    let key = segments[depth];
+    if depth + 1 == segments.len() {
+        if key != "*" && !table.contains_key(key) {
+            table.insert(key.to_owned(), default.clone());
+        }
+        return;
+    }
+
+    if key == "*" {
+        for child in table
+            .iter_mut()
+            .filter_map(|(_, child)| child.as_table_mut())
+        {
+            fill(schema, child, segments, depth + 1, default);
+        }
+        return;
+    }
+    if !table.contains_key(key) {
+        if !may_create(schema, &segments[..=depth]) {
+            return;
+        }
+        table.insert(key.to_owned(), Value::Table(Table::new()));
+    }
+    if let Some(child) = table.get_mut(key).and_then(Value::as_table_mut) {
+        fill(schema, child, segments, depth + 1, default);
+    }
+}
+
+/// Whether the table at the schema path `segments` may be created to hold
+/// defaults.
+fn may_create(schema: &Schema, segments: &[&str]) -> bool {
+    let path = segments.join(".");
+    if schema
+        .find(segments)
+        .is_some_and(|rule| rule.deprecation.is_some())
+    {
+        return false;
+    }
+    let tested_for_presence = schema.fields.iter().any(|rule| match &rule.required_if {
+        Some(Condition::Exists { field } | Condition::Missing { field }) => {
+            format!("{}.{field}", rule.table) == path
+        }
+        _ => false,
+    });
+    !tested_for_presence
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn omitted_fields_get_their_defaults() {
+        let input = r#"
+[proxy]
+id = "gw"
+
+[network.default.tcp_config]
+bind_port = 9090
+
+[network.internal]
+"#;
+        let config = apply_defaults(SchemaKind::Config, input).unwrap();
+        assert_eq!(config["logging"]["log_level"].as_str(), Some("error"));
+        assert_eq!(
+            config["proxy"]["content_limits"]["max_body_size"].as_integer(),
+            Some(10_485_760)
+        );
+
+        let listener = &config["network"]["default"]["tcp_config"];
+        assert_eq!(listener["bind_port"].as_integer(), Some(9090));
+        assert_eq!(listener["bind_address"].as_str(), Some("0.0.0.0"));
+
+        // Presence of `tcp_config` and `http3` enables a listener, and
+        // `[runbeam]` is deprecated, so neither is conjured up.
+        let internal = config["network"]["internal"].as_table().unwrap();
+        assert_eq!(internal["interface"].as_str(), Some("wg0"));
+        assert!(!internal.contains_key("tcp_config"));
+        assert!(!internal.contains_key("http3"));
+        assert!(config.get("runbeam").is_none());
+        assert!(config.get("services").is_none());
+    }
+
+    #[test]
+    fn templated_defaults_are_filled_in_from_the_document() {
+        let input = "[proxy]\nid = \"edge-gw\"\n\n[logging]\nlog_to_file = true\n";
+        let config = apply_defaults(SchemaKind::Config, input).unwrap();
+        assert_eq!(
+            config["logging"]["log_file_path"].as_str(),
+            Some("edge-gw.log")
+        );
+
+        let input = "[proxy]\nid = \"edge-gw\"\n\n[logging]\nlog_file_path = \"edge-gw.log\"\n";
+        assert!(effective_overrides(SchemaKind::Config, input).is_empty());
+    }
+
+    #[test]
+    fn only_fields_differing_from_their_default_are_overrides() {
+        let input = r#"
+[proxy]
+id = "gw"
+pipelines_path = "pipelines"
+
+[logging]
+log_level = "debug"
+
+[network.default.tcp_config]
+bind_address = "0.0.0.0"
+bind_port = 8080
+"#;
+        assert_eq!(
+            effective_overrides(SchemaKind::Config, input),
+            [("logging.log_level".to_owned(), Value::from("debug"))]
+        );
+        assert!(effective_overrides(SchemaKind::Config, "[logging\n").is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "schema-remote-ingress")]
+    fn invalid_documents_are_rejected() {
+        assert!(matches!(
+            apply_defaults(SchemaKind::Config, "[logging]\n"),
+            Err(ConfigError::Invalid(_))
+        ));
+        assert!(matches!(
+            apply_defaults(SchemaKind::RemoteIngress, "[remote_ingress.a]\n"),
+            Err(ConfigError::Invalid(_))
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "schema-remote-ingress")]
+    fn remote_ingress_catalogues_without_defaults_are_unchanged() {
+        let input = "[remote_ingress.hospital_a]\nurls = [\"https://a.example.com\"]\n";
+        assert_eq!(
+            apply_defaults(SchemaKind::RemoteIngress, input).unwrap(),
+            Value::Table(toml::from_str(input).unwrap())
+        );
+    }
+}
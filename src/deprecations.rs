@@ -0,0 +1,82 @@
+//! An upgrade-readiness report of the deprecated fields a document uses.
+//!
+//! Validation reports deprecated fields among everything else, as errors or
+//! warnings depending on the [`ValidationMode`](crate::ValidationMode).
+//! [`deprecations_in_use`] lists only them, with what to use instead, so an
+//! operator can plan the changes an upgrade needs before the fields are
+//! removed.
+
+use toml::Table;
+
+use crate::prelude::*;
+use crate::schema::bundled_schema;
+use crate::validate::{instances, join};
+use crate::SchemaKind;
+
+/// A deprecated table or field set in a document, from
+/// [`deprecations_in_use`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeprecationNotice {
+    /// Dotted path in the document, e.g. `runbeam`.
+    pub path: String,
+    /// The schema's guidance, from `deprecated = "..."`.
+    pub message: String,
+    /// The path to use instead, from `replaced_by = "..."`, if the schema
+    /// names one.
+    pub replacement: Option<String>,
+}
+
+/// Every deprecated table and field `toml_input`, a document of `kind`, sets,
+/// in schema order. Input that is not valid TOML lists none; the document is
+/// not otherwise validated, so the report is available for configs that
+/// still fail validation.
+pub fn deprecations_in_use(kind: SchemaKind, toml_input: &str) -> Vec<DeprecationNotice> {
+    let Ok(doc) = toml::from_str::<Table>(toml_input) else {
+        return Vec::new();
+    };
+    let schema = bundled_schema(kind);
+    let mut notices = Vec::new();
+    for rule in &schema.fields {
+        let Some(message) = &rule.deprecation else {
+            continue;
+        };
+        let segments: Vec<&str> = rule.path.split('.').collect();
+        let Some((key, parent)) = segments.split_last() else {
+            continue;
+        };
+        for (path, table) in instances(&doc, parent, "") {
+            let used = table.keys().filter(|present| *key == "*" || present == key);
+            notices.extend(used.map(|present| DeprecationNotice {
+                path: join(&path, present),
+                message: message.clone(),
+                replacement: rule.replacement.clone(),
+            }));
+        }
+    }
+    notices
+}
+
+#[cfg(all(test, feature = "schema-config"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deprecated_fields_in_use_are_listed_with_their_replacement() {
+        let config = "[proxy]\nid = \"gw\"\n\n[runbeam]\nenabled = true\n";
+        assert_eq!(
+            deprecations_in_use(SchemaKind::Config, config),
+            [DeprecationNotice {
+                path: "runbeam".to_owned(),
+                message: "use [provider.runbeam] and proxy.primary_provider instead \
+                          (see migrate_config)"
+                    .to_owned(),
+                replacement: Some("provider.runbeam".to_owned()),
+            }]
+        );
+
+        let current = "[proxy]\nid = \"gw\"\nprimary_provider = \"runbeam\"\n\n\
+                       [provider.runbeam]\napi = \"https://api.runbeam.io\"\n";
+        assert!(deprecations_in_use(SchemaKind::Config, current).is_empty());
+        assert!(deprecations_in_use(SchemaKind::Config, "[runbeam").is_empty());
+    }
+}
@@ -0,0 +1,605 @@
+//! Structural comparison of two versions of a schema.
+//!
+//! A textual diff of a schema file mixes reworded descriptions with real
+//! contract changes. [`diff_schemas`] compares the parsed rules instead and
+//! reports only the changes that affect which configs are accepted.
+//! [`suggest_migration`] turns those changes into steps for config authors,
+//! e.g. for release notes.
+
+use alloc::collections::{BTreeMap, BTreeSet};
+use core::fmt;
+
+use toml::Value;
+
+use crate::prelude::*;
+use crate::scaffold::placeholder;
+use crate::schema::{parse_schema, FieldRule, FieldType, SchemaParseError};
+
+/// Structural differences between two schema versions, each list sorted by path.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SchemaDiff {
+    /// Rules present only in the new schema.
+    pub added: Vec<FieldRule>,
+    /// Rules present only in the old schema.
+    pub removed: Vec<FieldRule>,
+    /// Rules whose declared type changed.
+    pub type_changed: Vec<TypeChange>,
+    /// Rules whose `required` flag changed.
+    pub required_changed: Vec<RequiredChange>,
+    /// Entries of the `[enumerations]`, `[protocol_compatibility]`,
+    /// `[middleware_order]` and `[middleware_versions]` tables that were
+    /// added, removed or changed, sorted by table and key.
+    pub list_changed: Vec<ListChange>,
+}
+
+/// A rule whose declared type differs between versions.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TypeChange {
+    /// Dotted path of the rule.
+    pub path: String,
+    /// The type in the old schema.
+    pub old: FieldType,
+    /// The type in the new schema.
+    pub new: FieldType,
+}
+
+/// A rule whose `required` flag differs between versions.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RequiredChange {
+    /// Dotted path of the rule.
+    pub path: String,
+    /// Whether the old schema requires it.
+    pub old: bool,
+    /// Whether the new schema requires it.
+    pub new: bool,
+}
+
+/// An entry of one of the schema's tables of string lists, such as
+/// `[middleware_order]`, that differs between versions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListChange {
+    /// The table, e.g. `middleware_order`.
+    pub table: String,
+    /// The entry's key, e.g. `jwt_auth`.
+    pub key: String,
+    /// The entry's list in the old schema, `None` if it had no entry.
+    pub old: Option<Vec<String>>,
+    /// The entry's list in the new schema, `None` if it has no entry.
+    pub new: Option<Vec<String>>,
+}
+
+impl ListChange {
+    /// Whether the change may reject configs the old schema accepted. An
+    /// entry of `[middleware_order]` adds constraints, so gaining a value
+    /// breaks; the other tables list what is accepted, where a missing
+    /// entry accepts anything, so adding an entry or dropping a value
+    /// breaks.
+    pub fn is_breaking(&self) -> bool {
+        let (old, new) = (self.old.as_deref(), self.new.as_deref());
+        if self.table == "middleware_order" {
+            return new
+                .unwrap_or_default()
+                .iter()
+                .any(|value| !old.unwrap_or_default().contains(value));
+        }
+        match (old, new) {
+            (_, None) => false,
+            (None, Some(_)) => true,
+            (Some(old), Some(new)) => old.iter().any(|value| !new.contains(value)),
+        }
+    }
+}
+
+impl SchemaDiff {
+    /// Whether nothing structural changed.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.removed.is_empty()
+            && self.type_changed.is_empty()
+            && self.required_changed.is_empty()
+            && self.list_changed.is_empty()
+    }
+
+    /// Whether a config valid under the old schema may be rejected by the new
+    /// one: a rule was removed, changed type, or became required, or a
+    /// [`ListChange`] is breaking.
+    pub fn is_breaking(&self) -> bool {
+        !self.removed.is_empty()
+            || !self.type_changed.is_empty()
+            || self.required_changed.iter().any(|change| change.new)
+            || self.list_changed.iter().any(ListChange::is_breaking)
+    }
+}
+
+/// Compare two schema documents rule by rule.
+pub fn diff_schemas(old: &str, new: &str) -> Result<SchemaDiff, SchemaParseError> {
+    let (old, new) = (parse_schema(old)?, parse_schema(new)?);
+    let mut diff = SchemaDiff::default();
+    let tables = [
+        ("enumerations", &old.enumerations, &new.enumerations),
+        (
+            "middleware_order",
+            &old.middleware_order,
+            &new.middleware_order,
+        ),
+        (
+            "middleware_versions",
+            &old.middleware_versions,
+            &new.middleware_versions,
+        ),
+        (
+            "protocol_compatibility",
+            &old.protocol_compatibility,
+            &new.protocol_compatibility,
+        ),
+    ];
+    for (table, old, new) in tables {
+        let keys: BTreeSet<&String> = old.keys().chain(new.keys()).collect();
+        for key in keys {
+            let (old, new) = (old.get(key), new.get(key));
+            if old != new {
+                diff.list_changed.push(ListChange {
+                    table: table.to_owned(),
+                    key: key.clone(),
+                    old: old.cloned(),
+                    new: new.cloned(),
+                });
+            }
+        }
+    }
+
+    let old = by_path(old.fields);
+    let new = by_path(new.fields);
+
+    for (path, old_rule) in &old {
+        let Some(new_rule) = new.get(path) else {
+            diff.removed.push(old_rule.clone());
+            continue;
+        };
+        if old_rule.ty != new_rule.ty {
+            diff.type_changed.push(TypeChange {
+                path: path.clone(),
+                old: old_rule.ty,
+                new: new_rule.ty,
+            });
+        }
+        if old_rule.required != new_rule.required {
+            diff.required_changed.push(RequiredChange {
+                path: path.clone(),
+                old: old_rule.required,
+                new: new_rule.required,
+            });
+        }
+    }
+    diff.added = new
+        .into_iter()
+        .filter(|(path, _)| !old.contains_key(path))
+        .map(|(_, rule)| rule)
+        .collect();
+
+    Ok(diff)
+}
+
+/// A step that brings a config in line with a new schema version, from
+/// [`suggest_migration`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct MigrationHint {
+    /// Path of the affected rule in the old schema, with `*` for wildcard
+    /// segments, e.g. `network.*.tcp_config.bind_port`. For added rules,
+    /// the path in the new schema.
+    pub path: String,
+    /// What changed.
+    pub kind: MigrationHintKind,
+    /// The step to take, e.g. ``rename `proxy.timeout` to `proxy.timeout_secs` ``.
+    pub message: String,
+}
+
+/// The schema change a [`MigrationHint`] responds to.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum MigrationHintKind {
+    /// The rule is gone; configs must drop it.
+    Removed,
+    /// The rule was removed and a rule of the same type added beside it.
+    Renamed {
+        /// Path of the rule in the new schema.
+        to: String,
+    },
+    /// The rule is new and required, or became required.
+    NowRequired {
+        /// A value to add: the rule's default, or else a placeholder of its
+        /// type as [`scaffold`](crate::scaffold) would emit.
+        value: Value,
+    },
+    /// The rule's declared type changed.
+    TypeChanged {
+        /// The type in the old schema.
+        old: FieldType,
+        /// The type in the new schema, which values must be converted to.
+        new: FieldType,
+    },
+}
+
+/// Formats as the hint's message.
+impl fmt::Display for MigrationHint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+/// The steps a config valid under the `old` schema takes to stay valid under
+/// the `new` one, sorted by path: drop removed fields, rename moved ones,
+/// add newly required ones, convert retyped ones.
+///
+/// Schemas do not record renames, so one is inferred when a field is removed
+/// and a single field of the same type is added under the same parent
+/// (preferring one with the same description), unless another removed field
+/// would be renamed to the same one. Removed and added tables are
+/// reported once, not field by field; changes that only widen what is
+/// accepted, such as new optional fields, need no step.
+pub fn suggest_migration(old: &str, new: &str) -> Result<Vec<MigrationHint>, SchemaParseError> {
+    let diff = diff_schemas(old, new)?;
+    let new_rules = by_path(parse_schema(new)?.fields);
+    let removed = outermost(&diff.removed);
+    let added = outermost(&diff.added);
+
+    let targets: Vec<Option<&FieldRule>> = removed
+        .iter()
+        .map(|old_rule| rename_target(old_rule, &added))
+        .collect();
+    let mut claims: BTreeMap<&str, usize> = BTreeMap::new();
+    for to in targets.iter().flatten() {
+        *claims.entry(to.path.as_str()).or_default() += 1;
+    }
+
+    let mut hints = Vec::new();
+    let mut renamed = BTreeSet::new();
+    for (old_rule, to) in removed.into_iter().zip(targets) {
+        let to = to.filter(|to| claims[to.path.as_str()] == 1);
+        let Some(to) = to else {
+            hints.push(MigrationHint {
+                path: old_rule.path.clone(),
+                kind: MigrationHintKind::Removed,
+                message: format!(
+                    "remove `{}`: the new schema no longer accepts it",
+                    old_rule.path
+                ),
+            });
+            continue;
+        };
+        renamed.insert(to.path.as_str());
+        hints.push(MigrationHint {
+            path: old_rule.path.clone(),
+            kind: MigrationHintKind::Renamed {
+                to: to.path.clone(),
+            },
+            message: format!("rename `{}` to `{}`", old_rule.path, to.path),
+        });
+    }
+
+    let newly_added = added
+        .iter()
+        .copied()
+        .filter(|rule| rule.required && !renamed.contains(rule.path.as_str()));
+    let became_required = diff
+        .required_changed
+        .iter()
+        .filter(|change| change.new)
+        .filter_map(|change| new_rules.get(&change.path));
+    for rule in newly_added.chain(became_required) {
+        let value = rule.default.clone().unwrap_or_else(|| placeholder(rule));
+        let message = match &value {
+            Value::Table(_) => format!("`{}` is now required; add the table", rule.path),
+            value => format!(
+                "`{}` is now required; add it with a value such as `{} = {value}`",
+                rule.path,
+                rule.name()
+            ),
+        };
+        hints.push(MigrationHint {
+            path: rule.path.clone(),
+            kind: MigrationHintKind::NowRequired { value },
+            message,
+        });
+    }
+
+    for change in &diff.type_changed {
+        hints.push(MigrationHint {
+            path: change.path.clone(),
+            kind: MigrationHintKind::TypeChanged {
+                old: change.old,
+                new: change.new,
+            },
+            message: format!(
+                "change `{}` from {} to {}",
+                change.path,
+                change.old.as_str(),
+                change.new.as_str()
+            ),
+        });
+    }
+
+    hints.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(hints)
+}
+
+/// The rules of `rules` not nested under another of them: the fields of a
+/// removed table go with it, and those of an added table are only needed
+/// once the table is.
+fn outermost(rules: &[FieldRule]) -> Vec<&FieldRule> {
+    let paths: BTreeSet<&str> = rules.iter().map(|rule| rule.path.as_str()).collect();
+    rules
+        .iter()
+        .filter(|rule| ancestors(&rule.path).all(|ancestor| !paths.contains(ancestor)))
+        .collect()
+}
+
+/// The added rule `removed` was presumably renamed to: the only added rule
+/// of the same type under the same parent, or the only such rule with the
+/// same description.
+fn rename_target<'a>(removed: &FieldRule, added: &[&'a FieldRule]) -> Option<&'a FieldRule> {
+    let same_slot: Vec<&FieldRule> = added
+        .iter()
+        .copied()
+        .filter(|rule| parent(&rule.path) == parent(&removed.path) && rule.ty == removed.ty)
+        .collect();
+    match same_slot[..] {
+        [only] => Some(only),
+        _ => {
+            let mut described = same_slot
+                .into_iter()
+                .filter(|rule| rule.description == removed.description);
+            match (described.next(), described.next()) {
+                (Some(only), None) => Some(only),
+                _ => None,
+            }
+        }
+    }
+}
+
+/// The parent path of `path`, empty for top-level rules.
+fn parent(path: &str) -> &str {
+    path.rsplit_once('.').map_or("", |(parent, _)| parent)
+}
+
+/// Every proper ancestor of `path`, outermost first.
+fn ancestors(path: &str) -> impl Iterator<Item = &str> {
+    path.match_indices('.').map(|(end, _)| &path[..end])
+}
+
+/// Index rules by path. Where a schema declares a path twice, the first
+/// declaration wins, matching how the validator resolves rules.
+fn by_path(fields: Vec<FieldRule>) -> BTreeMap<String, FieldRule> {
+    let mut map = BTreeMap::new();
+    for rule in fields {
+        map.entry(rule.path.clone()).or_insert(rule);
+    }
+    map
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OLD: &str = r#"
+[[table]]
+name = "proxy"
+required = true
+
+[[table.field]]
+name = "id"
+type = "string"
+required = false
+
+[[table.field]]
+name = "legacy_mode"
+type = "boolean"
+
+[[table.field]]
+name = "timeout"
+type = "string"
+"#;
+
+    const NEW: &str = r#"
+[[table]]
+name = "proxy"
+required = true
+
+[[table.field]]
+name = "id"
+type = "string"
+required = true
+
+[[table.field]]
+name = "timeout"
+type = "integer"
+
+[[table.field]]
+name = "region"
+type = "string"
+"#;
+
+    #[test]
+    fn reports_removed_added_and_changed_rules() {
+        let diff = diff_schemas(OLD, NEW).unwrap();
+
+        let paths = |rules: &[FieldRule]| rules.iter().map(|r| r.path.clone()).collect::<Vec<_>>();
+        assert_eq!(paths(&diff.removed), ["proxy.legacy_mode"]);
+        assert_eq!(paths(&diff.added), ["proxy.region"]);
+        assert_eq!(
+            diff.required_changed,
+            [RequiredChange {
+                path: "proxy.id".to_owned(),
+                old: false,
+                new: true,
+            }]
+        );
+        assert_eq!(
+            diff.type_changed,
+            [TypeChange {
+                path: "proxy.timeout".to_owned(),
+                old: FieldType::String,
+                new: FieldType::Integer,
+            }]
+        );
+        assert!(diff.is_breaking());
+    }
+
+    #[test]
+    #[cfg(feature = "schema-config")]
+    fn identical_schemas_have_an_empty_diff() {
+        let diff = diff_schemas(crate::CONFIG_SCHEMA, crate::CONFIG_SCHEMA).unwrap();
+        assert!(diff.is_empty());
+        assert!(!diff.is_breaking());
+    }
+
+    #[test]
+    fn adding_an_optional_field_is_not_breaking() {
+        let diff =
+            diff_schemas(NEW, &format!("{NEW}\n[[table.field]]\nname = \"zone\"\n")).unwrap();
+        assert_eq!(diff.added.len(), 1);
+        assert!(!diff.is_breaking());
+    }
+
+    #[test]
+    fn changed_list_tables_are_reported() {
+        let old = format!("[middleware_order]\njwt_auth = [\"transform\"]\n{OLD}");
+        let new = format!(
+            "[middleware_order]\njwt_auth = [\"transform\", \"webhook\"]\n\n\
+             [middleware_versions]\ntransform = [\"1\"]\n{OLD}"
+        );
+        let diff = diff_schemas(&old, &new).unwrap();
+        assert_eq!(
+            diff.list_changed,
+            [
+                ListChange {
+                    table: "middleware_order".to_owned(),
+                    key: "jwt_auth".to_owned(),
+                    old: Some(vec!["transform".to_owned()]),
+                    new: Some(vec!["transform".to_owned(), "webhook".to_owned()]),
+                },
+                ListChange {
+                    table: "middleware_versions".to_owned(),
+                    key: "transform".to_owned(),
+                    old: None,
+                    new: Some(vec!["1".to_owned()]),
+                },
+            ]
+        );
+        assert!(diff.list_changed.iter().all(ListChange::is_breaking));
+        assert!(diff.is_breaking());
+
+        // Dropping the constraints only widens what is accepted.
+        let relaxed = diff_schemas(&new, &old).unwrap();
+        assert!(!relaxed.is_empty());
+        assert!(!relaxed.is_breaking());
+    }
+
+    #[test]
+    fn migration_hints_follow_renames_and_new_requirements() {
+        let old = r#"
+[[table]]
+name = "proxy"
+
+[[table.field]]
+name = "id"
+type = "string"
+
+[[table.field]]
+name = "legacy_mode"
+type = "boolean"
+
+[[table.field]]
+name = "timeout"
+description = "Upstream timeout in seconds"
+"#;
+        let new = r#"
+[[table]]
+name = "proxy"
+
+[[table.field]]
+name = "id"
+type = "string"
+required = true
+
+[[table.field]]
+name = "timeout_secs"
+description = "Upstream timeout in seconds"
+
+[[table.field]]
+name = "zone"
+required = true
+default = "eu-1"
+
+[[table]]
+name = "tracing"
+required = true
+
+[[table.field]]
+name = "endpoint"
+required = true
+"#;
+        let hints = suggest_migration(old, new).unwrap();
+        let messages: Vec<String> = hints.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            messages,
+            [
+                "`proxy.id` is now required; add it with a value such as `id = \"\"`",
+                "remove `proxy.legacy_mode`: the new schema no longer accepts it",
+                "rename `proxy.timeout` to `proxy.timeout_secs`",
+                "`proxy.zone` is now required; add it with a value such as `zone = \"eu-1\"`",
+                "`tracing` is now required; add the table",
+            ]
+        );
+        assert_eq!(
+            hints[2].kind,
+            MigrationHintKind::Renamed {
+                to: "proxy.timeout_secs".to_owned()
+            }
+        );
+        assert_eq!(
+            hints[3].kind,
+            MigrationHintKind::NowRequired {
+                value: Value::String("eu-1".to_owned())
+            }
+        );
+
+        let retyped = suggest_migration(OLD, NEW).unwrap();
+        assert_eq!(
+            retyped.last().unwrap().message,
+            "change `proxy.timeout` from string to integer"
+        );
+        assert_eq!(suggest_migration(NEW, NEW), Ok(Vec::new()));
+    }
+
+    #[test]
+    fn a_field_two_removed_fields_could_become_is_not_a_rename() {
+        let old = r#"
+[[table]]
+name = "backend"
+
+[[table.field]]
+name = "host"
+
+[[table.field]]
+name = "address"
+"#;
+        let new = r#"
+[[table]]
+name = "backend"
+
+[[table.field]]
+name = "endpoint"
+"#;
+        let hints = suggest_migration(old, new).unwrap();
+        assert_eq!(
+            hints.iter().map(|hint| &hint.kind).collect::<Vec<_>>(),
+            [&MigrationHintKind::Removed, &MigrationHintKind::Removed]
+        );
+    }
+
+    #[test]
+    fn unparsable_schemas_are_errors() {
+        assert!(diff_schemas("[[table]", NEW).is_err());
+    }
+}
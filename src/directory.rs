@@ -0,0 +1,1411 @@
+//! Validation of a whole deployment directory.
+//!
+//! A gateway deployment is laid out by convention:
+//!
+//! ```text
+//! config.toml              gateway config (required)
+//! pipelines/*.toml         pipeline files (optional)
+//! mesh/*.toml              mesh files (optional)
+//! remote_ingress/*.toml    remote ingress catalogues (optional)
+//! ```
+//!
+//! [`validate_directory`] validates each file against its schema, runs the
+//! [cross-file checks](crate::crossref), and groups every error by file.
+//! Watch-mode tools that validate the same tree repeatedly can pass a
+//! [`ValidationCache`] to [`validate_directory_cached`] so unchanged files
+//! are not validated again. [`DirectoryReport::stats`] summarises what the
+//! deployment defines, for dashboards. With the `archive` feature,
+//! [`validate_archive`] validates a deployment shipped as one tar or zip in
+//! memory.
+//!
+//! Any file may set a top-level `include = "common.toml"`, naming a fragment
+//! relative to the file's own directory and inside the deployment. The
+//! fragment, with any fragments it includes in turn, is merged beneath the
+//! file, which wins where both set a value, and the assembled document is
+//! validated. Errors are reported against the including file, without a
+//! span where a fragment set the value.
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+use sha2::{Digest, Sha256};
+use toml::{Table, Value};
+
+use crate::compat::INCLUDE_KEY;
+use crate::crossref::{
+    unknown_pipeline_references, unknown_providers, unreachable_pipelines,
+    validate_cross_references,
+};
+use crate::document::value_at;
+use crate::merge::{merge_tables, MergeOptions};
+use crate::schema::bundled_schema;
+use crate::span::SpanIndex;
+use crate::validate::{
+    parse_error, report_parsed, validate_report, ValidationError, ValidationErrorKind,
+    ValidationOptions, ValidationReport,
+};
+use crate::SchemaKind;
+
+/// The result of validating a deployment directory.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DirectoryReport {
+    /// Every file that was found, config first, then pipeline, mesh and
+    /// remote ingress files, each sorted by name.
+    pub files: Vec<FileReport>,
+    /// Required files that do not exist. Missing or empty optional
+    /// directories (`pipelines/`, `mesh/`, `remote_ingress/`) are not listed.
+    pub missing: Vec<PathBuf>,
+    stats: DeploymentStats,
+}
+
+/// What a deployment defines, from [`DirectoryReport::stats`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DeploymentStats {
+    /// `pipelines.*` tables across the pipeline files.
+    pub pipelines: usize,
+    /// `endpoints.*` tables across the pipeline files.
+    pub endpoints: usize,
+    /// `backends.*` tables across the pipeline files.
+    pub backends: usize,
+    /// `mesh.*` tables across the mesh files.
+    pub meshes: usize,
+    /// `remote_ingress.*` tables across the remote ingress catalogues.
+    pub remote_ingresses: usize,
+    /// TCP and HTTP/3 listeners of the config's networks, counting a
+    /// network's `tcp_config` and `http3` tables as one each.
+    pub listeners: usize,
+    /// The distinct `service` types the endpoints and backends use.
+    pub service_types: BTreeSet<String>,
+}
+
+impl DeploymentStats {
+    /// Add what another file defines.
+    fn add(&mut self, other: DeploymentStats) {
+        let DeploymentStats {
+            pipelines,
+            endpoints,
+            backends,
+            meshes,
+            remote_ingresses,
+            listeners,
+            service_types,
+        } = other;
+        self.pipelines += pipelines;
+        self.endpoints += endpoints;
+        self.backends += backends;
+        self.meshes += meshes;
+        self.remote_ingresses += remote_ingresses;
+        self.listeners += listeners;
+        self.service_types.extend(service_types);
+    }
+}
+
+/// The errors found in one file of a deployment.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileReport {
+    /// Path of the file, joined onto the directory passed in.
+    pub path: PathBuf,
+    /// The schema the file was validated against.
+    pub kind: SchemaKind,
+    /// Schema and cross-reference errors, with paths and spans relative to
+    /// this file.
+    pub errors: Vec<ValidationError>,
+}
+
+impl DirectoryReport {
+    /// Whether every required file exists and no file has errors. Warnings
+    /// do not count.
+    pub fn is_ok(&self) -> bool {
+        self.missing.is_empty()
+            && self
+                .files
+                .iter()
+                .all(|file| file.errors.iter().all(|error| !error.is_error()))
+    }
+
+    /// Every error in the deployment as one [`ValidationReport`], with
+    /// [`ValidationError::file`] set to the file each was found in.
+    pub fn report(&self) -> ValidationReport {
+        let errors = self.files.iter().flat_map(|file| {
+            file.errors.iter().map(|error| ValidationError {
+                file: Some(file.path.clone()),
+                ..error.clone()
+            })
+        });
+        ValidationReport::new(errors.collect())
+    }
+
+    /// Counts of what the deployment defines, taken from the files as they
+    /// were read for validation. Files that are not valid TOML count for
+    /// nothing.
+    pub fn stats(&self) -> DeploymentStats {
+        self.stats.clone()
+    }
+}
+
+/// Per-file validation results from earlier runs of
+/// [`validate_directory_cached`], keyed by schema and a SHA-256 digest of the
+/// file's contents.
+///
+/// The cache is owned by the caller and lives in memory only. Each run keeps
+/// just the entries for the files it saw, so the cache never outgrows the
+/// tree.
+#[derive(Debug, Clone, Default)]
+pub struct ValidationCache {
+    entries: HashMap<(SchemaKind, [u8; 32]), (Vec<ValidationError>, DeploymentStats)>,
+    hits: usize,
+}
+
+impl ValidationCache {
+    /// An empty cache.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// How many files have been served from the cache rather than
+    /// validated, over every run so far.
+    pub fn hits(&self) -> usize {
+        self.hits
+    }
+
+    /// How many files' results are cached.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether no results are cached.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Forget every cached result.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+/// Validate the deployment rooted at `root`.
+///
+/// A file longer than [`ValidationOptions::DEFAULT_MAX_INPUT_BYTES`] is
+/// reported as [`InputTooLarge`](ValidationErrorKind::InputTooLarge) without
+/// being read whole.
+pub fn validate_directory(root: &Path) -> DirectoryReport {
+    let (files, missing) = discover(root);
+    let loaded = files
+        .into_iter()
+        .map(|(path, kind)| {
+            let source = read_file(&path);
+            load(root, path, kind, source, &read_file)
+        })
+        .collect();
+    assemble(loaded, missing)
+}
+
+/// [`validate_directory`], reusing `cache`'s results for files whose
+/// contents are unchanged since an earlier run. Cross-file checks always run
+/// in full, so the report is identical to the one [`validate_directory`]
+/// returns. Files with an `include` are validated on every run, since the
+/// fragments they include may have changed.
+///
+/// With the `fs-checks` feature, results also depend on the files a config
+/// names, which the cache cannot see change, so `cache` is left untouched
+/// and every file is validated.
+pub fn validate_directory_cached(root: &Path, cache: &mut ValidationCache) -> DirectoryReport {
+    if cfg!(feature = "fs-checks") {
+        return validate_directory(root);
+    }
+    let (files, missing) = discover(root);
+    let mut seen = HashSet::new();
+    let loaded = files
+        .into_iter()
+        .map(|(path, kind)| {
+            let source = match read_file(&path) {
+                Ok(source) => source,
+                Err(err) => return load(root, path, kind, Err(err), &read_file),
+            };
+            // Files with an `include` are never cached, so a hit is a file
+            // without one.
+            let key = (kind, Sha256::digest(&source).into());
+            if let Some((errors, stats)) = cache.entries.get(&key) {
+                seen.insert(key);
+                cache.hits += 1;
+                let errors = errors.clone();
+                return (
+                    FileReport { path, kind, errors },
+                    Some(source),
+                    stats.clone(),
+                );
+            }
+            let (loaded, included) = load_including(root, path, kind, Ok(source), &read_file);
+            if !included {
+                seen.insert(key);
+                cache
+                    .entries
+                    .insert(key, (loaded.0.errors.clone(), loaded.2.clone()));
+            }
+            loaded
+        })
+        .collect();
+    cache.entries.retain(|key, _| seen.contains(key));
+    assemble(loaded, missing)
+}
+
+/// [`validate_directory`] for async callers, reading and validating up to
+/// [`MAX_CONCURRENT_FILES`] files at a time. Validation runs on tokio's
+/// blocking thread pool, so this must be called within a tokio runtime.
+/// The report is identical to the one [`validate_directory`] returns.
+#[cfg(feature = "tokio")]
+pub async fn validate_directory_async(root: &Path) -> DirectoryReport {
+    validate_directory_streaming(root, |_| {}).await
+}
+
+/// [`validate_directory_async`], passing each file's report to `on_file` as
+/// soon as the file is validated, so callers can show results while the
+/// rest of a large deployment is still being read.
+///
+/// Files arrive in the order they finish, not report order, and carry only
+/// their own diagnostics: errors from the cross-file checks, which need
+/// every file, are only in the returned report.
+#[cfg(feature = "tokio")]
+pub async fn validate_directory_streaming(
+    root: &Path,
+    on_file: impl FnMut(&FileReport),
+) -> DirectoryReport {
+    stream_directory(root, |_| {}, on_file).await
+}
+
+/// [`validate_directory_streaming`], also calling `on_spawn` with the index
+/// of each file in report order as its validation starts.
+#[cfg(feature = "tokio")]
+async fn stream_directory(
+    root: &Path,
+    mut on_spawn: impl FnMut(usize),
+    mut on_file: impl FnMut(&FileReport),
+) -> DirectoryReport {
+    use std::sync::Arc;
+
+    use tokio::task::{spawn_blocking, JoinError, JoinSet};
+
+    let root = Arc::new(root.to_owned());
+    let listed = Arc::clone(&root);
+    let (files, missing) = spawn_blocking(move || discover(&listed))
+        .await
+        .expect("listing the deployment directory panicked");
+
+    let mut loaded: Vec<Option<Loaded>> = vec![None; files.len()];
+    let mut finish = |result: Result<(usize, Loaded), JoinError>| {
+        let (index, file) = result.expect("validating a file panicked");
+        on_file(&file.0);
+        loaded[index] = Some(file);
+    };
+    let mut tasks = JoinSet::new();
+    for (index, (path, kind)) in files.into_iter().enumerate() {
+        // Waiting for a slot hands over a finished file, so results stream
+        // out while the rest are still to be started.
+        if tasks.len() == MAX_CONCURRENT_FILES {
+            if let Some(result) = tasks.join_next().await {
+                finish(result);
+            }
+        }
+        let root = Arc::clone(&root);
+        on_spawn(index);
+        tasks.spawn_blocking(move || {
+            let source = read_file(&path);
+            (index, load(&root, path, kind, source, &read_file))
+        });
+    }
+    while let Some(result) = tasks.join_next().await {
+        finish(result);
+    }
+    assemble(loaded.into_iter().flatten().collect(), missing)
+}
+
+/// How many files [`validate_directory_async`] reads and validates at once.
+#[cfg(feature = "tokio")]
+pub const MAX_CONCURRENT_FILES: usize = 16;
+
+/// Validate a deployment shipped as one archive held in memory, without
+/// writing it to disk. `bytes` is a tar, gzipped tar or zip file.
+///
+/// Entries are mapped to schemas by the deployment layout, either at the top
+/// of the archive or all under one directory, as `tar -czf deploy.tgz
+/// deploy/` packs them; other entries are ignored. Only `.toml` entries are
+/// read, so included fragments must be `.toml` files too, and an entry
+/// longer than [`ValidationOptions::DEFAULT_MAX_INPUT_BYTES`] is reported as
+/// [`InputTooLarge`](ValidationErrorKind::InputTooLarge) without being
+/// decompressed further. Files are reported under their paths in the
+/// archive, and the cross-file checks run as for [`validate_directory`]. An
+/// archive that cannot be read is reported as an `Io` error on a file with
+/// an empty path.
+#[cfg(feature = "archive")]
+pub fn validate_archive(bytes: &[u8]) -> DirectoryReport {
+    let entries = match archive_entries(bytes) {
+        Ok(entries) => entries,
+        Err(err) => {
+            return assemble(
+                vec![load(
+                    Path::new(""),
+                    PathBuf::new(),
+                    SchemaKind::Config,
+                    Err(err),
+                    &|_| Err(io::ErrorKind::NotFound.into()),
+                )],
+                Vec::new(),
+            )
+        }
+    };
+
+    // A single top-level directory holding the whole deployment, unless it
+    // is one of the deployment's own directories.
+    let config = Path::new("config.toml");
+    let mut tops = entries.keys().filter_map(|path| path.components().next());
+    let root = match (entries.contains_key(config), tops.next()) {
+        (false, Some(top))
+            if tops.all(|other| other == top)
+                && !["pipelines", "mesh", "remote_ingress"]
+                    .contains(&top.as_os_str().to_str().unwrap_or("")) =>
+        {
+            PathBuf::from(top.as_os_str())
+        }
+        _ => PathBuf::new(),
+    };
+
+    let mut config_entry = None;
+    let mut pipelines = Vec::new();
+    let mut meshes = Vec::new();
+    let mut remote_ingresses = Vec::new();
+    // Fragments are looked up among the entries, never on disk.
+    let read_entry = |path: &Path| match entries.get(path) {
+        Some(Ok(source)) => Ok(source.clone()),
+        Some(Err(err)) => Err(io::Error::new(err.kind(), err.to_string())),
+        None => Err(io::Error::from(io::ErrorKind::NotFound)),
+    };
+    for (path, source) in &entries {
+        let Ok(relative) = path.strip_prefix(&root) else {
+            continue;
+        };
+        let source = match source {
+            Ok(source) => Ok(source.clone()),
+            Err(err) => Err(io::Error::new(err.kind(), err.to_string())),
+        };
+        let path = path.clone();
+        let dir = relative.parent().and_then(Path::to_str);
+        let is_toml = relative.extension().is_some_and(|ext| ext == "toml");
+        match dir {
+            _ if relative == config => config_entry = Some((path, SchemaKind::Config, source)),
+            Some("pipelines") if is_toml => pipelines.push((path, SchemaKind::Pipeline, source)),
+            Some("mesh") if is_toml => meshes.push((path, SchemaKind::Mesh, source)),
+            Some("remote_ingress") if is_toml => {
+                remote_ingresses.push((path, SchemaKind::RemoteIngress, source));
+            }
+            _ => {}
+        }
+    }
+
+    let missing = match config_entry {
+        Some(_) => Vec::new(),
+        None => vec![root.join(config)],
+    };
+    let loaded = config_entry
+        .into_iter()
+        .chain(pipelines)
+        .chain(meshes)
+        .chain(remote_ingresses)
+        .map(|(path, kind, source)| load(&root, path, kind, source, &read_entry))
+        .collect();
+    assemble(loaded, missing)
+}
+
+/// The regular `.toml` files in an archive by path, with leading `./`
+/// removed, and their contents read as UTF-8. An entry longer than
+/// [`ValidationOptions::DEFAULT_MAX_INPUT_BYTES`] is read no further and
+/// fails with [`io::ErrorKind::FileTooLarge`]. Of entries with the same
+/// path, the last wins, as when the archive is unpacked.
+#[cfg(feature = "archive")]
+fn archive_entries(
+    bytes: &[u8],
+) -> io::Result<std::collections::BTreeMap<PathBuf, io::Result<String>>> {
+    use std::io::{Cursor, Read};
+    use std::path::Component;
+
+    let mut entries = std::collections::BTreeMap::new();
+    let mut add = |path: &Path, reader: &mut dyn Read| {
+        // Entries whose paths escape the archive are never unpacked.
+        if !path
+            .components()
+            .all(|component| matches!(component, Component::Normal(_) | Component::CurDir))
+            || path.extension().is_none_or(|ext| ext != "toml")
+        {
+            return;
+        }
+        let path: PathBuf = path
+            .components()
+            .filter(|component| !matches!(component, Component::CurDir))
+            .collect();
+        entries.insert(path, read_capped(reader));
+    };
+
+    if bytes.starts_with(b"PK") {
+        let invalid = |err: zip::result::ZipError| io::Error::new(io::ErrorKind::InvalidData, err);
+        let mut archive = zip::ZipArchive::new(Cursor::new(bytes)).map_err(invalid)?;
+        for index in 0..archive.len() {
+            let mut file = archive.by_index(index).map_err(invalid)?;
+            if file.is_file() {
+                let path = PathBuf::from(file.name().map_err(invalid)?.into_owned());
+                add(&path, &mut file);
+            }
+        }
+        return Ok(entries);
+    }
+
+    let reader: Box<dyn Read + '_> = if bytes.starts_with(&[0x1f, 0x8b]) {
+        Box::new(flate2::read::GzDecoder::new(bytes))
+    } else {
+        Box::new(bytes)
+    };
+    for entry in tar::Archive::new(reader).entries()? {
+        let mut entry = entry?;
+        if entry.header().entry_type().is_file() {
+            let path = entry.path()?.into_owned();
+            add(&path, &mut entry);
+        }
+    }
+    Ok(entries)
+}
+
+/// Everything `reader` yields as UTF-8, reading no more than one byte past
+/// [`ValidationOptions::DEFAULT_MAX_INPUT_BYTES`].
+fn read_capped(reader: &mut dyn std::io::Read) -> io::Result<String> {
+    use std::io::Read;
+
+    let limit = ValidationOptions::DEFAULT_MAX_INPUT_BYTES;
+    let mut bytes = Vec::new();
+    reader.take(limit as u64 + 1).read_to_end(&mut bytes)?;
+    if bytes.len() > limit {
+        return Err(io::Error::new(
+            io::ErrorKind::FileTooLarge,
+            format!("the file is longer than the limit of {limit} bytes"),
+        ));
+    }
+    String::from_utf8(bytes).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+/// A file's report, its source if the file could be read, and what it
+/// defines.
+type Loaded = (FileReport, Option<String>, DeploymentStats);
+
+/// Reads an included fragment: from disk, or from an archive's entries.
+type ReadFragment<'a> = &'a dyn Fn(&Path) -> io::Result<String>;
+
+/// The file at `path`, read as by [`read_capped`].
+fn read_file(path: &Path) -> io::Result<String> {
+    read_capped(&mut fs::File::open(path)?)
+}
+
+/// The files of the deployment at `root` in report order, with their
+/// schemas, and the required files that do not exist.
+fn discover(root: &Path) -> (Vec<(PathBuf, SchemaKind)>, Vec<PathBuf>) {
+    let mut files = Vec::new();
+    let mut missing = Vec::new();
+
+    let config_path = root.join("config.toml");
+    if config_path.is_file() {
+        files.push((config_path, SchemaKind::Config));
+    } else {
+        missing.push(config_path);
+    }
+    for path in toml_files(&root.join("pipelines")) {
+        files.push((path, SchemaKind::Pipeline));
+    }
+    for path in toml_files(&root.join("mesh")) {
+        files.push((path, SchemaKind::Mesh));
+    }
+    for path in toml_files(&root.join("remote_ingress")) {
+        files.push((path, SchemaKind::RemoteIngress));
+    }
+    (files, missing)
+}
+
+/// Validate one file of the deployment at `root`, given the result of
+/// reading it, with the fragments it includes merged in. The cross-file
+/// checks read the config as assembled, so they see what its fragments
+/// define, and other files as written, since they locate errors in them.
+fn load(
+    root: &Path,
+    path: PathBuf,
+    kind: SchemaKind,
+    source: io::Result<String>,
+    read: ReadFragment<'_>,
+) -> Loaded {
+    load_including(root, path, kind, source, read).0
+}
+
+/// [`load`], and whether the file has an `include`, so that its report
+/// depends on more than its own contents.
+fn load_including(
+    root: &Path,
+    path: PathBuf,
+    kind: SchemaKind,
+    source: io::Result<String>,
+    read: ReadFragment<'_>,
+) -> (Loaded, bool) {
+    let mut included = false;
+    let mut stats = DeploymentStats::default();
+    let (source, errors) = match source {
+        Ok(source) => {
+            let mut assembled = None;
+            // Empty and invalid input are reported by `validate_report`.
+            let parsed = if source.trim().is_empty() {
+                None
+            } else {
+                toml::from_str::<Table>(&source).ok()
+            };
+            let schema = bundled_schema(kind);
+            let options = ValidationOptions::default();
+            let errors = match parsed {
+                Some(own) if own.contains_key(INCLUDE_KEY) => {
+                    included = true;
+                    let mut doc = own.clone();
+                    let mut errors = Vec::new();
+                    let mut chain = vec![normalize(&path)];
+                    merge_includes(&mut doc, &normalize(root), &mut chain, read, &mut errors);
+                    count(kind, &doc, &mut stats);
+                    let at =
+                        SpanIndex::build(&source).and_then(|spans| spans.position(INCLUDE_KEY));
+                    let mut report =
+                        report_parsed(&schema, &doc, &source, options, None).into_diagnostics();
+                    // Spans are found in this file's source, so they are
+                    // wrong for what a fragment set.
+                    for error in &mut report {
+                        if !written_by(&own, &doc, &error.path) {
+                            error.span = None;
+                        }
+                    }
+                    report.extend(errors.into_iter().map(|error| error.at(at)));
+                    if kind == SchemaKind::Config {
+                        assembled = toml::to_string(&doc).ok();
+                    }
+                    report
+                }
+                Some(doc) => {
+                    count(kind, &doc, &mut stats);
+                    report_parsed(&schema, &doc, &source, options, None).into_diagnostics()
+                }
+                None => validate_report(kind, &source).into_diagnostics(),
+            };
+            (Some(assembled.unwrap_or(source)), errors)
+        }
+        Err(err) => {
+            let kind = match err.kind() {
+                io::ErrorKind::FileTooLarge => ValidationErrorKind::InputTooLarge {
+                    limit: ValidationOptions::DEFAULT_MAX_INPUT_BYTES,
+                },
+                _ => ValidationErrorKind::Io,
+            };
+            (None, vec![ValidationError::new("", kind, err.to_string())])
+        }
+    };
+    ((FileReport { path, kind, errors }, source, stats), included)
+}
+
+/// Whether what `path` names in `assembled` is as the including file `own`
+/// wrote it. For a path set nowhere, such as a missing required field, this
+/// asks about its nearest ancestor that is set.
+fn written_by(own: &Table, assembled: &Table, path: &str) -> bool {
+    let mut path = path;
+    while !path.is_empty() {
+        if let Some(value) = value_at(assembled, path) {
+            return value_at(own, path) == Some(value);
+        }
+        path = &path[..path.rfind(['.', '[']).unwrap_or(0)];
+    }
+    true
+}
+
+/// Merge the fragment `doc` includes, and the fragments that includes in
+/// turn, beneath `doc`. `chain` holds the path of `doc` and of every file
+/// that included it. Fragments outside `root`, and those that are missing,
+/// invalid or would close a cycle, are reported and skipped.
+fn merge_includes(
+    doc: &mut Table,
+    root: &Path,
+    chain: &mut Vec<PathBuf>,
+    read: ReadFragment<'_>,
+    errors: &mut Vec<ValidationError>,
+) {
+    let Some(include) = doc.get(INCLUDE_KEY).and_then(Value::as_str) else {
+        return;
+    };
+    let including = chain
+        .last()
+        .expect("the chain starts at the validated file");
+    let path = normalize(&including.parent().unwrap_or(Path::new("")).join(include));
+    let shown = path.display().to_string();
+    let inside = path.strip_prefix(root).is_ok_and(|rest| {
+        rest.components()
+            .all(|component| matches!(component, Component::Normal(_)))
+    });
+    if Path::new(include).is_absolute() || !inside {
+        errors.push(ValidationError::new(
+            INCLUDE_KEY,
+            ValidationErrorKind::IncludeOutsideDeployment {
+                include: shown.clone(),
+            },
+            format!("the included file `{shown}` is outside the deployment"),
+        ));
+        return;
+    }
+    if let Some(start) = chain.iter().position(|seen| *seen == path) {
+        let chain: Vec<String> = chain[start..]
+            .iter()
+            .chain([&path])
+            .map(|path| path.display().to_string())
+            .collect();
+        let message = format!("`{shown}` includes itself through {}", chain.join(" -> "));
+        errors.push(ValidationError::new(
+            INCLUDE_KEY,
+            ValidationErrorKind::IncludeCycle { chain },
+            message,
+        ));
+        return;
+    }
+    let source = match read(&path) {
+        Ok(source) => source,
+        Err(err) => {
+            errors.push(ValidationError::new(
+                INCLUDE_KEY,
+                ValidationErrorKind::MissingInclude {
+                    include: shown.clone(),
+                },
+                format!("the included file `{shown}` could not be read: {err}"),
+            ));
+            return;
+        }
+    };
+    let mut fragment = match toml::from_str::<Table>(&source) {
+        Ok(fragment) => fragment,
+        Err(err) => {
+            let error = parse_error(&source, &err);
+            let at = error
+                .span
+                .map(|(line, column)| format!(" at {line}:{column}"))
+                .unwrap_or_default();
+            errors.push(ValidationError::new(
+                INCLUDE_KEY,
+                ValidationErrorKind::InvalidToml,
+                format!(
+                    "the included file `{shown}` is invalid{at}: {}",
+                    error.message
+                ),
+            ));
+            return;
+        }
+    };
+    chain.push(path);
+    merge_includes(&mut fragment, root, chain, read, errors);
+    chain.pop();
+    let own = core::mem::replace(doc, fragment);
+    merge_tables(doc, own, MergeOptions::default());
+}
+
+/// `path` with `.` components dropped and `..` applied, so the same file
+/// reached two ways compares equal. Does not touch the filesystem.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normal = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir
+                if matches!(normal.components().next_back(), Some(Component::Normal(_))) =>
+            {
+                normal.pop();
+            }
+            component => normal.push(component),
+        }
+    }
+    normal
+}
+
+/// Combine the per-file reports, in [`discover`] order, and run the
+/// cross-file checks.
+fn assemble(loaded: Vec<Loaded>, missing: Vec<PathBuf>) -> DirectoryReport {
+    let mut report = DirectoryReport {
+        files: Vec::with_capacity(loaded.len()),
+        missing,
+        stats: DeploymentStats::default(),
+    };
+    let mut config = None;
+    let mut pipelines: Vec<(usize, Option<String>)> = Vec::new();
+    let mut meshes: Vec<(usize, Option<String>)> = Vec::new();
+    let mut remote_ingresses = Vec::new();
+    for (file, source, stats) in loaded {
+        report.stats.add(stats);
+        match file.kind {
+            SchemaKind::Config => config = Some(source),
+            SchemaKind::Pipeline => pipelines.push((report.files.len(), source)),
+            SchemaKind::Mesh => meshes.push((report.files.len(), source)),
+            SchemaKind::RemoteIngress => remote_ingresses.extend(source),
+        }
+        report.files.push(file);
+    }
+    let sources: Vec<(usize, &str)> = pipelines
+        .iter()
+        .filter_map(|(index, source)| Some((*index, source.as_deref()?)))
+        .collect();
+    let texts: Vec<&str> = sources.iter().map(|(_, source)| *source).collect();
+
+    for error in unreachable_pipelines(&texts) {
+        let file = sources[error.pipeline.expect("reported in a pipeline file")].0;
+        report.files[file].errors.push(error.error);
+    }
+    let mesh_sources: Vec<(usize, &str)> = meshes
+        .iter()
+        .filter_map(|(index, source)| Some((*index, source.as_deref()?)))
+        .collect();
+    let mesh_texts: Vec<&str> = mesh_sources.iter().map(|(_, source)| *source).collect();
+    let catalogues: Vec<&str> = remote_ingresses.iter().map(String::as_str).collect();
+    for (mesh, error) in unknown_pipeline_references(&mesh_texts, &texts, &catalogues) {
+        report.files[mesh_sources[mesh].0].errors.push(error);
+    }
+    if let Some(Some(config)) = config {
+        // Middleware types registered in the config are invisible to
+        // per-file pipeline validation.
+        let registered = registered_middleware_types(&config);
+        for file in &mut report.files {
+            file.errors.retain(|error| match &error.kind {
+                ValidationErrorKind::UnknownMiddlewareType { reference, .. } => {
+                    !registered.contains(reference)
+                }
+                _ => true,
+            });
+        }
+
+        for (mesh, error) in unknown_providers(&config, &mesh_texts) {
+            report.files[mesh_sources[mesh].0].errors.push(error);
+        }
+
+        let errors = validate_cross_references(&config, &texts).err();
+
+        for error in errors.into_iter().flatten() {
+            // Parse failures are already reported by per-file validation.
+            if matches!(
+                error.error.kind,
+                ValidationErrorKind::InvalidToml | ValidationErrorKind::DuplicateKey { .. }
+            ) {
+                continue;
+            }
+            let file = match error.pipeline {
+                Some(pipeline) => sources[pipeline].0,
+                None => 0,
+            };
+            report.files[file].errors.push(error.error);
+        }
+    }
+    for file in &mut report.files {
+        let errors = std::mem::take(&mut file.errors);
+        file.errors = ValidationReport::new(errors).into_diagnostics();
+    }
+
+    report
+}
+
+/// Add what `doc`, a file of `kind`, defines to `stats`.
+fn count(kind: SchemaKind, doc: &Table, stats: &mut DeploymentStats) {
+    let tables = |key: &str| doc.get(key).and_then(toml::Value::as_table);
+    match kind {
+        SchemaKind::Config => {
+            for network in tables("network").into_iter().flat_map(|t| t.values()) {
+                stats.listeners += ["tcp_config", "http3"]
+                    .iter()
+                    .filter(|listener| network.get(listener).is_some())
+                    .count();
+            }
+        }
+        SchemaKind::Pipeline => {
+            stats.pipelines += tables("pipelines").map_or(0, toml::Table::len);
+            for (section, total) in [
+                ("endpoints", &mut stats.endpoints),
+                ("backends", &mut stats.backends),
+            ] {
+                let instances = tables(section).into_iter().flat_map(|t| t.values());
+                for instance in instances {
+                    *total += 1;
+                    if let Some(service) = instance.get("service").and_then(toml::Value::as_str) {
+                        stats.service_types.insert(service.to_owned());
+                    }
+                }
+            }
+        }
+        SchemaKind::Mesh => stats.meshes += tables("mesh").map_or(0, toml::Table::len),
+        SchemaKind::RemoteIngress => {
+            stats.remote_ingresses += tables("remote_ingress").map_or(0, toml::Table::len);
+        }
+    }
+}
+
+fn registered_middleware_types(config: &str) -> Vec<String> {
+    let Ok(doc) = toml::from_str::<toml::Table>(config) else {
+        return Vec::new();
+    };
+    let registered = doc.get("middleware_types").and_then(toml::Value::as_table);
+    registered
+        .into_iter()
+        .flat_map(|t| t.keys().cloned())
+        .collect()
+}
+
+/// The `*.toml` files directly inside `dir`, sorted by name. Empty if the
+/// directory does not exist.
+fn toml_files(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut files: Vec<PathBuf> = entries
+        .filter_map(|entry| Some(entry.ok()?.path()))
+        .filter(|path| path.is_file() && path.extension().is_some_and(|ext| ext == "toml"))
+        .collect();
+    files.sort();
+    files
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::validate::ErrorCode;
+
+    /// A fresh, empty directory under the system temp dir.
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("harmony-dsl-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn write(dir: &Path, file: &str, contents: &str) {
+        let path = dir.join(file);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+
+    const CONFIG: &str = "[proxy]\nid = \"gw\"\n\n[services.http]\nmodule = \"\"\n\n[middleware_types.gateway_audit]\nmodule = \"audit\"\n";
+
+    /// [`CONFIG`] with the `runbeam` provider defined, for meshes that use it.
+    const RUNBEAM_CONFIG: &str = "[proxy]\nid = \"gw\"\n\n[services.http]\nmodule = \"\"\n\n[middleware_types.gateway_audit]\nmodule = \"audit\"\n\n[provider.runbeam]\napi = \"https://api.runbeam.io\"\n";
+
+    #[test]
+    fn missing_config_is_reported_but_missing_mesh_is_not() {
+        let dir = scratch_dir("missing-config");
+        fs::create_dir_all(dir.join("mesh")).unwrap();
+
+        let report = validate_directory(&dir);
+        assert_eq!(report.missing, vec![dir.join("config.toml")]);
+        assert!(report.files.is_empty());
+        assert!(!report.is_ok());
+
+        write(&dir, "config.toml", CONFIG);
+        let report = validate_directory(&dir);
+        assert!(report.missing.is_empty());
+        assert!(report.is_ok(), "{report:?}");
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn included_fragments_are_merged_beneath_the_including_file() {
+        let dir = scratch_dir("include");
+        write(
+            &dir,
+            "config.toml",
+            "include = \"shared/base.toml\"\n\n[logging]\nlog_level = \"warn\"\n",
+        );
+        // The fragment supplies the required `proxy.id` and the service the
+        // pipeline uses.
+        write(
+            &dir,
+            "shared/base.toml",
+            "[proxy]\nid = \"gw\"\n\n[services.http]\nmodule = \"\"\n\n[logging]\nlog_level = 7\n",
+        );
+        write(
+            &dir,
+            "pipelines/a.toml",
+            "[endpoints.in]\nservice = \"http\"\n",
+        );
+        let report = validate_directory(&dir);
+        assert!(report.is_ok(), "{report:?}");
+
+        write(&dir, "config.toml", "[logging]\nlog_level = \"warn\"\n");
+        let report = validate_directory(&dir);
+        assert!(report.files[0]
+            .errors
+            .iter()
+            .any(|error| error.kind == ValidationErrorKind::MissingRequired));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn missing_and_cyclic_includes_are_reported() {
+        let dir = scratch_dir("include-errors");
+        write(&dir, "config.toml", "include = \"base.toml\"\n");
+        let report = validate_directory(&dir);
+        let error = report.files[0]
+            .errors
+            .iter()
+            .find(|error| error.code == ErrorCode::MissingInclude)
+            .unwrap();
+        assert_eq!(error.path, "include");
+        assert_eq!(error.span, Some((1, 11)));
+        let base = dir.join("base.toml").display().to_string();
+        assert_eq!(
+            error.kind,
+            ValidationErrorKind::MissingInclude {
+                include: base.clone()
+            }
+        );
+
+        write(
+            &dir,
+            "base.toml",
+            "include = \"./config.toml\"\n\n[proxy]\nid = \"gw\"\n",
+        );
+        let report = validate_directory(&dir);
+        let config = dir.join("config.toml").display().to_string();
+        assert_eq!(
+            report.files[0]
+                .errors
+                .iter()
+                .map(|error| &error.kind)
+                .collect::<Vec<_>>(),
+            [&ValidationErrorKind::IncludeCycle {
+                chain: vec![config.clone(), base, config],
+            }]
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn includes_stay_inside_the_deployment() {
+        let parent = scratch_dir("include-outside");
+        let dir = parent.join("deploy");
+        write(&parent, "base.toml", "[proxy]\nid = \"gw\"\n");
+        let absolute = parent.join("base.toml").display().to_string();
+        for include in ["../base.toml", "shared/../../base.toml", &absolute] {
+            let config = format!("include = {include:?}\n");
+            write(&dir, "config.toml", &config);
+            let report = validate_directory(&dir);
+            let outside: Vec<_> = report.files[0]
+                .errors
+                .iter()
+                .filter_map(|error| match &error.kind {
+                    ValidationErrorKind::IncludeOutsideDeployment { include } => Some(include),
+                    _ => None,
+                })
+                .collect();
+            assert_eq!(outside, [&absolute], "{include}");
+        }
+        fs::remove_dir_all(parent).unwrap();
+    }
+
+    #[test]
+    fn errors_set_by_fragments_have_no_span() {
+        let dir = scratch_dir("include-spans");
+        write(
+            &dir,
+            "config.toml",
+            "include = \"base.toml\"\n\n[logging]\nlog_level = 3\n",
+        );
+        write(&dir, "base.toml", "[proxy]\nid = 7\n");
+        let report = validate_directory(&dir);
+        let spans: Vec<_> = report.files[0]
+            .errors
+            .iter()
+            .map(|error| (error.path.as_str(), error.span))
+            .collect();
+        assert_eq!(
+            spans,
+            [("proxy.id", None), ("logging.log_level", Some((4, 13)))]
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn errors_are_grouped_by_file() {
+        let dir = scratch_dir("grouped");
+        write(&dir, "config.toml", CONFIG);
+        write(
+            &dir,
+            "pipelines/a.toml",
+            "[endpoints.in]\nservice = \"http\"\n",
+        );
+        write(
+            &dir,
+            "pipelines/b.toml",
+            "[endpoints.in]\nservice = \"htp\"\n",
+        );
+        write(
+            &dir,
+            "pipelines/c.toml",
+            "[middleware.audit]\ntype = \"gateway_audit\"\n",
+        );
+        write(&dir, "notes.txt", "ignored");
+
+        let report = validate_directory(&dir);
+        let paths: Vec<_> = report.files.iter().map(|file| file.path.clone()).collect();
+        assert_eq!(
+            paths,
+            [
+                dir.join("config.toml"),
+                dir.join("pipelines/a.toml"),
+                dir.join("pipelines/b.toml"),
+                dir.join("pipelines/c.toml"),
+            ]
+        );
+
+        let errors = |index: usize| -> Vec<_> {
+            report.files[index]
+                .errors
+                .iter()
+                .map(|err| err.kind.clone())
+                .collect()
+        };
+        assert!(errors(1).is_empty());
+        // `gateway_audit` is registered in the config, not the pipeline.
+        assert!(errors(3).is_empty(), "{:?}", errors(3));
+        assert_eq!(
+            errors(2),
+            [ValidationErrorKind::UnknownServiceType {
+                reference: "htp".to_owned(),
+                suggestion: Some("http".to_owned()),
+            }]
+        );
+
+        let report = report.report();
+        let files: Vec<_> = report
+            .diagnostics()
+            .iter()
+            .map(|error| error.file.clone().unwrap())
+            .collect();
+        assert_eq!(files, [dir.join("pipelines/b.toml")]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn pipelines_no_endpoint_routes_to_are_flagged() {
+        let dir = scratch_dir("unreachable");
+        write(&dir, "config.toml", CONFIG);
+        write(
+            &dir,
+            "pipelines/endpoints.toml",
+            "[endpoints.web]\nservice = \"http\"\n\n[backends.app]\nservice = \"http\"\n",
+        );
+        let pipeline = |name: &str, endpoint: &str| {
+            format!(
+                "[pipelines.{name}]\nnetworks = [\"default\"]\nendpoints = [\"{endpoint}\"]\n\
+                 backends = [\"app\"]\n"
+            )
+        };
+        write(&dir, "pipelines/live.toml", &pipeline("live", "web"));
+        write(&dir, "pipelines/orphan.toml", &pipeline("orphan", "legacy"));
+
+        let report = validate_directory(&dir);
+        let warnings: Vec<_> = report
+            .report()
+            .into_diagnostics()
+            .into_iter()
+            .filter(|error| matches!(error.kind, ValidationErrorKind::UnreachablePipeline { .. }))
+            .collect();
+        assert_eq!(warnings.len(), 1, "{warnings:?}");
+        assert_eq!(warnings[0].file, Some(dir.join("pipelines/orphan.toml")));
+        assert_eq!(warnings[0].path, "pipelines.orphan.endpoints");
+        assert_eq!(warnings[0].span, Some((3, 13)));
+        assert_eq!(warnings[0].severity, crate::Severity::Warning);
+        assert!(report.is_ok(), "{report:?}");
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn meshes_naming_undefined_ingresses_are_flagged() {
+        let dir = scratch_dir("mesh-refs");
+        write(&dir, "config.toml", RUNBEAM_CONFIG);
+        write(
+            &dir,
+            "pipelines/imaging.toml",
+            "[pipelines.imaging.mesh.ingress.imaging_in]\ntype = \"http\"\n\
+             urls = [\"https://gw.example.com/imaging\"]\n",
+        );
+        write(
+            &dir,
+            "mesh/hospital.toml",
+            "[mesh.hospital]\ntype = \"http\"\nprovider = \"runbeam\"\n\
+             ingress = [\"imaging_inn\", \"partner_gw\"]\negress = [\"runbeam.id.7\"]\n",
+        );
+        // Ingresses of other gateways come from remote ingress catalogues.
+        write(
+            &dir,
+            "remote_ingress/partners.toml",
+            "[remote_ingress.partner_gw]\nurls = [\"https://partner.example.com\"]\n",
+        );
+
+        let report = validate_directory(&dir);
+        let catalogue = report.files.last().unwrap();
+        assert_eq!(catalogue.kind, SchemaKind::RemoteIngress);
+        assert!(catalogue.errors.is_empty(), "{report:?}");
+        let mesh = report
+            .files
+            .iter()
+            .find(|file| file.kind == SchemaKind::Mesh)
+            .unwrap();
+        assert_eq!(mesh.path, dir.join("mesh/hospital.toml"));
+        let kinds: Vec<_> = mesh.errors.iter().map(|error| error.kind.clone()).collect();
+        assert_eq!(
+            kinds,
+            [ValidationErrorKind::UnknownPipelineReference {
+                reference: "imaging_inn".to_owned(),
+                suggestion: Some("imaging_in".to_owned()),
+            }]
+        );
+        assert_eq!(mesh.errors[0].span, Some((4, 12)));
+        assert!(!report.is_ok());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    #[cfg(not(feature = "fs-checks"))]
+    fn unchanged_files_are_served_from_the_cache() {
+        let dir = scratch_dir("cached");
+        write(&dir, "config.toml", CONFIG);
+        write(
+            &dir,
+            "pipelines/a.toml",
+            "[endpoints.in]\nservice = \"http\"\n",
+        );
+        write(
+            &dir,
+            "pipelines/b.toml",
+            "[endpoints.out]\nservice = \"htp\"\n",
+        );
+
+        let mut cache = ValidationCache::new();
+        let first = validate_directory_cached(&dir, &mut cache);
+        assert_eq!(first, validate_directory(&dir));
+        assert_eq!((cache.hits(), cache.len()), (0, 3));
+
+        let second = validate_directory_cached(&dir, &mut cache);
+        assert_eq!(second, first);
+        assert_eq!(cache.hits(), 3);
+
+        write(
+            &dir,
+            "pipelines/b.toml",
+            "[endpoints.out]\nservice = \"http\"\n",
+        );
+        let third = validate_directory_cached(&dir, &mut cache);
+        assert_eq!(third, validate_directory(&dir));
+        assert!(third.is_ok(), "{third:?}");
+        assert_eq!((cache.hits(), cache.len()), (5, 3));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "fs-checks")]
+    fn the_cache_is_bypassed_when_validation_reads_the_filesystem() {
+        let dir = scratch_dir("cached-fs");
+        write(&dir, "config.toml", CONFIG);
+
+        let mut cache = ValidationCache::new();
+        for _ in 0..2 {
+            assert_eq!(
+                validate_directory_cached(&dir, &mut cache),
+                validate_directory(&dir)
+            );
+        }
+        assert_eq!((cache.hits(), cache.len()), (0, 0));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn stats_count_what_the_deployment_defines() {
+        let dir = scratch_dir("stats");
+        write(
+            &dir,
+            "config.toml",
+            &format!(
+                "{CONFIG}\n[network.default.tcp_config]\nbind_address = \"0.0.0.0\"\n\
+                 bind_port = 8080\n\n[network.edge.tcp_config]\nbind_address = \"0.0.0.0\"\n\
+                 bind_port = 8443\n\n[network.edge.http3]\nbind_address = \"0.0.0.0\"\n\
+                 bind_port = 443\ncert_path = \"cert.pem\"\nkey_path = \"key.pem\"\n"
+            ),
+        );
+        let pipeline = |name: &str| {
+            format!(
+                "[pipelines.{name}]\nnetworks = [\"default\"]\nendpoints = [\"{name}_in\"]\n\
+                 backends = [\"{name}_out\"]\n\n[endpoints.{name}_in]\nservice = \"http\"\n\n\
+                 [backends.{name}_out]\nservice = \"fhir\"\n"
+            )
+        };
+        write(&dir, "pipelines/a.toml", &pipeline("a"));
+        write(&dir, "pipelines/b.toml", &pipeline("b"));
+        write(&dir, "pipelines/broken.toml", "[pipelines.c\n");
+        write(&dir, "mesh/core.toml", "[mesh.core]\ntype = \"http\"\n");
+        write(
+            &dir,
+            "remote_ingress/partners.toml",
+            "[remote_ingress.a]\nurls = [\"https://a.example.com\"]\n\n\
+             [remote_ingress.b]\nurls = [\"https://b.example.com\"]\n",
+        );
+
+        let stats = validate_directory(&dir).stats();
+        assert_eq!(stats.remote_ingresses, 2);
+        assert_eq!(stats.pipelines, 2);
+        assert_eq!(stats.listeners, 3);
+        assert_eq!((stats.endpoints, stats.backends, stats.meshes), (2, 2, 1));
+        assert_eq!(
+            stats.service_types.into_iter().collect::<Vec<_>>(),
+            ["fhir", "http"]
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(feature = "archive")]
+    #[test]
+    fn archives_are_validated_without_unpacking() {
+        let mut tar = tar::Builder::new(Vec::new());
+        let mut append = |path: &str, contents: &str| {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(contents.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            tar.append_data(&mut header, path, contents.as_bytes())
+                .unwrap();
+        };
+        append("deploy/config.toml", CONFIG);
+        append(
+            "deploy/pipelines/api.toml",
+            "[pipelines.api]\nnetworks = [\"default\"]\nendpoints = [\"web\"]\nbackends = [\"app\"]\n\n\
+             [endpoints.web]\nservice = \"fhir\"\n\n[backends.app]\nservice = \"http\"\n",
+        );
+        append("deploy/README.md", "ignored");
+        let bytes = tar.into_inner().unwrap();
+
+        let report = validate_archive(&bytes);
+        let paths: Vec<_> = report.files.iter().map(|file| file.path.clone()).collect();
+        assert_eq!(
+            paths,
+            [
+                PathBuf::from("deploy/config.toml"),
+                PathBuf::from("deploy/pipelines/api.toml"),
+            ]
+        );
+        assert!(report.missing.is_empty());
+        assert!(report.files[0].errors.is_empty(), "{report:?}");
+        // The config registers no `fhir` service.
+        let errors = &report.files[1].errors;
+        assert_eq!(errors.len(), 1, "{errors:?}");
+        assert_eq!(errors[0].path, "endpoints.web.service");
+        assert_eq!(report.stats().pipelines, 1);
+
+        let report = validate_archive(b"not an archive");
+        assert_eq!(report.files.len(), 1);
+        assert_eq!(report.files[0].errors[0].kind, ValidationErrorKind::Io);
+    }
+
+    #[cfg(feature = "archive")]
+    #[test]
+    fn oversized_zip_entries_are_not_read() {
+        use std::io::Write;
+
+        let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        let mut append = |path: &str, contents: &[u8]| {
+            zip.start_file(path, zip::write::SimpleFileOptions::default())
+                .unwrap();
+            zip.write_all(contents).unwrap();
+        };
+        append("config.toml", CONFIG.as_bytes());
+        let limit = ValidationOptions::DEFAULT_MAX_INPUT_BYTES;
+        let mut large = b"# padding\n".repeat(limit / 10 + 1);
+        large.extend_from_slice(b"[pipelines.api]\n");
+        append("pipelines/large.toml", &large);
+        append("pipelines/notes.txt", &large);
+        let bytes = zip.finish().unwrap().into_inner();
+
+        let report = validate_archive(&bytes);
+        let paths: Vec<_> = report.files.iter().map(|file| file.path.clone()).collect();
+        assert_eq!(
+            paths,
+            [
+                PathBuf::from("config.toml"),
+                PathBuf::from("pipelines/large.toml"),
+            ]
+        );
+        assert!(report.files[0].errors.is_empty(), "{report:?}");
+        let errors = &report.files[1].errors;
+        assert_eq!(errors.len(), 1, "{errors:?}");
+        assert_eq!(errors[0].kind, ValidationErrorKind::InputTooLarge { limit });
+    }
+
+    #[test]
+    fn oversized_files_are_not_read() {
+        let dir = scratch_dir("oversized");
+        write(&dir, "config.toml", CONFIG);
+        let limit = ValidationOptions::DEFAULT_MAX_INPUT_BYTES;
+        let mut large = "# padding\n".repeat(limit / 10 + 1);
+        large.push_str("[pipelines.api]\n");
+        write(&dir, "pipelines/large.toml", &large);
+
+        let report = validate_directory(&dir);
+        assert!(report.files[0].errors.is_empty(), "{report:?}");
+        let errors = &report.files[1].errors;
+        assert_eq!(errors.len(), 1, "{errors:?}");
+        assert_eq!(errors[0].kind, ValidationErrorKind::InputTooLarge { limit });
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(feature = "tokio")]
+    #[test]
+    fn async_validation_matches_sync_validation() {
+        let dir = scratch_dir("async");
+        write(&dir, "config.toml", CONFIG);
+        for index in 0..(MAX_CONCURRENT_FILES + 4) {
+            let service = if index % 5 == 0 { "htp" } else { "http" };
+            write(
+                &dir,
+                &format!("pipelines/p{index:02}.toml"),
+                &format!("[endpoints.in]\nservice = \"{service}\"\n"),
+            );
+        }
+        write(&dir, "mesh/core.toml", "[mesh.core]\ningress = [1]\n");
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let report = runtime.block_on(validate_directory_async(&dir));
+        assert_eq!(report, validate_directory(&dir));
+        assert_eq!(report.files.len(), MAX_CONCURRENT_FILES + 6);
+        assert!(!report.is_ok());
+
+        let mut streamed = Vec::new();
+        let report = runtime.block_on(validate_directory_streaming(&dir, |file| {
+            streamed.push(file.path.clone());
+        }));
+        assert_eq!(report, validate_directory(&dir));
+        streamed.sort();
+        let mut paths: Vec<_> = report.files.iter().map(|file| file.path.clone()).collect();
+        paths.sort();
+        assert_eq!(streamed, paths);
+
+        // Files are handed over while later ones are still to be started.
+        let events = std::cell::RefCell::new(Vec::new());
+        runtime.block_on(stream_directory(
+            &dir,
+            |index| events.borrow_mut().push(Some(index)),
+            |_| events.borrow_mut().push(None),
+        ));
+        let events = events.into_inner();
+        let last_spawn = events
+            .iter()
+            .position(|event| *event == Some(report.files.len() - 1))
+            .unwrap();
+        assert!(events[..last_spawn].contains(&None), "{events:?}");
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
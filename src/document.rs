@@ -0,0 +1,249 @@
+//! Editing documents without losing their formatting.
+//!
+//! A settings UI that toggles one field should write back the file the user
+//! wrote, comments and all. [`Document`] wraps a `toml_edit` document and
+//! checks each value against its schema rule before setting it, so edits
+//! can neither clobber formatting nor introduce a field-level error.
+//! Callers that only read one value can use [`get_value`] instead.
+
+use std::fmt;
+
+use toml::Table;
+use toml_edit::{DocumentMut, Item, TableLike};
+
+use crate::schema::{bundled_schema, FieldType, Schema};
+use crate::validate::{check_value, parse_error, ValidationError, ValidationErrorKind};
+use crate::SchemaKind;
+
+/// A TOML document of one [`SchemaKind`], edited in place by schema field
+/// path. [`Display`](fmt::Display) writes it back with its original comments,
+/// ordering and layout.
+#[derive(Debug, Clone)]
+pub struct Document {
+    kind: SchemaKind,
+    schema: Schema,
+    doc: DocumentMut,
+}
+
+impl Document {
+    /// Parse `toml_input` as a document of `kind`. The document itself is not
+    /// validated; see [`validate`](crate::validate).
+    pub fn parse(kind: SchemaKind, toml_input: &str) -> Result<Self, Box<ValidationError>> {
+        toml::from_str::<Table>(toml_input)
+            .map_err(|err| Box::new(parse_error(toml_input, &err)))?;
+        let doc = toml_input.parse().map_err(|err: toml_edit::TomlError| {
+            Box::new(ValidationError::new(
+                "",
+                ValidationErrorKind::InvalidToml,
+                err.message(),
+            ))
+        })?;
+        Ok(Self {
+            kind,
+            schema: bundled_schema(kind).into_owned(),
+            doc,
+        })
+    }
+
+    /// The schema this document is edited against.
+    pub fn kind(&self) -> SchemaKind {
+        self.kind
+    }
+
+    /// The value at a path such as `management.enabled`, if set; see
+    /// [`get_value`].
+    pub fn get(&self, path: &str) -> Option<toml::Value> {
+        get_value(&self.doc.to_string(), path)
+    }
+
+    /// Set the field at `path` to `value`, creating missing parent tables.
+    ///
+    /// Fails without changing the document if the schema does not declare
+    /// the field, or if `value` breaks the field's rule (type, `values`,
+    /// bounds or `pattern`). Comments attached to a replaced value are kept.
+    pub fn set(
+        &mut self,
+        path: &str,
+        value: impl Into<toml::Value>,
+    ) -> Result<(), Box<ValidationError>> {
+        let value = value.into();
+        let Some(rule) = self.schema.field(path) else {
+            return Err(Box::new(ValidationError::new(
+                path,
+                ValidationErrorKind::UnknownKey { suggestion: None },
+                format!("`{path}` is not a field of the {} schema", self.kind.name()),
+            )));
+        };
+        if let Some(error) = check_value(rule, path, &value) {
+            return Err(Box::new(error));
+        }
+        let mut new = value
+            .to_string()
+            .parse::<toml_edit::Value>()
+            .map_err(|err| {
+                Box::new(ValidationError::new(
+                    path,
+                    ValidationErrorKind::InvalidToml,
+                    err.to_string(),
+                ))
+            })?;
+
+        let (parents, key) = match path.rsplit_once('.') {
+            Some((parents, key)) => (parents.split('.').collect(), key),
+            None => (Vec::new(), path),
+        };
+        let mut table: &mut dyn TableLike = self.doc.as_table_mut();
+        for parent in parents {
+            let item = table.entry(parent).or_insert_with(|| {
+                let mut table = toml_edit::Table::new();
+                table.set_implicit(true);
+                Item::Table(table)
+            });
+            if !item.is_table_like() {
+                return Err(Box::new(ValidationError::new(
+                    path,
+                    ValidationErrorKind::TypeMismatch {
+                        expected: FieldType::Table { pattern: false },
+                        found: item.type_name().to_owned(),
+                    },
+                    format!("`{parent}` in `{path}` is not a table"),
+                )));
+            }
+            table = item.as_table_like_mut().expect("checked to be table-like");
+        }
+        match table.get_mut(key) {
+            Some(Item::Value(old)) => {
+                *new.decor_mut() = old.decor().clone();
+                *old = new;
+            }
+            _ => {
+                table.insert(key, Item::Value(new));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The value at `path` in `toml_input`, or `None` if the input is not valid
+/// TOML or nothing is set there.
+///
+/// `path` is dotted, with bracketed indices into arrays, e.g.
+/// `storage.options.bucket` or `mesh.core.ingress[0]`. Tables come back whole.
+pub fn get_value(toml_input: &str, path: &str) -> Option<toml::Value> {
+    let table: Table = toml::from_str(toml_input).ok()?;
+    value_at(&table, path).cloned()
+}
+
+/// The value at a dotted, possibly indexed, path beneath `table`.
+pub(crate) fn value_at<'a>(table: &'a Table, path: &str) -> Option<&'a toml::Value> {
+    let mut table = table;
+    let mut segments = path.split('.').peekable();
+    while let Some(segment) = segments.next() {
+        let (key, mut indices) = segment.split_at(segment.find('[').unwrap_or(segment.len()));
+        let mut value = table.get(key)?;
+        while let Some(rest) = indices.strip_prefix('[') {
+            let (index, rest) = rest.split_once(']')?;
+            value = value.as_array()?.get(index.parse::<usize>().ok()?)?;
+            indices = rest;
+        }
+        if !indices.is_empty() {
+            return None;
+        }
+        if segments.peek().is_none() {
+            return Some(value);
+        }
+        table = value.as_table()?;
+    }
+    None
+}
+
+impl fmt::Display for Document {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.doc)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "schema-config")]
+    const INPUT: &str = r#"# Edge gateway
+[proxy]
+id = "edge" # do not rename
+
+# Admin API, firewalled
+[management]
+enabled = false # flipped by the UI
+network = "default"
+auth_token = "${ADMIN_TOKEN}"
+"#;
+
+    #[test]
+    #[cfg(feature = "schema-config")]
+    fn flipping_a_boolean_keeps_comments() {
+        let mut doc = Document::parse(SchemaKind::Config, INPUT).unwrap();
+        assert_eq!(doc.get("management.enabled"), Some(false.into()));
+
+        doc.set("management.enabled", true).unwrap();
+        assert_eq!(doc.get("management.enabled"), Some(true.into()));
+        assert_eq!(
+            doc.to_string(),
+            INPUT.replace("enabled = false", "enabled = true")
+        );
+    }
+
+    #[test]
+    fn values_are_found_by_indexed_path() {
+        let input = r#"
+[network.http.tcp_config]
+bind_port = 8080
+
+[[network.http.routes]]
+port = 8081
+
+[[network.http.routes]]
+port = 8082
+"#;
+        assert_eq!(
+            get_value(input, "network.http.tcp_config.bind_port"),
+            Some(8080.into())
+        );
+        assert_eq!(
+            get_value(input, "network.http.routes[1].port"),
+            Some(8082.into())
+        );
+
+        let tcp = get_value(input, "network.http.tcp_config").unwrap();
+        assert_eq!(tcp.as_table().unwrap().len(), 1);
+
+        assert_eq!(get_value(input, "network.http.routes[2].port"), None);
+        assert_eq!(get_value(input, "network.http.routes[x]"), None);
+        assert_eq!(
+            get_value(input, "network.http.tcp_config.bind_port.x"),
+            None
+        );
+        assert_eq!(get_value("[network", "network"), None);
+    }
+
+    #[test]
+    #[cfg(feature = "schema-config")]
+    fn invalid_values_are_rejected() {
+        let mut doc = Document::parse(SchemaKind::Config, INPUT).unwrap();
+        let error = doc.set("management.enabled", "yes").unwrap_err();
+        assert!(matches!(
+            error.kind,
+            ValidationErrorKind::TypeMismatch { .. }
+        ));
+        let error = doc.set("logging.log_level", "loud").unwrap_err();
+        assert!(matches!(error.kind, ValidationErrorKind::NotInEnum { .. }));
+        let error = doc.set("management.enabledd", true).unwrap_err();
+        assert!(matches!(error.kind, ValidationErrorKind::UnknownKey { .. }));
+        assert_eq!(doc.to_string(), INPUT);
+
+        doc.set("logging.log_level", "debug").unwrap();
+        assert!(doc
+            .to_string()
+            .ends_with("\n[logging]\nlog_level = \"debug\"\n"));
+    }
+}
@@ -0,0 +1,245 @@
+//! Checks for `${ENV_VAR}` placeholders in config values.
+//!
+//! Secrets are kept out of config files by writing `${NAME}` in a string
+//! value and expanding it from the environment at startup. A typo in the
+//! name, or a missing `}`, would otherwise be passed downstream as a literal.
+//! `$${NAME}` escapes the placeholder and is left alone.
+
+use core::fmt;
+use std::collections::HashMap;
+
+use toml::{Table, Value};
+
+use crate::span::{line_col, SpanIndex};
+use crate::validate::{element, join, write_diagnostic};
+
+/// A malformed or unresolvable placeholder.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EnvError {
+    /// Dotted path to the string value containing the placeholder.
+    /// Empty for errors that apply to the whole document.
+    pub path: String,
+    /// What is wrong with the placeholder.
+    pub kind: EnvErrorKind,
+    /// Human-readable description of the failure.
+    pub message: String,
+    /// 1-based `(line, column)` of the value in the original TOML.
+    pub span: Option<(usize, usize)>,
+}
+
+/// The problem an [`EnvError`] reports.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum EnvErrorKind {
+    /// The input could not be parsed as TOML.
+    InvalidToml,
+    /// A `${` has no closing `}`.
+    Unclosed,
+    /// The text between `${` and `}` is not a valid variable name.
+    InvalidName {
+        /// The text found between the braces.
+        name: String,
+    },
+    /// The placeholder names a variable missing from the environment.
+    Undefined {
+        /// The variable name.
+        name: String,
+    },
+}
+
+/// Formats as `line:column: path: message`, like [`ValidationError`](crate::ValidationError).
+impl fmt::Display for EnvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_diagnostic(f, None::<&str>, self.span, &self.path, &self.message)
+    }
+}
+
+impl core::error::Error for EnvError {}
+
+/// Check every `${NAME}` placeholder in the string values of `toml_input`
+/// against `env`.
+///
+/// Variable names must match `[A-Za-z_][A-Za-z0-9_]*`.
+pub fn validate_env_placeholders(
+    toml_input: &str,
+    env: &HashMap<String, String>,
+) -> Result<(), Vec<EnvError>> {
+    let doc: Table = toml::from_str(toml_input).map_err(|err| {
+        vec![EnvError {
+            path: String::new(),
+            kind: EnvErrorKind::InvalidToml,
+            message: err.message().to_owned(),
+            span: err.span().map(|span| line_col(toml_input, span.start)),
+        }]
+    })?;
+
+    let spans = SpanIndex::build(toml_input);
+    let mut errors = Vec::new();
+    for (key, value) in &doc {
+        scan_value(value, key, env, &mut errors);
+    }
+    for error in &mut errors {
+        error.span = spans.as_ref().and_then(|spans| spans.position(&error.path));
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+fn scan_value(
+    value: &Value,
+    path: &str,
+    env: &HashMap<String, String>,
+    errors: &mut Vec<EnvError>,
+) {
+    match value {
+        Value::String(text) => scan_str(text, path, env, errors),
+        Value::Array(items) => {
+            for (index, item) in items.iter().enumerate() {
+                scan_value(item, &element(path, index), env, errors);
+            }
+        }
+        Value::Table(table) => {
+            for (key, value) in table {
+                scan_value(value, &join(path, key), env, errors);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn scan_str(text: &str, path: &str, env: &HashMap<String, String>, errors: &mut Vec<EnvError>) {
+    let error = |kind, message: String| EnvError {
+        path: path.to_owned(),
+        kind,
+        message,
+        span: None,
+    };
+
+    let mut rest = text;
+    while let Some(start) = rest.find('$') {
+        rest = &rest[start..];
+        if rest.starts_with("$$") {
+            rest = &rest[2..];
+            continue;
+        }
+        let Some(body) = rest.strip_prefix("${") else {
+            rest = &rest[1..];
+            continue;
+        };
+        let Some(end) = body.find('}') else {
+            errors.push(error(
+                EnvErrorKind::Unclosed,
+                "placeholder `${` is never closed".to_owned(),
+            ));
+            return;
+        };
+
+        let name = &body[..end];
+        if !is_valid_name(name) {
+            errors.push(error(
+                EnvErrorKind::InvalidName {
+                    name: name.to_owned(),
+                },
+                format!("`{name}` is not a valid environment variable name"),
+            ));
+        } else if !env.contains_key(name) {
+            errors.push(error(
+                EnvErrorKind::Undefined {
+                    name: name.to_owned(),
+                },
+                format!("environment variable `{name}` is not set"),
+            ));
+        }
+        rest = &body[end + 1..];
+    }
+}
+
+fn is_valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    matches!(chars.next(), Some(c) if c == '_' || c.is_ascii_alphabetic())
+        && chars.all(|c| c == '_' || c.is_ascii_alphanumeric())
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    fn env(vars: &[&str]) -> HashMap<String, String> {
+        vars.iter()
+            .map(|name| (name.to_string(), "value".to_owned()))
+            .collect()
+    }
+
+    fn kinds(input: &str, vars: &[&str]) -> Vec<(String, EnvErrorKind)> {
+        validate_env_placeholders(input, &env(vars))
+            .unwrap_err()
+            .into_iter()
+            .map(|err| (err.path, err.kind))
+            .collect()
+    }
+
+    #[test]
+    fn resolved_placeholders_are_accepted() {
+        let input =
+            "[storage.options]\naccess_key_id = \"${AWS_KEY}\"\nurl = \"s3://${BUCKET}/x\"\n";
+        assert_eq!(
+            validate_env_placeholders(input, &env(&["AWS_KEY", "BUCKET"])),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn undefined_variables_are_reported_with_a_location() {
+        let input = "[proxy]\nid = \"gw\"\nrequired_env_vars = [\"${HOME}\", \"${MISSING}\"]\n";
+        let errors = validate_env_placeholders(input, &env(&["HOME"])).unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].path, "proxy.required_env_vars[1]");
+        assert_eq!(
+            errors[0].kind,
+            EnvErrorKind::Undefined {
+                name: "MISSING".to_owned()
+            }
+        );
+        assert_eq!(errors[0].span, Some((3, 33)));
+    }
+
+    #[test]
+    fn malformed_placeholders_are_reported() {
+        assert_eq!(
+            kinds("a = \"${OPEN\"\nb = \"${1X}\"\n", &[]),
+            vec![
+                ("a".to_owned(), EnvErrorKind::Unclosed),
+                (
+                    "b".to_owned(),
+                    EnvErrorKind::InvalidName {
+                        name: "1X".to_owned()
+                    }
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn escaped_placeholders_are_literals() {
+        let input = "a = \"$${LITERAL} costs $5\"\n";
+        assert_eq!(validate_env_placeholders(input, &env(&[])), Ok(()));
+    }
+
+    proptest! {
+        #[test]
+        fn arbitrary_input_never_panics(
+            text in "[$${}a-zA-Z_0-9é]{0,24}",
+            bytes in any::<Vec<u8>>(),
+        ) {
+            let vars = env(&["HOME", "_A1"]);
+            let _ = validate_env_placeholders(&format!("v = {}", toml::Value::String(text)), &vars);
+            let _ = validate_env_placeholders(&String::from_utf8_lossy(&bytes), &vars);
+        }
+    }
+}
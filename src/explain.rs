@@ -0,0 +1,230 @@
+//! A trace of how each schema rule applied to a document.
+//!
+//! [`validate`](crate::validate) only reports what failed. When a rule does
+//! not fire as expected, e.g. a `required_if` that never requires its field,
+//! [`explain`] shows every rule that applies to the document and what came
+//! of it: passed, failed, filled from its default, or skipped because its
+//! condition does not hold.
+
+use toml::{Table, Value};
+
+use crate::prelude::*;
+use crate::schema::{bundled_schema, lookup, Condition, FieldRule, Schema};
+use crate::validate::{instances, join, validate_report, ValidationError};
+use crate::SchemaKind;
+
+/// How one rule applied to one place in the document, from [`explain`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldTrace {
+    /// Dotted path in the document, e.g. `network.default.tcp_config`.
+    pub path: String,
+    /// The rule applied at `path`.
+    pub rule: FieldRule,
+    /// What came of it.
+    pub outcome: TraceOutcome,
+}
+
+/// The outcome of a [`FieldTrace`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum TraceOutcome {
+    /// The value is present and satisfies the rule.
+    Passed,
+    /// The value breaks the rule, or is missing although required.
+    Failed {
+        /// The message of the first error reported for the path.
+        reason: String,
+    },
+    /// The value is absent, so the rule's default applies.
+    Default {
+        /// The default value.
+        value: Value,
+    },
+    /// The rule does not apply because its condition does not hold: a
+    /// `required_if` whose field is absent, or a `[[table.extension]]` for
+    /// other instances.
+    Skipped {
+        /// The condition that does not hold.
+        condition: Condition,
+    },
+    /// The value is absent, which the optional rule allows.
+    Absent,
+}
+
+/// Trace every rule of the bundled schema for `kind` against `toml_input`.
+///
+/// Rules are traced in schema order, per instance for wildcard tables, and
+/// the fields of absent tables are left out. Documents that are not valid
+/// TOML yield no traces; [`validate`](crate::validate) reports those.
+pub fn explain(kind: SchemaKind, toml_input: &str) -> Vec<FieldTrace> {
+    let Ok(doc) = toml::from_str::<Table>(toml_input) else {
+        return Vec::new();
+    };
+    let errors = validate_report(kind, toml_input).into_diagnostics();
+    let schema = bundled_schema(kind);
+    let mut traces = Vec::new();
+    for table in schema.fields.iter().filter(|rule| rule.is_table()) {
+        trace_table(&schema, table, &doc, &errors, &mut traces);
+    }
+    traces
+}
+
+fn trace_table(
+    schema: &Schema,
+    table: &FieldRule,
+    doc: &Table,
+    errors: &[ValidationError],
+    traces: &mut Vec<FieldTrace>,
+) {
+    let segments: Vec<&str> = table.path.split('.').collect();
+    let found = instances(doc, &segments, "");
+    if found.is_empty() && !table.is_pattern() {
+        let outcome = match failure(errors, &table.path) {
+            Some(reason) => TraceOutcome::Failed { reason },
+            None => TraceOutcome::Absent,
+        };
+        traces.push(FieldTrace {
+            path: table.path.clone(),
+            rule: table.clone(),
+            outcome,
+        });
+    }
+    for (path, instance) in found {
+        traces.push(FieldTrace {
+            outcome: present(errors, &path),
+            path: path.clone(),
+            rule: table.clone(),
+        });
+        let fields = schema.fields.iter().filter(|rule| {
+            rule.table == table.path
+                && !rule.is_table()
+                && !rule.relative_path().split('.').any(|seg| seg == "*")
+        });
+        for rule in fields {
+            let field_path = join(&path, rule.relative_path());
+            if let Some(when) = rule.extension.as_ref().filter(|when| !when.holds(instance)) {
+                // Another rule for the path, such as the table's own, wins.
+                if !traces.iter().any(|trace| trace.path == field_path) {
+                    traces.push(FieldTrace {
+                        path: field_path,
+                        rule: rule.clone(),
+                        outcome: TraceOutcome::Skipped {
+                            condition: when.clone(),
+                        },
+                    });
+                }
+                continue;
+            }
+            let outcome = match lookup(instance, rule.relative_path()) {
+                Some(_) => present(errors, &field_path),
+                None => absent(rule, instance, errors, &field_path),
+            };
+            traces.push(FieldTrace {
+                path: field_path,
+                rule: rule.clone(),
+                outcome,
+            });
+        }
+    }
+}
+
+fn present(errors: &[ValidationError], path: &str) -> TraceOutcome {
+    match failure(errors, path) {
+        Some(reason) => TraceOutcome::Failed { reason },
+        None => TraceOutcome::Passed,
+    }
+}
+
+fn absent(
+    rule: &FieldRule,
+    instance: &Table,
+    errors: &[ValidationError],
+    path: &str,
+) -> TraceOutcome {
+    if let Some(reason) = failure(errors, path) {
+        return TraceOutcome::Failed { reason };
+    }
+    if let Some(condition) = rule
+        .required_if
+        .as_ref()
+        .filter(|when| !when.holds(instance))
+    {
+        return TraceOutcome::Skipped {
+            condition: condition.clone(),
+        };
+    }
+    match &rule.default {
+        Some(value) => TraceOutcome::Default {
+            value: value.clone(),
+        },
+        None => TraceOutcome::Absent,
+    }
+}
+
+/// The message of the first error at `path` or inside it, such as an
+/// element of an array.
+fn failure(errors: &[ValidationError], path: &str) -> Option<String> {
+    errors
+        .iter()
+        .filter(|error| error.is_error())
+        .find(|error| {
+            error.path.strip_prefix(path).is_some_and(|rest| {
+                rest.is_empty() || rest.starts_with('[') || rest.starts_with('.')
+            })
+        })
+        .map(|error| error.message.clone())
+}
+
+#[cfg(all(test, feature = "schema-config"))]
+mod tests {
+    use super::*;
+
+    fn outcome(traces: &[FieldTrace], path: &str) -> TraceOutcome {
+        let trace = traces.iter().find(|trace| trace.path == path);
+        trace
+            .unwrap_or_else(|| panic!("no trace for {path}"))
+            .outcome
+            .clone()
+    }
+
+    #[test]
+    fn unmet_conditions_are_recorded_as_skipped() {
+        let config = r#"
+[proxy]
+id = "gw"
+
+[network.default]
+enable_wireguard = false
+
+[network.default.tcp_config]
+bind_address = "0.0.0.0"
+bind_port = 70000
+"#;
+        let traces = explain(SchemaKind::Config, config);
+        assert_eq!(outcome(&traces, "proxy.id"), TraceOutcome::Passed);
+        // `name` is only required when `id` is missing.
+        assert_eq!(
+            outcome(&traces, "proxy.name"),
+            TraceOutcome::Skipped {
+                condition: Condition::Missing {
+                    field: "id".to_owned()
+                }
+            }
+        );
+        assert!(matches!(
+            outcome(&traces, "network.default.tcp_config.bind_port"),
+            TraceOutcome::Failed { reason } if reason.contains("out of range")
+        ));
+        assert_eq!(
+            outcome(&traces, "proxy.pipelines_path"),
+            TraceOutcome::Default {
+                value: Value::String("pipelines".to_owned())
+            }
+        );
+        assert_eq!(outcome(&traces, "logging"), TraceOutcome::Absent);
+        assert!(!traces
+            .iter()
+            .any(|trace| trace.path.starts_with("logging.")));
+
+        assert!(explain(SchemaKind::Config, "[proxy").is_empty());
+    }
+}
@@ -0,0 +1,204 @@
+//! Machine-applicable fixes for validation errors.
+//!
+//! Some errors have an obvious remedy: a misspelt key with a close match, or
+//! a missing required field whose schema declares a default. The validator
+//! attaches a [`Fix`] to those errors, and [`apply_fixes`] makes the edits
+//! with `toml_edit`, so editors can offer them as quick fixes and the CLI can
+//! apply them with `--fix` without disturbing comments or layout.
+
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "std")]
+use toml_edit::{DocumentMut, Entry, Item, Key, TableLike, Value};
+
+use crate::prelude::*;
+
+/// An edit that resolves a [`ValidationError`](crate::ValidationError).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Fix {
+    /// Path of the key to edit, in the form of
+    /// [`ValidationError::path`](crate::ValidationError::path).
+    pub path: String,
+    /// The edit to make at `path`.
+    #[serde(flatten)]
+    pub action: FixAction,
+}
+
+/// The edit a [`Fix`] makes.
+///
+/// Serializes with an `action` tag in snake case, alongside the variant's
+/// fields.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum FixAction {
+    /// Rename the key, keeping its value and comments.
+    RenameKey {
+        /// The new name of the key.
+        to: String,
+    },
+    /// Add the missing key.
+    Insert {
+        /// The value to set, e.g. the field's schema default.
+        value: toml::Value,
+    },
+}
+
+/// Apply `fixes` to `toml_input` in order, keeping comments and formatting.
+///
+/// Fixes whose path no longer exists, or that would overwrite or remove a
+/// key already present, are skipped, as is everything if the input is not
+/// valid TOML. Only with the `std` feature.
+#[cfg(feature = "std")]
+pub fn apply_fixes(toml_input: &str, fixes: &[Fix]) -> String {
+    let Ok(mut doc) = toml_input.parse::<DocumentMut>() else {
+        return toml_input.to_owned();
+    };
+    for fix in fixes {
+        let (parent, key) = match fix.path.rsplit_once('.') {
+            Some((parent, key)) => (Some(parent), key),
+            None => (None, fix.path.as_str()),
+        };
+        let table = match parent {
+            Some(parent) => parent
+                .split('.')
+                .try_fold(doc.as_table_mut() as &mut dyn TableLike, child),
+            None => Some(doc.as_table_mut() as &mut dyn TableLike),
+        };
+        let Some(table) = table else {
+            continue;
+        };
+
+        match &fix.action {
+            FixAction::RenameKey { to } => {
+                if table.contains_key(key) && !table.contains_key(to) {
+                    rename_key(table, key, to);
+                }
+            }
+            FixAction::Insert { value } => {
+                let Ok(value) = value.to_string().parse::<Value>() else {
+                    continue;
+                };
+                if !table.contains_key(key) {
+                    table.insert(key, Item::Value(value));
+                }
+            }
+        }
+    }
+    doc.to_string()
+}
+
+/// The table at `segment` of `table`, where the segment may index an array
+/// of tables (`rules[1]`).
+#[cfg(feature = "std")]
+fn child<'d>(table: &'d mut dyn TableLike, segment: &str) -> Option<&'d mut dyn TableLike> {
+    let Some((key, index)) = segment.strip_suffix(']').and_then(|s| s.split_once('[')) else {
+        return table.get_mut(segment)?.as_table_like_mut();
+    };
+    let index: usize = index.parse().ok()?;
+    match table.get_mut(key)? {
+        Item::ArrayOfTables(tables) => Some(tables.get_mut(index)?),
+        Item::Value(Value::Array(items)) => match items.get_mut(index)? {
+            Value::InlineTable(table) => Some(table),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Rename `from` to `to` in a table, keeping the key's position and the
+/// comments attached to it. If `to` is already set, `from` is dropped.
+#[cfg(feature = "std")]
+pub(crate) fn rename_key(table: &mut dyn TableLike, from: &str, to: &str) {
+    if table.contains_key(to) {
+        table.remove(from);
+        return;
+    }
+    let keys: Vec<String> = table.iter().map(|(key, _)| key.to_owned()).collect();
+    for name in keys {
+        let Some(key) = table.key(&name).cloned() else {
+            continue;
+        };
+        let Some(item) = table.remove(&name) else {
+            continue;
+        };
+        if let Entry::Vacant(entry) = table.entry_format(&renamed(key, from, to)) {
+            entry.insert(item);
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+fn renamed(key: Key, from: &str, to: &str) -> Key {
+    if key.get() == from {
+        Key::new(to).with_leaf_decor(key.leaf_decor().clone())
+    } else {
+        key
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    #[cfg(feature = "schema-config")]
+    use crate::{validate::validate_report, SchemaKind};
+
+    #[cfg(feature = "schema-config")]
+    fn fixes(kind: SchemaKind, input: &str) -> Vec<Fix> {
+        validate_report(kind, input)
+            .into_diagnostics()
+            .into_iter()
+            .filter_map(|error| error.fix)
+            .collect()
+    }
+
+    #[test]
+    #[cfg(feature = "schema-config")]
+    fn typos_and_missing_defaults_are_fixed() {
+        let input = "# gateway\n[proxy]\nidd = \"gw\" # name\n\n[storage]\noptions = { path = \"/var/lib/harmony\" }\n";
+        let found = fixes(SchemaKind::Config, input);
+        assert_eq!(
+            found,
+            [
+                Fix {
+                    path: "proxy.idd".to_owned(),
+                    action: FixAction::RenameKey {
+                        to: "id".to_owned()
+                    },
+                },
+                Fix {
+                    path: "storage.backend".to_owned(),
+                    action: FixAction::Insert {
+                        value: toml::Value::from("filesystem")
+                    },
+                },
+            ]
+        );
+
+        let fixed = apply_fixes(input, &found);
+        assert_eq!(
+            fixed,
+            "# gateway\n[proxy]\nid = \"gw\" # name\n\n[storage]\noptions = { path = \"/var/lib/harmony\" }\nbackend = \"filesystem\"\n"
+        );
+        assert_eq!(crate::validate(SchemaKind::Config, &fixed), Ok(()));
+    }
+
+    #[test]
+    fn stale_fixes_are_skipped() {
+        let input = "[proxy]\nid = \"gw\"\n";
+        let stale = [
+            Fix {
+                path: "proxy.idd".to_owned(),
+                action: FixAction::RenameKey {
+                    to: "id".to_owned(),
+                },
+            },
+            Fix {
+                path: "missing.table.key".to_owned(),
+                action: FixAction::Insert {
+                    value: toml::Value::from(1),
+                },
+            },
+        ];
+        assert_eq!(apply_fixes(input, &stale), input);
+    }
+}
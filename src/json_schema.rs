@@ -0,0 +1,311 @@
+//! Conversion of the schema DSL into JSON Schema (draft 2020-12).
+//!
+//! Editors such as Monaco consume JSON Schema for completion and inline
+//! validation. [`to_json_schema`] maps each rule onto the equivalent JSON
+//! Schema keywords so browser tooling can reuse the DSL without
+//! reimplementing it: field types become `type`, descriptions and defaults
+//! carry over, unconditionally required fields are listed in `required`, and
+//! wildcard tables become `patternProperties`.
+
+use serde_json::{json, Map, Value as Json};
+
+use crate::compat::{INCLUDE_KEY, VERSION_KEY};
+use crate::prelude::*;
+use crate::schema::{bundled_schema, schema_version, FieldRule, FieldType, Schema};
+use crate::SchemaKind;
+
+const DRAFT: &str = "https://json-schema.org/draft/2020-12/schema";
+
+/// Pattern used for wildcard segments such as the `*` in `provider.*`.
+const WILDCARD_PATTERN: &str = "^.*$";
+
+/// Every enabled schema as one JSON object keyed by [`SchemaKind::name`],
+/// for editors that map files to schemas in a single setting.
+///
+/// Each entry holds the kind's `schema`, from [`to_json_schema`], and a
+/// `fileMatch` list of globs for the files it suggests it for, following the
+/// deployment directory layout (`config.toml`, `pipelines/*.toml`, ...).
+pub fn json_schema_bundle() -> Json {
+    let bundle = SchemaKind::all().iter().map(|kind| {
+        let entry = json!({
+            "fileMatch": [file_match(*kind)],
+            "schema": to_json_schema(*kind),
+        });
+        (kind.name().to_owned(), entry)
+    });
+    Json::Object(bundle.collect())
+}
+
+/// The files a kind's schema is suggested for.
+fn file_match(kind: SchemaKind) -> &'static str {
+    match kind {
+        #[cfg(feature = "schema-config")]
+        SchemaKind::Config => "**/config.toml",
+        #[cfg(feature = "schema-pipeline")]
+        SchemaKind::Pipeline => "**/pipelines/*.toml",
+        #[cfg(feature = "schema-mesh")]
+        SchemaKind::Mesh => "**/mesh/*.toml",
+        #[cfg(feature = "schema-remote-ingress")]
+        SchemaKind::RemoteIngress => "**/remote-ingress/*.toml",
+    }
+}
+
+/// Convert a bundled schema into a JSON Schema document.
+pub fn to_json_schema(kind: SchemaKind) -> Json {
+    let schema = bundled_schema(kind);
+    let mut root = convert(&schema);
+    let version = child(&mut root, VERSION_KEY);
+    version.insert("type".to_owned(), json!("string"));
+    version.insert(
+        "description".to_owned(),
+        json!("Schema version this file was written against"),
+    );
+    let include = child(&mut root, INCLUDE_KEY);
+    include.insert("type".to_owned(), json!("string"));
+    include.insert(
+        "description".to_owned(),
+        json!("Fragment merged beneath this file when its directory is validated"),
+    );
+    root.insert("$schema".to_owned(), json!(DRAFT));
+    root.insert(
+        "title".to_owned(),
+        json!(format!("Harmony {} {}", kind.name(), schema_version(kind))),
+    );
+    Json::Object(root)
+}
+
+fn convert(schema: &Schema) -> Map<String, Json> {
+    let mut root = object();
+    for rule in &schema.fields {
+        let segments: Vec<&str> = rule.segments().collect();
+        let Some((name, parents)) = segments.split_last() else {
+            continue;
+        };
+
+        let parent = descend(&mut root, parents);
+        if *name != "*" && rule.required && rule.required_if.is_none() {
+            push_required(parent, name);
+        }
+        let node = child(parent, name);
+        describe(node, rule);
+    }
+    root
+}
+
+/// Walk (creating as needed) the object node for `segments`, stepping into
+/// `items` whenever a segment is an array of tables.
+fn descend<'a>(
+    mut node: &'a mut Map<String, Json>,
+    segments: &[&str],
+) -> &'a mut Map<String, Json> {
+    for segment in segments {
+        node = child(node, segment);
+        if node.get("type") == Some(&json!("array")) {
+            node = node
+                .entry("items")
+                .or_insert_with(|| Json::Object(object()))
+                .as_object_mut()
+                .expect("items is always an object");
+        }
+    }
+    node
+}
+
+fn child<'a>(node: &'a mut Map<String, Json>, segment: &str) -> &'a mut Map<String, Json> {
+    let (container, key) = if segment == "*" {
+        ("patternProperties", WILDCARD_PATTERN)
+    } else {
+        ("properties", segment)
+    };
+    node.entry(container)
+        .or_insert_with(|| Json::Object(Map::new()))
+        .as_object_mut()
+        .expect("properties are always objects")
+        .entry(key)
+        .or_insert_with(|| Json::Object(object()))
+        .as_object_mut()
+        .expect("property schemas are always objects")
+}
+
+fn object() -> Map<String, Json> {
+    let mut node = Map::new();
+    node.insert("type".to_owned(), json!("object"));
+    node
+}
+
+fn push_required(node: &mut Map<String, Json>, name: &str) {
+    if let Json::Array(required) = node.entry("required").or_insert_with(|| json!([])) {
+        required.push(json!(name));
+    }
+}
+
+fn describe(node: &mut Map<String, Json>, rule: &FieldRule) {
+    node.insert("type".to_owned(), json!(json_type(rule.ty)));
+    if let Some(description) = &rule.description {
+        node.insert("description".to_owned(), json!(description));
+    }
+    if rule.deprecation.is_some() {
+        node.insert("deprecated".to_owned(), json!(true));
+    }
+    // Templated defaults depend on the document, so cannot be given here.
+    if let Some(default) = rule
+        .default
+        .as_ref()
+        .filter(|_| rule.default_references().is_empty())
+    {
+        if let Ok(default) = serde_json::to_value(default) {
+            node.insert("default".to_owned(), default);
+        }
+    }
+    if let Some(example) = &rule.example {
+        if let Ok(example) = serde_json::to_value(example) {
+            node.insert("examples".to_owned(), json!([example]));
+        }
+    }
+    if let (FieldType::Array, Some(item_type)) = (rule.ty, rule.item_type) {
+        let items = node
+            .entry("items")
+            .or_insert_with(|| Json::Object(Map::new()))
+            .as_object_mut()
+            .expect("items is always an object");
+        items.insert("type".to_owned(), json!(json_type(item_type)));
+        if let Some(allowed) = &rule.allowed_values {
+            items.insert("enum".to_owned(), json!(allowed));
+        }
+    } else if let Some(allowed) = &rule.allowed_values {
+        node.insert("enum".to_owned(), json!(allowed));
+    }
+    if let Some(pattern) = &rule.pattern {
+        node.insert("pattern".to_owned(), json!(pattern.as_str()));
+    }
+    if rule.non_empty && rule.ty == FieldType::String {
+        node.insert("minLength".to_owned(), json!(1));
+    }
+    if let Some(min) = rule.min {
+        node.insert("minimum".to_owned(), json!(min));
+    }
+    if let Some(max) = rule.max {
+        node.insert("maximum".to_owned(), json!(max));
+    }
+    if let Some(min_items) = rule.min_items {
+        node.insert("minItems".to_owned(), json!(min_items));
+    }
+    if let Some(max_items) = rule.max_items {
+        node.insert("maxItems".to_owned(), json!(max_items));
+    }
+}
+
+fn json_type(ty: FieldType) -> &'static str {
+    match ty {
+        FieldType::String
+        | FieldType::Duration
+        | FieldType::ByteSize
+        | FieldType::IpAddr
+        | FieldType::SocketAddr
+        | FieldType::Cidr
+        | FieldType::Email
+        | FieldType::Url => "string",
+        FieldType::Integer => "integer",
+        FieldType::Float => "number",
+        FieldType::Boolean => "boolean",
+        FieldType::Array => "array",
+        FieldType::Table { .. } => "object",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "schema-config")]
+    fn toml_to_json(input: &str) -> Json {
+        let value: toml::Table = toml::from_str(input).unwrap();
+        serde_json::to_value(value).unwrap()
+    }
+
+    #[test]
+    fn every_kind_produces_a_valid_json_schema() {
+        for kind in SchemaKind::all() {
+            let schema = to_json_schema(*kind);
+            assert_eq!(schema["$schema"], DRAFT);
+            assert!(
+                jsonschema::validator_for(&schema).is_ok(),
+                "{}",
+                kind.name()
+            );
+        }
+    }
+
+    #[test]
+    #[cfg(all(
+        feature = "schema-config",
+        feature = "schema-pipeline",
+        feature = "schema-mesh",
+        feature = "schema-remote-ingress"
+    ))]
+    fn the_bundle_holds_every_kind_with_a_file_match() {
+        let bundle = json_schema_bundle();
+        let entries = bundle.as_object().unwrap();
+        assert_eq!(entries.len(), SchemaKind::all().len());
+        for kind in SchemaKind::all() {
+            let entry = &entries[kind.name()];
+            assert_eq!(entry["schema"], to_json_schema(*kind));
+            assert!(entry["schema"].is_object());
+            assert!(entry["fileMatch"][0].as_str().unwrap().ends_with(".toml"));
+        }
+        assert_eq!(bundle["config"]["fileMatch"], json!(["**/config.toml"]));
+    }
+
+    #[test]
+    #[cfg(feature = "schema-config")]
+    fn wildcard_tables_become_pattern_properties() {
+        let schema = to_json_schema(SchemaKind::Config);
+        let provider = &schema["properties"]["provider"]["patternProperties"][WILDCARD_PATTERN];
+        assert_eq!(provider["type"], "object");
+        assert_eq!(provider["properties"]["poll_interval_secs"]["default"], 30);
+        let proxy = &schema["properties"]["proxy"];
+        assert_eq!(proxy["properties"]["id"]["examples"], json!(["edge-gw-01"]));
+        assert_eq!(schema["required"], json!(["proxy"]));
+    }
+
+    #[test]
+    #[cfg(feature = "schema-config")]
+    fn known_good_config_validates() {
+        let validator = jsonschema::validator_for(&to_json_schema(SchemaKind::Config)).unwrap();
+        let good = toml_to_json(
+            r#"
+[proxy]
+id = "gateway"
+
+[network.default.tcp_config]
+bind_address = "0.0.0.0"
+bind_port = 8080
+
+[services.http]
+module = ""
+type = ["endpoint", "backend"]
+"#,
+        );
+        assert!(validator.is_valid(&good));
+
+        let bad = toml_to_json("[services.http]\ntype = \"endpoint\"\n");
+        assert!(!validator.is_valid(&bad));
+
+        let bad = toml_to_json("[proxy]\nid = \"gw\"\n\n[logging]\nlog_level = \"loud\"\n");
+        assert!(!validator.is_valid(&bad));
+
+        let bad = toml_to_json("[proxy]\nid = \"gw\"\njwks_cache_duration_hours = 0\n");
+        assert!(!validator.is_valid(&bad));
+    }
+
+    #[test]
+    #[cfg(feature = "schema-pipeline")]
+    fn arrays_of_tables_describe_their_items() {
+        let schema = to_json_schema(SchemaKind::Pipeline);
+        let rules = &schema["properties"]["middleware"]["patternProperties"][WILDCARD_PATTERN]
+            ["properties"]["options"]["properties"]["rules"];
+        assert_eq!(rules["type"], "array");
+        assert_eq!(rules["items"]["type"], "object");
+        assert_eq!(rules["items"]["properties"]["allow"]["type"], "string");
+    }
+}
@@ -8,6 +8,7 @@
 //! - `harmony-config-schema.toml` - Main gateway configuration schema
 //! - `harmony-pipeline-schema.toml` - Pipeline configuration schema
 //! - `harmony-mesh-schema.toml` - Data mesh configuration schema
+//! - `harmony-common-schema.toml` - Shared definitions referenced by the other schemas
 //!
 //! ## Usage
 //!
@@ -15,6 +16,16 @@
 //! consumers for validation purposes. The schema files are located in the
 //! crate root and can be referenced when building validators.
 //!
+//! The crate also ships a validator, so consumers don't need to write their
+//! own:
+//!
+//! ```
+//! # #[cfg(feature = "schema-config")] {
+//! let errors = harmony_dsl::validate_config("[logging]\nlog_level = \"info\"\n").unwrap_err();
+//! assert_eq!(errors[0].path, "proxy");
+//! # }
+//! ```
+//!
 //! ## Schema Files
 //!
 //! ### harmony-config-schema.toml
@@ -44,6 +55,43 @@
 //! - Ingress configurations (how mesh requests arrive)
 //! - Egress configurations (how mesh requests are sent)
 //!
+//! ## Cargo Features
+//!
+//! Each bundled schema sits behind a default feature: `schema-config`,
+//! `schema-pipeline`, `schema-mesh` and `schema-remote-ingress`. Builds that
+//! only need some of them can disable the defaults and list those. A disabled
+//! schema's constant and [`SchemaKind`] variant are not compiled, and neither
+//! are the modules that depend on it ([`model`], [`builder`], [`migrate`],
+//! [`defaults`], [`canonical`], [`merge`], [`mod@plan`], [`mod@redact`] and [`rules`] need
+//! `schema-config`; [`directory`] needs the config, pipeline and mesh
+//! schemas). With no schema at all, [`validate_with_schema`] still
+//! validates against a schema the caller supplies.
+//!
+//! The default `std` feature provides everything that needs the standard
+//! library: the filesystem and IO helpers ([`directory`], [`report`]), the
+//! `toml_edit`-based [`apply_fixes`], [`document`], [`migrate`], [`plan()`]
+//! and [`redact()`], and [`env`]. Without it the crate is `no_std` and only
+//! needs `alloc`, so schema parsing and validation run on targets without
+//! an operating system; [`ValidationError`]s then carry no `file`.
+//!
+//! The optional `tokio` feature adds
+//! [`validate_directory_async`](directory::validate_directory_async), which
+//! validates a deployment's files concurrently, and
+//! [`validate_directory_streaming`](directory::validate_directory_streaming),
+//! which also hands over each file's report as it completes. The optional
+//! `archive` feature adds [`validate_archive`](directory::validate_archive), which
+//! validates a deployment packed as a tar or zip in memory. The optional `fs-checks`
+//! feature makes validation look at the files a config names: TLS
+//! certificates and keys must exist and hold PEM data, and the log file's
+//! directory must exist and be writable. The optional `binary` feature adds
+//! [`Schema::to_bytes`] and [`Schema::from_bytes`], so a proxy can cache a
+//! parsed schema instead of parsing TOML at every start. The optional
+//! `remote` feature adds [`fetch_schema`](remote::fetch_schema), which
+//! downloads a schema the cloud API publishes, to validate against when the
+//! bundled one may be out of date. The optional `wireguard-keys` feature
+//! derives each WireGuard interface's public key from its private key, to
+//! report a peer listing the interface's own key.
+//!
 //! ## Cross-Language Support
 //!
 //! These schemas are designed to work with both:
@@ -52,56 +100,402 @@
 //!
 //! ## Version
 //!
-//! Schema version: Config 1.11.0 / Pipeline 1.10.0 / Mesh 1.11.0
+//! Each schema declares its own version in its `[schema]` table; read it at
+//! runtime with [`schema_version`] rather than hardcoding it.
 //!
 //! ## License
 //!
 //! MIT License - See LICENSE file for details
 
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+use prelude::*;
+
+/// What the `std` prelude adds to `core`'s, for modules that also build
+/// without `std`.
+mod prelude {
+    pub(crate) use alloc::borrow::ToOwned;
+    pub(crate) use alloc::boxed::Box;
+    pub(crate) use alloc::string::{String, ToString};
+    pub(crate) use alloc::vec::Vec;
+    pub(crate) use alloc::{format, vec};
+}
+
 /// The contents of the harmony-config-schema.toml file
+#[cfg(feature = "schema-config")]
 pub const CONFIG_SCHEMA: &str = include_str!("../harmony-config-schema.toml");
 
 /// The contents of the harmony-pipeline-schema.toml file
+#[cfg(feature = "schema-pipeline")]
 pub const PIPELINE_SCHEMA: &str = include_str!("../harmony-pipeline-schema.toml");
 
 /// The contents of the harmony-mesh-schema.toml file
+#[cfg(feature = "schema-mesh")]
 pub const MESH_SCHEMA: &str = include_str!("../harmony-mesh-schema.toml");
 
 /// The contents of the harmony-remote-ingress-schema.toml file
-pub const REMOTE_INGRESS_SCHEMA: &str =
-    include_str!("../harmony-remote-ingress-schema.toml");
+#[cfg(feature = "schema-remote-ingress")]
+pub const REMOTE_INGRESS_SCHEMA: &str = include_str!("../harmony-remote-ingress-schema.toml");
+
+/// The contents of the harmony-common-schema.toml file: shared definitions
+/// the other schemas pull in with `ref = "common.<name>"`
+pub const COMMON_SCHEMA: &str = include_str!("../harmony-common-schema.toml");
+
+/// Selects one of the bundled schemas at runtime.
+///
+/// Only the variants whose `schema-*` feature is enabled exist.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SchemaKind {
+    /// Main gateway configuration (`config.toml`), see [`CONFIG_SCHEMA`].
+    #[cfg(feature = "schema-config")]
+    Config,
+    /// Pipeline configuration (`pipelines/*.toml`), see [`PIPELINE_SCHEMA`].
+    #[cfg(feature = "schema-pipeline")]
+    Pipeline,
+    /// Data mesh configuration (`mesh/*.toml`), see [`MESH_SCHEMA`].
+    #[cfg(feature = "schema-mesh")]
+    Mesh,
+    /// Remote ingress catalogue, see [`REMOTE_INGRESS_SCHEMA`].
+    #[cfg(feature = "schema-remote-ingress")]
+    RemoteIngress,
+}
+
+/// Error returned when parsing a [`SchemaKind`] from an unrecognised name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownSchemaKind(pub String);
+
+impl core::fmt::Display for UnknownSchemaKind {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "unknown schema kind `{}`; expected config, pipeline, mesh or remote-ingress",
+            self.0
+        )
+    }
+}
+
+impl core::error::Error for UnknownSchemaKind {}
 
-#[cfg(test)]
+impl SchemaKind {
+    /// Every enabled schema kind, in a stable order.
+    pub fn all() -> &'static [SchemaKind] {
+        &[
+            #[cfg(feature = "schema-config")]
+            SchemaKind::Config,
+            #[cfg(feature = "schema-pipeline")]
+            SchemaKind::Pipeline,
+            #[cfg(feature = "schema-mesh")]
+            SchemaKind::Mesh,
+            #[cfg(feature = "schema-remote-ingress")]
+            SchemaKind::RemoteIngress,
+        ]
+    }
+
+    /// The name accepted by [`SchemaKind::from_str`](core::str::FromStr::from_str),
+    /// e.g. `"remote-ingress"`.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            #[cfg(feature = "schema-config")]
+            SchemaKind::Config => "config",
+            #[cfg(feature = "schema-pipeline")]
+            SchemaKind::Pipeline => "pipeline",
+            #[cfg(feature = "schema-mesh")]
+            SchemaKind::Mesh => "mesh",
+            #[cfg(feature = "schema-remote-ingress")]
+            SchemaKind::RemoteIngress => "remote-ingress",
+        }
+    }
+
+    /// The bundled schema source for this kind.
+    pub fn source(&self) -> &'static str {
+        match *self {
+            #[cfg(feature = "schema-config")]
+            SchemaKind::Config => CONFIG_SCHEMA,
+            #[cfg(feature = "schema-pipeline")]
+            SchemaKind::Pipeline => PIPELINE_SCHEMA,
+            #[cfg(feature = "schema-mesh")]
+            SchemaKind::Mesh => MESH_SCHEMA,
+            #[cfg(feature = "schema-remote-ingress")]
+            SchemaKind::RemoteIngress => REMOTE_INGRESS_SCHEMA,
+        }
+    }
+}
+
+impl core::str::FromStr for SchemaKind {
+    type Err = UnknownSchemaKind;
+
+    /// Parses `config`, `pipeline`, `mesh` or `remote-ingress` (`remote_ingress`
+    /// is accepted too). Names of disabled kinds are unknown.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            #[cfg(feature = "schema-config")]
+            "config" => Ok(SchemaKind::Config),
+            #[cfg(feature = "schema-pipeline")]
+            "pipeline" => Ok(SchemaKind::Pipeline),
+            #[cfg(feature = "schema-mesh")]
+            "mesh" => Ok(SchemaKind::Mesh),
+            #[cfg(feature = "schema-remote-ingress")]
+            "remote-ingress" | "remote_ingress" => Ok(SchemaKind::RemoteIngress),
+            other => Err(UnknownSchemaKind(other.to_owned())),
+        }
+    }
+}
+
+#[cfg(feature = "binary")]
+pub mod binary;
+#[cfg(feature = "schema-config")]
+pub mod builder;
+#[cfg(feature = "schema-config")]
+pub mod canonical;
+pub mod check;
+pub mod compat;
+pub mod crossref;
+#[cfg(feature = "schema-config")]
+pub mod defaults;
+pub mod deprecations;
+pub mod diff;
+#[cfg(all(
+    feature = "std",
+    feature = "schema-config",
+    feature = "schema-pipeline",
+    feature = "schema-mesh",
+    feature = "schema-remote-ingress"
+))]
+pub mod directory;
+#[cfg(feature = "std")]
+pub mod document;
+#[cfg(feature = "std")]
+pub mod env;
+pub mod explain;
+pub mod fix;
+pub mod json_schema;
+pub mod lint;
+pub mod lsp;
+#[cfg(feature = "schema-config")]
+pub mod merge;
+pub mod messages;
+#[cfg(all(feature = "std", feature = "schema-config"))]
+pub mod migrate;
+#[cfg(feature = "schema-config")]
+pub mod model;
+#[cfg(all(feature = "std", feature = "schema-config"))]
+pub mod plan;
+#[cfg(all(feature = "std", feature = "schema-config"))]
+pub mod redact;
+#[cfg(feature = "remote")]
+pub mod remote;
+#[cfg(feature = "std")]
+pub mod report;
+#[cfg(feature = "schema-pipeline")]
+pub mod routing;
+#[cfg(feature = "schema-config")]
+pub mod rules;
+pub mod scaffold;
+pub mod schema;
+mod span;
+mod suggest;
+pub mod validate;
+pub mod validators;
+pub mod visit;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+pub mod wire;
+
+#[cfg(all(feature = "binary", feature = "std"))]
+pub use binary::write_schema_bytes;
+#[cfg(feature = "schema-config")]
+pub use builder::{ConfigBuilder, S3Config};
+#[cfg(feature = "schema-config")]
+pub use canonical::{canonicalize, canonicalize_with, CanonicalizeOptions};
+pub use check::{check, CheckOptions, Finding, FindingCategory};
+pub use compat::{check_compatibility, detect_schema_version, Compatibility, DetectionResult};
+#[cfg(all(feature = "std", feature = "schema-config"))]
+pub use crossref::validate_bundle;
+pub use crossref::{
+    unknown_pipeline_references, unknown_providers, unreachable_pipelines,
+    validate_cross_references, CrossRefError,
+};
+#[cfg(feature = "schema-config")]
+pub use defaults::{apply_defaults, effective_overrides};
+pub use deprecations::{deprecations_in_use, DeprecationNotice};
+pub use diff::{
+    diff_schemas, suggest_migration, ListChange, MigrationHint, MigrationHintKind, SchemaDiff,
+};
+#[cfg(all(
+    feature = "archive",
+    feature = "schema-config",
+    feature = "schema-pipeline",
+    feature = "schema-mesh",
+    feature = "schema-remote-ingress"
+))]
+pub use directory::validate_archive;
+#[cfg(all(
+    feature = "std",
+    feature = "schema-config",
+    feature = "schema-pipeline",
+    feature = "schema-mesh",
+    feature = "schema-remote-ingress"
+))]
+pub use directory::{
+    validate_directory, validate_directory_cached, DeploymentStats, DirectoryReport, FileReport,
+    ValidationCache,
+};
+#[cfg(all(
+    feature = "tokio",
+    feature = "schema-config",
+    feature = "schema-pipeline",
+    feature = "schema-mesh",
+    feature = "schema-remote-ingress"
+))]
+pub use directory::{validate_directory_async, validate_directory_streaming};
+#[cfg(feature = "std")]
+pub use document::{get_value, Document};
+#[cfg(feature = "std")]
+pub use env::{validate_env_placeholders, EnvError, EnvErrorKind};
+pub use explain::{explain, FieldTrace, TraceOutcome};
+#[cfg(feature = "std")]
+pub use fix::apply_fixes;
+pub use fix::{Fix, FixAction};
+pub use json_schema::{json_schema_bundle, to_json_schema};
+pub use lint::{lint, lint_with, Lint, LintLevel, LintOptions};
+pub use lsp::{
+    completions, diagnostics, Completion, CompletionKind, Diagnostic, DiagnosticSeverity,
+    RelatedInformation, TextPosition, TextRange,
+};
+#[cfg(feature = "schema-config")]
+pub use merge::{merge_configs, merge_configs_with, ArrayMerge, MergeOptions};
+pub use messages::MessageCatalog;
+#[cfg(all(feature = "std", feature = "schema-config"))]
+pub use migrate::{migrate_config, MigrateError};
+#[cfg(feature = "schema-config")]
+pub use model::{
+    load_config, parse_config, parse_service_types, ConfigError, ProxyConfig, ServiceTypeRegistry,
+};
+#[cfg(all(feature = "std", feature = "schema-config"))]
+pub use plan::{plan, Plan, PlanOptions, PlannedChange};
+#[cfg(all(feature = "std", feature = "schema-config"))]
+pub use redact::redact;
+#[cfg(feature = "remote")]
+pub use remote::{fetch_schema, FetchError};
+#[cfg(all(
+    feature = "std",
+    feature = "schema-config",
+    feature = "schema-pipeline",
+    feature = "schema-mesh",
+    feature = "schema-remote-ingress"
+))]
+pub use report::to_sarif;
+#[cfg(feature = "std")]
+pub use report::{render_json, render_terminal};
+#[cfg(feature = "schema-pipeline")]
+pub use routing::{match_route, pipeline_routes, PipelineRoute, RouteMatch};
+#[cfg(feature = "schema-config")]
+pub use rules::{validate_config_with_rules, ValidationRule};
+#[cfg(feature = "schema-config")]
+pub use scaffold::minimal_valid;
+pub use scaffold::scaffold;
+pub use schema::{
+    all_field_paths, describe_field, enum_fields, field_tree, parse_metadata, parse_schema,
+    parse_version, required_fields, schema_hash, schema_metadata, schema_summary, schema_version,
+    validate_schema_itself, verify_schema, Condition, FieldNode, FieldRule, FieldType, Schema,
+    SchemaLint, SchemaLintKind, SchemaMetadata, SchemaParseError, SchemaSummary, WeightSum,
+};
+/// The schema version type, re-exported so callers of
+/// [`validate_for_version`] need not depend on `semver` themselves.
+pub use semver::Version;
+#[cfg(feature = "schema-remote-ingress")]
+pub use validate::validate_remote_ingress;
+pub use validate::{
+    validate, validate_best_effort, validate_bytes, validate_coerced, validate_for_version,
+    validate_json, validate_report, validate_report_with, validate_subtree, validate_value,
+    validate_with, validate_with_schema, ErrorCode, Severity, ValidationError, ValidationErrorKind,
+    ValidationErrors, ValidationMode, ValidationOptions, ValidationReport,
+};
+#[cfg(feature = "schema-config")]
+pub use validate::{validate_config, validate_config_with};
+#[cfg(feature = "std")]
+pub use validate::{validate_reader, validate_reader_with};
+pub use validators::{validator, validators, Validator};
+pub use visit::{walk, ConfigVisitor};
+pub use wire::{from_wire, to_wire, WireDiagnostic, WireError, WireReport};
+
+#[cfg(all(
+    test,
+    any(
+        feature = "schema-config",
+        feature = "schema-pipeline",
+        feature = "schema-mesh",
+        feature = "schema-remote-ingress"
+    )
+))]
 mod tests {
     use super::*;
 
     #[test]
+    #[cfg(feature = "schema-config")]
     fn config_schema_is_not_empty() {
         assert!(!CONFIG_SCHEMA.is_empty());
         assert!(CONFIG_SCHEMA.contains("[schema]"));
     }
 
     #[test]
+    #[cfg(feature = "schema-config")]
     fn config_schema_has_provider_table() {
         assert!(CONFIG_SCHEMA.contains("provider.*"));
         assert!(CONFIG_SCHEMA.contains("Provider configuration for resource resolution"));
     }
 
     #[test]
+    #[cfg(feature = "schema-pipeline")]
     fn pipeline_schema_is_not_empty() {
         assert!(!PIPELINE_SCHEMA.is_empty());
         assert!(PIPELINE_SCHEMA.contains("[schema]"));
     }
 
     #[test]
+    #[cfg(feature = "schema-mesh")]
     fn mesh_schema_is_not_empty() {
         assert!(!MESH_SCHEMA.is_empty());
         assert!(MESH_SCHEMA.contains("[schema]"));
     }
 
     #[test]
+    #[cfg(feature = "schema-remote-ingress")]
     fn remote_ingress_schema_is_not_empty() {
         assert!(!REMOTE_INGRESS_SCHEMA.is_empty());
         assert!(REMOTE_INGRESS_SCHEMA.contains("[schema]"));
     }
+
+    #[test]
+    #[cfg(feature = "schema-remote-ingress")]
+    fn schema_kind_round_trips_through_its_name() {
+        for kind in SchemaKind::all() {
+            assert_eq!(kind.name().parse::<SchemaKind>(), Ok(*kind));
+        }
+        assert_eq!(
+            "remote_ingress".parse::<SchemaKind>(),
+            Ok(SchemaKind::RemoteIngress)
+        );
+        assert_eq!(
+            "gateway".parse::<SchemaKind>(),
+            Err(UnknownSchemaKind("gateway".to_owned()))
+        );
+    }
+
+    #[test]
+    #[cfg(all(
+        feature = "schema-config",
+        feature = "schema-pipeline",
+        feature = "schema-mesh",
+        feature = "schema-remote-ingress"
+    ))]
+    fn schema_kind_source_matches_constants() {
+        assert_eq!(SchemaKind::Config.source(), CONFIG_SCHEMA);
+        assert_eq!(SchemaKind::Pipeline.source(), PIPELINE_SCHEMA);
+        assert_eq!(SchemaKind::Mesh.source(), MESH_SCHEMA);
+        assert_eq!(SchemaKind::RemoteIngress.source(), REMOTE_INGRESS_SCHEMA);
+        assert_eq!(SchemaKind::all().len(), 4);
+    }
 }
@@ -0,0 +1,331 @@
+//! Best-practice advisories beyond schema validation.
+//!
+//! A document can be valid and still risky: a listener on every interface
+//! with no TLS, a secret committed in plain text. [`lint`] reports such
+//! patterns as advisories. It is separate from [`validate`](crate::validate),
+//! so callers opt in, and each lint can be switched off by its code with
+//! [`LintOptions::allow`].
+
+use core::fmt;
+
+use toml::{Table, Value};
+
+use crate::prelude::*;
+use crate::schema::{bundled_schema, lookup, Schema};
+use crate::span::SpanIndex;
+use crate::validate::{element, instances, join, write_diagnostic};
+use crate::SchemaKind;
+
+/// One advisory found by [`lint`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lint {
+    /// Stable name of the lint, e.g. `bind_all_without_tls`, as listed in
+    /// [`LintOptions::allow`] to suppress it.
+    pub lint_code: &'static str,
+    /// How strongly the lint advises a change.
+    pub level: LintLevel,
+    /// Dotted path to the value the advisory is about.
+    pub path: String,
+    /// Human-readable description of the risk.
+    pub message: String,
+    /// 1-based `(line, column)` of the value in the original TOML.
+    pub span: Option<(usize, usize)>,
+}
+
+/// How strongly a [`Lint`] advises a change.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum LintLevel {
+    /// Likely a mistake or a security risk.
+    Warning,
+    /// Worth a second look, but often intended.
+    Info,
+}
+
+/// Settings for [`lint_with`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LintOptions {
+    /// Codes of lints not to run, e.g. `["permissive_cidr"]`.
+    pub allow: Vec<String>,
+}
+
+/// Formats as `line:column: path: message [lint_code]`.
+impl fmt::Display for Lint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_diagnostic(f, None::<&str>, self.span, &self.path, &self.message)?;
+        write!(f, " [{}]", self.lint_code)
+    }
+}
+
+/// Run every lint over `toml_input`, a document of `kind`, returning the
+/// advisories in document order. Documents that are not valid TOML yield
+/// none; [`validate`](crate::validate) reports those.
+pub fn lint(kind: SchemaKind, toml_input: &str) -> Vec<Lint> {
+    lint_with(kind, toml_input, LintOptions::default())
+}
+
+/// [`lint`] with explicit [`LintOptions`].
+pub fn lint_with(kind: SchemaKind, toml_input: &str, options: LintOptions) -> Vec<Lint> {
+    let Ok(doc) = toml::from_str::<Table>(toml_input) else {
+        return Vec::new();
+    };
+    let schema = bundled_schema(kind);
+    let mut linter = Linter {
+        schema: &schema,
+        spans: SpanIndex::build(toml_input),
+        lints: Vec::new(),
+    };
+    let enabled = |code: &str| !options.allow.iter().any(|allowed| allowed == code);
+    if enabled(BIND_ALL_WITHOUT_TLS) {
+        linter.bind_all_without_tls(&doc);
+    }
+    if enabled(INLINE_SECRET) {
+        linter.inline_secret(&doc);
+    }
+    if enabled(PERMISSIVE_CIDR) {
+        linter.permissive_cidr(&doc);
+    }
+    let mut lints = linter.lints;
+    lints.sort_by_key(|lint| lint.span);
+    lints
+}
+
+const BIND_ALL_WITHOUT_TLS: &str = "bind_all_without_tls";
+const INLINE_SECRET: &str = "inline_secret";
+const PERMISSIVE_CIDR: &str = "permissive_cidr";
+
+/// Runs lints over one document. Each lint only looks at tables the
+/// document's schema declares.
+struct Linter<'a> {
+    schema: &'a Schema,
+    spans: Option<SpanIndex<'a>>,
+    lints: Vec<Lint>,
+}
+
+impl Linter<'_> {
+    fn push(&mut self, lint_code: &'static str, level: LintLevel, path: String, message: String) {
+        let span = self.spans.as_ref().and_then(|spans| spans.position(&path));
+        self.lints.push(Lint {
+            lint_code,
+            level,
+            path,
+            message,
+            span,
+        });
+    }
+
+    fn declares(&self, path: &str) -> bool {
+        self.schema.fields.iter().any(|rule| rule.path == path)
+    }
+
+    /// A TCP listener on every interface that serves plain HTTP.
+    fn bind_all_without_tls(&mut self, doc: &Table) {
+        if !self.declares("network.*.tcp_config.bind_address") {
+            return;
+        }
+        for (path, listener) in instances(doc, &["network", "*", "tcp_config"], "") {
+            let address = listener.get("bind_address").and_then(Value::as_str);
+            let tls = listener.contains_key("cert_path") && listener.contains_key("key_path");
+            if let (Some(address @ ("0.0.0.0" | "::")), false) = (address, tls) {
+                self.push(
+                    BIND_ALL_WITHOUT_TLS,
+                    LintLevel::Warning,
+                    format!("{path}.bind_address"),
+                    format!(
+                        "listening on `{address}` without TLS exposes plain HTTP on every \
+                         interface; set `cert_path` and `key_path`, or bind a private address"
+                    ),
+                );
+            }
+        }
+    }
+
+    /// A field the schema marks `secret = true` written into the file rather
+    /// than read from the environment as an `${ENV_VAR}` placeholder.
+    fn inline_secret(&mut self, doc: &Table) {
+        let schema = self.schema;
+        for rule in schema.fields.iter().filter(|rule| rule.secret) {
+            let table: Vec<&str> = rule
+                .table
+                .split('.')
+                .filter(|seg| !seg.is_empty())
+                .collect();
+            let field = rule.relative_path();
+            for (path, instance) in instances(doc, &table, "") {
+                if rule
+                    .extension
+                    .as_ref()
+                    .is_some_and(|when| !when.holds(instance))
+                {
+                    continue;
+                }
+                let Some(value) = lookup(instance, field).and_then(Value::as_str) else {
+                    continue;
+                };
+                if !value.contains("${") {
+                    self.push(
+                        INLINE_SECRET,
+                        LintLevel::Warning,
+                        join(&path, field),
+                        format!(
+                            "`{field}` is written inline; read it from an `${{ENV_VAR}}` placeholder"
+                        ),
+                    );
+                }
+            }
+        }
+    }
+
+    /// A WireGuard peer allowed to send from, and routed, every address.
+    fn permissive_cidr(&mut self, doc: &Table) {
+        if !self.declares("network.*.wireguard_peers.*.allowed_ips") {
+            return;
+        }
+        for (path, peer) in instances(doc, &["network", "*", "wireguard_peers", "*"], "") {
+            let ips = peer.get("allowed_ips").and_then(Value::as_array);
+            for (index, ip) in ips.into_iter().flatten().enumerate() {
+                if let Some(range @ ("0.0.0.0/0" | "::/0")) = ip.as_str() {
+                    self.push(
+                        PERMISSIVE_CIDR,
+                        LintLevel::Info,
+                        element(&format!("{path}.allowed_ips"), index),
+                        format!("`{range}` routes all traffic through this peer"),
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[cfg(all(test, feature = "schema-config"))]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"[proxy]
+id = "gw"
+
+[management]
+enabled = true
+network = "default"
+auth_token = "hunter2"
+
+[network.default.tcp_config]
+bind_address = "0.0.0.0"
+bind_port = 8080
+
+[network.internal.tcp_config]
+bind_address = "10.0.0.1"
+bind_port = 8081
+
+[network.internal.wireguard_peers.hub]
+public_key = "xTIBA5rboUvnH4htodjb6e697QjLERt1NAB4mZqp8Dg="
+allowed_ips = ["10.0.0.2/32", "0.0.0.0/0"]
+"#;
+
+    fn codes(lints: &[Lint]) -> Vec<(&'static str, &str, LintLevel)> {
+        lints
+            .iter()
+            .map(|lint| (lint.lint_code, lint.path.as_str(), lint.level))
+            .collect()
+    }
+
+    #[test]
+    fn lints_fire_in_document_order() {
+        let lints = lint(SchemaKind::Config, CONFIG);
+        assert_eq!(
+            codes(&lints),
+            [
+                (INLINE_SECRET, "management.auth_token", LintLevel::Warning),
+                (
+                    BIND_ALL_WITHOUT_TLS,
+                    "network.default.tcp_config.bind_address",
+                    LintLevel::Warning
+                ),
+                (
+                    PERMISSIVE_CIDR,
+                    "network.internal.wireguard_peers.hub.allowed_ips[1]",
+                    LintLevel::Info
+                ),
+            ]
+        );
+        assert_eq!(lints[0].span, Some((7, 14)));
+        assert_eq!(
+            lints[2].to_string(),
+            "19:31: network.internal.wireguard_peers.hub.allowed_ips[1]: \
+             `0.0.0.0/0` routes all traffic through this peer [permissive_cidr]"
+        );
+    }
+
+    #[test]
+    fn every_secret_field_is_linted() {
+        let config = r#"[proxy]
+id = "gw"
+
+[network.default]
+enable_wireguard = true
+private_key = "yAnz5TF+lXXJte14tji3zlMNq+hd2rYUIgJBgB3fBmk="
+
+[storage]
+backend = "s3"
+options = { bucket = "scans", secret_access_key = "wJalrXUtnFEMI" }
+
+[authentications.basic]
+type = "basic"
+options = { username = "admin", password = "${ADMIN_PASSWORD}" }
+
+[authentications.jwt]
+type = "jwt"
+options = { hs256_secret = "shared" }
+"#;
+        assert_eq!(
+            codes(&lint(SchemaKind::Config, config)),
+            [
+                (
+                    INLINE_SECRET,
+                    "network.default.private_key",
+                    LintLevel::Warning
+                ),
+                (
+                    INLINE_SECRET,
+                    "storage.options.secret_access_key",
+                    LintLevel::Warning
+                ),
+                (
+                    INLINE_SECRET,
+                    "authentications.jwt.options.hs256_secret",
+                    LintLevel::Warning
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "schema-mesh")]
+    fn lints_can_be_suppressed() {
+        let options = LintOptions {
+            allow: vec![INLINE_SECRET.to_owned(), PERMISSIVE_CIDR.to_owned()],
+        };
+        let lints = lint_with(SchemaKind::Config, CONFIG, options);
+        assert_eq!(
+            codes(&lints),
+            [(
+                BIND_ALL_WITHOUT_TLS,
+                "network.default.tcp_config.bind_address",
+                LintLevel::Warning
+            )]
+        );
+
+        let secured = CONFIG.replace("\"hunter2\"", "\"${ADMIN_TOKEN}\"").replace(
+            "bind_port = 8080",
+            "bind_port = 8080\ncert_path = \"a.pem\"\nkey_path = \"a.key\"",
+        );
+        assert_eq!(
+            codes(&lint(SchemaKind::Config, &secured)),
+            [(
+                PERMISSIVE_CIDR,
+                "network.internal.wireguard_peers.hub.allowed_ips[1]",
+                LintLevel::Info
+            )]
+        );
+        assert!(lint(SchemaKind::Mesh, CONFIG).is_empty());
+    }
+}
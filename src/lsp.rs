@@ -0,0 +1,406 @@
+//! Diagnostics and completions in the shape the Language Server Protocol
+//! expects.
+//!
+//! An editor integration forwards [`diagnostics`] as the `diagnostics` of a
+//! `textDocument/publishDiagnostics` notification. Unlike
+//! [`ValidationError::span`], ranges cover the whole offending token and are
+//! 0-based, counting columns in UTF-16 code units as LSP does by default.
+//! [`completions`] answers a `textDocument/completion` request.
+
+use serde::{Serialize, Serializer};
+use toml::{Table, Value};
+
+use crate::prelude::*;
+use crate::schema::{bundled_schema, FieldType, Schema};
+use crate::span::{line_col, offset, SpanIndex};
+use crate::validate::{validate_report, ErrorCode, Severity, ValidationError, ValidationErrorKind};
+use crate::SchemaKind;
+
+/// A validation error as an LSP `Diagnostic`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Diagnostic {
+    /// The offending token, or the start of the document for errors that
+    /// apply to all of it.
+    pub range: TextRange,
+    /// The error's [`Severity`].
+    pub severity: DiagnosticSeverity,
+    /// The error's [`ErrorCode`], serialized as its string.
+    pub code: ErrorCode,
+    /// The error's message.
+    pub message: String,
+    /// Other places involved, e.g. the first definition of a duplicate key.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub related_information: Vec<RelatedInformation>,
+}
+
+/// Something that could be typed at the cursor, from [`completions`], as an
+/// LSP `CompletionItem`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Completion {
+    /// The key or value, e.g. `bind_port` or `info`.
+    pub label: String,
+    /// Whether the completion is a field, a table or an allowed value.
+    pub kind: CompletionKind,
+    /// The declared type of a key, e.g. `integer`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    /// The schema's description of a key.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub documentation: Option<String>,
+    /// What to insert when it differs from the label: string values are
+    /// quoted unless the cursor is already inside a string.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub insert_text: Option<String>,
+}
+
+/// LSP `CompletionItemKind`, serialized as its number.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CompletionKind {
+    /// A key holding a value.
+    Field = 5,
+    /// A key holding a table.
+    Module = 9,
+    /// One of a field's allowed values.
+    EnumMember = 20,
+}
+
+/// LSP `DiagnosticSeverity`, serialized as its number.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DiagnosticSeverity {
+    /// [`Severity::Error`].
+    Error = 1,
+    /// [`Severity::Warning`].
+    Warning = 2,
+    /// [`Severity::Info`].
+    Information = 3,
+}
+
+/// An LSP `Range`: from `start` up to, not including, `end`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct TextRange {
+    /// The first character of the range.
+    pub start: TextPosition,
+    /// The character after the last one in the range.
+    pub end: TextPosition,
+}
+
+/// An LSP `Position`, 0-based, with `character` in UTF-16 code units.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub struct TextPosition {
+    /// The line, counting from 0.
+    pub line: u32,
+    /// The column in UTF-16 code units, counting from 0.
+    pub character: u32,
+}
+
+/// An LSP `DiagnosticRelatedInformation` without its `location.uri`, which
+/// is always the diagnosed document; the server adds it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RelatedInformation {
+    /// Where in the document the other place is.
+    pub range: TextRange,
+    /// What the other place is, e.g. `` `id` is first defined here``.
+    pub message: String,
+}
+
+impl Serialize for DiagnosticSeverity {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u8(*self as u8)
+    }
+}
+
+impl Serialize for CompletionKind {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u8(*self as u8)
+    }
+}
+
+impl From<Severity> for DiagnosticSeverity {
+    fn from(severity: Severity) -> Self {
+        match severity {
+            Severity::Error => DiagnosticSeverity::Error,
+            Severity::Warning => DiagnosticSeverity::Warning,
+            Severity::Info => DiagnosticSeverity::Information,
+        }
+    }
+}
+
+/// Validate `toml_input`, a document of `kind`, and return its errors and
+/// warnings as LSP diagnostics, in the order of
+/// [`validate_report`](crate::validate_report).
+///
+/// Meant to run on every change in an editor, so it accepts any input,
+/// including half-typed documents that are not valid TOML yet.
+pub fn diagnostics(kind: SchemaKind, toml_input: &str) -> Vec<Diagnostic> {
+    let spans = SpanIndex::build(toml_input);
+    validate_report(kind, toml_input)
+        .into_diagnostics()
+        .into_iter()
+        .map(|error| diagnostic(toml_input, spans.as_ref(), error))
+        .collect()
+}
+
+/// What could be typed at byte `offset` of `toml_input`, a document of
+/// `kind`.
+///
+/// After `key =`, these are the allowed values of the field, if the schema
+/// lists them. Elsewhere outside a table header, they are the keys the
+/// schema declares in the table the cursor is in, as set by the last header
+/// above it, or under the dotted key typed so far, with their types and
+/// descriptions. Keys are sorted and not narrowed by what is typed, which
+/// editors filter on as the user types.
+///
+/// Like [`diagnostics`], this accepts any input, since the document being
+/// edited is rarely valid TOML. Values inside inline tables get no
+/// completions.
+pub fn completions(kind: SchemaKind, toml_input: &str, offset: usize) -> Vec<Completion> {
+    let mut offset = offset.min(toml_input.len());
+    while !toml_input.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    let line_start = toml_input[..offset].rfind('\n').map_or(0, |pos| pos + 1);
+    let typed = toml_input[line_start..offset].trim_start();
+    if typed.starts_with('[') || typed.starts_with('#') {
+        return Vec::new();
+    }
+    let mut segments = toml_input[..line_start]
+        .lines()
+        .rev()
+        .find_map(header)
+        .unwrap_or_default();
+    let schema = bundled_schema(kind);
+
+    if let Some((key, value)) = typed.split_once('=') {
+        let Some(key) = dotted_key(key.trim()) else {
+            return Vec::new();
+        };
+        segments.extend(key);
+        return values(&schema, &segments, value);
+    }
+    // Every dotted segment typed before the one being completed.
+    if let Some((parent, _)) = typed.rsplit_once('.') {
+        let Some(parent) = dotted_key(parent) else {
+            return Vec::new();
+        };
+        segments.extend(parent);
+    }
+    schema
+        .known_keys(&segments)
+        .into_iter()
+        .map(|key| {
+            let mut path = segments.clone();
+            path.push(key.to_owned());
+            let rule = schema.find(&path);
+            let table = rule.is_none_or(|rule| matches!(rule.ty, FieldType::Table { .. }));
+            Completion {
+                label: key.to_owned(),
+                kind: if table {
+                    CompletionKind::Module
+                } else {
+                    CompletionKind::Field
+                },
+                detail: rule.map(|rule| rule.ty.as_str().to_owned()),
+                documentation: rule.and_then(|rule| rule.description.clone()),
+                insert_text: None,
+            }
+        })
+        .collect()
+}
+
+/// The allowed values of the field at `segments`, with `typed` the text
+/// after its `=` up to the cursor.
+fn values(schema: &Schema, segments: &[String], typed: &str) -> Vec<Completion> {
+    let Some(rule) = schema.find(segments) else {
+        return Vec::new();
+    };
+    let strings = rule.ty == FieldType::String
+        || (rule.ty == FieldType::Array && rule.item_type == Some(FieldType::String));
+    let in_string = typed.matches('"').count() % 2 == 1;
+    let allowed = rule.allowed_values.iter().flatten();
+    allowed
+        .map(|value| Completion {
+            label: value.clone(),
+            kind: CompletionKind::EnumMember,
+            detail: None,
+            documentation: None,
+            insert_text: (strings && !in_string).then(|| format!("\"{value}\"")),
+        })
+        .collect()
+}
+
+/// The path of the table a `[table]` or `[[array]]` header line opens.
+fn header(line: &str) -> Option<Vec<String>> {
+    let line = line.trim_start();
+    let key = match line.strip_prefix("[[") {
+        Some(rest) => rest.split_once("]]")?.0,
+        None => line.strip_prefix('[')?.split_once(']')?.0,
+    };
+    dotted_key(key)
+}
+
+/// The segments of a TOML key such as `tcp_config."bind.port"`, by parsing
+/// it as the key of a one-line document.
+fn dotted_key(key: &str) -> Option<Vec<String>> {
+    let doc: Table = toml::from_str(&format!("{key} = 0")).ok()?;
+    let mut segments = Vec::new();
+    let mut table = &doc;
+    loop {
+        let (name, value) = table.iter().next()?;
+        segments.push(name.clone());
+        match value {
+            Value::Table(inner) => table = inner,
+            _ => return Some(segments),
+        }
+    }
+}
+
+fn diagnostic(src: &str, spans: Option<&SpanIndex<'_>>, error: ValidationError) -> Diagnostic {
+    let related_information = match &error.kind {
+        ValidationErrorKind::DuplicateKey {
+            key,
+            first_span: Some(first),
+        } => vec![RelatedInformation {
+            range: token(src, spans, &error.path, *first),
+            message: format!("`{key}` is first defined here"),
+        }],
+        _ => Vec::new(),
+    };
+    Diagnostic {
+        range: error
+            .span
+            .map(|span| token(src, spans, &error.path, span))
+            .unwrap_or_default(),
+        severity: error.severity.into(),
+        code: error.code,
+        message: error.message,
+        related_information,
+    }
+}
+
+/// The range of the token at the 1-based `span`: the value at `path` if it
+/// starts there, else the rest of the line. Tables are marked by their
+/// header.
+fn token(src: &str, spans: Option<&SpanIndex<'_>>, path: &str, span: (usize, usize)) -> TextRange {
+    let start = offset(src, span).unwrap_or(src.len());
+    let value = spans
+        .and_then(|spans| spans.range(path))
+        .filter(|range| range.start == start && range.end > start);
+    let end = match value {
+        Some(range) => range.end,
+        None => {
+            let line_end = src[start..].find('\n').map_or(src.len(), |end| start + end);
+            start + src[start..line_end].trim_end().len()
+        }
+    };
+    TextRange {
+        start: position(src, start),
+        end: position(src, end),
+    }
+}
+
+/// The LSP position of a byte offset.
+fn position(src: &str, offset: usize) -> TextPosition {
+    let (line, _) = line_col(src, offset);
+    let line_start = src[..offset].rfind('\n').map_or(0, |pos| pos + 1);
+    let character = src[line_start..offset].encode_utf16().count();
+    TextPosition {
+        line: (line - 1) as u32,
+        character: character as u32,
+    }
+}
+
+#[cfg(all(test, feature = "schema-config"))]
+mod tests {
+    use super::*;
+
+    fn range(start: (u32, u32), end: (u32, u32)) -> TextRange {
+        let position = |(line, character)| TextPosition { line, character };
+        TextRange {
+            start: position(start),
+            end: position(end),
+        }
+    }
+
+    #[test]
+    fn ranges_cover_the_offending_token() {
+        let config = "[proxy]\nid = \"gw\"\n\n[network.default.tcp_config]\n\
+                      bind_address = \"0.0.0.0\"\nbind_port = \"8080\" # 🚀\n";
+        let found = diagnostics(SchemaKind::Config, config);
+        assert_eq!(found.len(), 2);
+        assert_eq!(found[1].range, range((5, 12), (5, 18)));
+        assert_eq!(found[1].severity, DiagnosticSeverity::Error);
+        assert_eq!(found[1].code, ErrorCode::TypeMismatch);
+        let json = serde_json::to_value(&found[1]).unwrap();
+        assert_eq!(
+            json["range"]["end"],
+            serde_json::json!({ "line": 5, "character": 18 })
+        );
+        assert_eq!(json["severity"], 1);
+        assert_eq!(json["code"], "E_TYPE_MISMATCH");
+        assert!(json.get("relatedInformation").is_none());
+
+        // The advisory for the omitted `force_https` marks the table header.
+        assert_eq!(found[0].range, range((3, 0), (3, 28)));
+        assert_eq!(found[0].severity, DiagnosticSeverity::Information);
+        assert_eq!(found[0].code, ErrorCode::ImplicitSecurityDefault);
+        assert_eq!(serde_json::to_value(&found[0]).unwrap()["severity"], 3);
+
+        // The first definition of a duplicate key, with columns in UTF-16.
+        let found = diagnostics(SchemaKind::Config, "[proxy]\nid = \"𝄞\"\nid = \"b\"\n");
+        assert_eq!(found[0].code, ErrorCode::DuplicateKey);
+        assert_eq!(found[0].related_information[0].range, range((1, 5), (1, 9)));
+    }
+
+    #[test]
+    fn completions_offer_the_keys_and_values_valid_at_the_cursor() {
+        let labels = |src: &str| -> Vec<String> {
+            completions(SchemaKind::Config, src, src.len())
+                .into_iter()
+                .map(|completion| completion.label)
+                .collect()
+        };
+
+        let found = completions(SchemaKind::Config, "[proxy]\ni", 9);
+        let id = found
+            .iter()
+            .find(|completion| completion.label == "id")
+            .unwrap();
+        assert_eq!(id.kind, CompletionKind::Field);
+        assert_eq!(id.detail.as_deref(), Some("string"));
+        assert!(id.documentation.is_some());
+        let json = serde_json::to_value(id).unwrap();
+        assert_eq!(json["kind"], 5);
+        assert!(json.get("insertText").is_none());
+
+        // Keys of the table the cursor is in, and of dotted keys typed.
+        let logging = labels("[proxy]\nid = \"gw\"\n\n[logging]\n");
+        assert!(logging.iter().any(|key| key == "log_level"));
+        assert!(!logging.iter().any(|key| key == "id"));
+        let root = completions(SchemaKind::Config, "", 0);
+        let network = root.iter().find(|completion| completion.label == "network");
+        assert_eq!(
+            network.map(|completion| completion.kind),
+            Some(CompletionKind::Module)
+        );
+        let tcp = labels("[network.default]\ntcp_config.");
+        assert!(tcp.iter().any(|key| key == "bind_port"));
+
+        // Allowed values after `=`, quoted unless a string is open.
+        let levels = completions(SchemaKind::Config, "[logging]\nlog_level = ", 22);
+        assert_eq!(levels.len(), 5);
+        assert_eq!(levels[2].label, "info");
+        assert_eq!(levels[2].kind, CompletionKind::EnumMember);
+        assert_eq!(levels[2].insert_text.as_deref(), Some("\"info\""));
+        let open = completions(SchemaKind::Config, "[logging]\nlog_level = \"", 23);
+        assert_eq!(open[2].insert_text, None);
+        assert_eq!(
+            labels("[network.default.tcp_config]\ntls_min_version = "),
+            ["1.2", "1.3"]
+        );
+
+        assert!(labels("[logging]\nlog_to_file = ").is_empty());
+        assert!(labels("[prox").is_empty());
+    }
+}
@@ -0,0 +1,140 @@
+//! Layering environment overrides onto a base config.
+//!
+//! Deployments often keep a `config.base.toml` and one small override file
+//! per environment. [`merge_configs`] combines the two deterministically, so
+//! the merged document can go through [`validate`](crate::validate) or
+//! [`parse_config`](crate::parse_config) like any other file.
+
+use toml::{Table, Value};
+
+use crate::model::ConfigError;
+use crate::prelude::*;
+use crate::validate::parse_error;
+
+/// Settings for [`merge_configs_with`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MergeOptions {
+    /// How an array in the override combines with the base's array at the
+    /// same path.
+    pub arrays: ArrayMerge,
+}
+
+/// How [`merge_configs_with`] combines two arrays at the same path.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ArrayMerge {
+    /// The override's array replaces the base's, so an environment can drop
+    /// entries as well as add them.
+    #[default]
+    Replace,
+    /// The override's elements are appended after the base's.
+    Append,
+}
+
+/// Deep-merge `override_` onto `base` and return the merged TOML.
+///
+/// Tables present in both documents are merged key by key; any other value in
+/// the override, including an array, replaces the base's. Neither document is
+/// validated, since either may be partial on its own. Comments and layout are
+/// dropped, and keys come out sorted as in
+/// [`canonicalize`](crate::canonicalize).
+pub fn merge_configs(base: &str, override_: &str) -> Result<String, ConfigError> {
+    merge_configs_with(base, override_, MergeOptions::default())
+}
+
+/// [`merge_configs`] with explicit [`MergeOptions`].
+pub fn merge_configs_with(
+    base: &str,
+    override_: &str,
+    options: MergeOptions,
+) -> Result<String, ConfigError> {
+    let parse = |input: &str| {
+        toml::from_str::<Table>(input)
+            .map_err(|err| ConfigError::Invalid(vec![parse_error(input, &err)].into()))
+    };
+    let mut merged = parse(base)?;
+    merge_tables(&mut merged, parse(override_)?, options);
+    Ok(toml::to_string(&merged).expect("parsed TOML serializes back to TOML"))
+}
+
+pub(crate) fn merge_tables(base: &mut Table, overlay: Table, options: MergeOptions) {
+    for (key, value) in overlay {
+        match (base.get_mut(&key), value) {
+            (Some(Value::Table(base)), Value::Table(overlay)) => {
+                merge_tables(base, overlay, options);
+            }
+            (Some(Value::Array(base)), Value::Array(overlay))
+                if options.arrays == ArrayMerge::Append =>
+            {
+                base.extend(overlay);
+            }
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::validate::validate_config;
+
+    const BASE: &str = r#"
+[proxy]
+id = "gw"
+required_env_vars = ["RUNBEAM_TOKEN"]
+
+[logging]
+log_level = "info"
+log_file_path = "/var/log/harmony.log"
+
+[network.default.tcp_config]
+bind_address = "127.0.0.1"
+bind_port = 8080
+"#;
+
+    const PRODUCTION: &str = r#"
+proxy.required_env_vars = ["ADMIN_TOKEN"]
+
+[logging]
+log_level = "warn"
+
+[network.default.tcp_config]
+bind_address = "0.0.0.0"
+
+[network.internal.tcp_config]
+bind_address = "10.0.0.1"
+bind_port = 8081
+"#;
+
+    #[test]
+    fn nested_tables_merge_and_the_override_wins() {
+        let merged = merge_configs(BASE, PRODUCTION).unwrap();
+        assert_eq!(
+            merged,
+            "[logging]\nlog_file_path = \"/var/log/harmony.log\"\nlog_level = \"warn\"\n\n\
+             [network.default.tcp_config]\nbind_address = \"0.0.0.0\"\nbind_port = 8080\n\n\
+             [network.internal.tcp_config]\nbind_address = \"10.0.0.1\"\nbind_port = 8081\n\n\
+             [proxy]\nid = \"gw\"\nrequired_env_vars = [\"ADMIN_TOKEN\"]\n"
+        );
+        assert_eq!(validate_config(&merged), Ok(()));
+    }
+
+    #[test]
+    fn arrays_can_be_appended() {
+        let options = MergeOptions {
+            arrays: ArrayMerge::Append,
+        };
+        let merged: Table =
+            toml::from_str(&merge_configs_with(BASE, PRODUCTION, options).unwrap()).unwrap();
+        assert_eq!(
+            merged["proxy"]["required_env_vars"],
+            Value::Array(vec!["RUNBEAM_TOKEN".into(), "ADMIN_TOKEN".into()])
+        );
+
+        let Err(ConfigError::Invalid(errors)) = merge_configs(BASE, "[logging\n") else {
+            panic!("a malformed override is reported");
+        };
+        assert_eq!(errors.0[0].kind, crate::ValidationErrorKind::InvalidToml);
+    }
+}
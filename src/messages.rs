@@ -0,0 +1,393 @@
+//! Validation messages from translatable templates.
+//!
+//! The `message` field of a [`ValidationError`] is written in English by the
+//! check that found the problem. For other languages,
+//! [`ValidationError::message()`] renders a template chosen by the error's
+//! [`code`](crate::ValidationErrorKind::code) instead, filling in `{path}`
+//! and the fields of its [`ValidationErrorKind`], e.g. `{found}` for a
+//! [`TypeMismatch`](ValidationErrorKind::TypeMismatch). English templates
+//! are built in; a [`MessageCatalog`] adds other locales or replaces them.
+//!
+//! ```
+//! # #[cfg(feature = "schema-config")] {
+//! use harmony_dsl::{validate_config, MessageCatalog};
+//!
+//! let mut catalog = MessageCatalog::new();
+//! catalog.insert("de", "E_TYPE_MISMATCH", "`{path}` muss vom Typ {expected} sein");
+//!
+//! let errors = validate_config("[proxy]\nid = 7\n").unwrap_err();
+//! assert_eq!(
+//!     errors[0].message_with("de", &catalog),
+//!     "`proxy.id` muss vom Typ string sein"
+//! );
+//! assert_eq!(
+//!     errors[0].message("en"),
+//!     "`proxy.id` must be of type string, found integer"
+//! );
+//! # }
+//! ```
+
+use alloc::collections::BTreeMap;
+
+use serde_json::Value;
+
+use crate::prelude::*;
+use crate::validate::{ValidationError, ValidationErrorKind};
+
+/// The built-in English template for each error code.
+const ENGLISH: &[(&str, &str)] = &[
+    ("E_EMPTY_INPUT", "input is empty"),
+    ("E_INVALID_TOML", "the document is not valid TOML"),
+    ("E_INVALID_JSON", "the document is not valid JSON"),
+    ("E_INVALID_UTF8", "the input is not UTF-8 at byte {offset}"),
+    ("E_INVALID_SCHEMA", "the schema is not valid"),
+    (
+        "E_INPUT_TOO_LARGE",
+        "the input is longer than the limit of {limit} bytes",
+    ),
+    ("E_IO", "the file could not be read"),
+    (
+        "E_MISSING_INCLUDE",
+        "the included file `{include}` could not be read",
+    ),
+    (
+        "E_INCLUDE_OUTSIDE_DEPLOYMENT",
+        "the included file `{include}` is outside the deployment",
+    ),
+    (
+        "E_INCLUDE_CYCLE",
+        "the file includes itself through {chain}",
+    ),
+    ("E_MISSING_REQUIRED", "`{path}` is required"),
+    (
+        "E_CONDITIONALLY_REQUIRED",
+        "`{path}` is required because {condition}",
+    ),
+    (
+        "E_TYPE_MISMATCH",
+        "`{path}` must be of type {expected}, found {found}",
+    ),
+    (
+        "E_COERCED",
+        "`{path}` was read as {expected} from `{found}`",
+    ),
+    (
+        "E_NOT_IN_ENUM",
+        "`{path}` must be one of {allowed}, found `{found}`",
+    ),
+    ("E_OUT_OF_RANGE", "`{path}` is out of range: {actual}"),
+    (
+        "E_TOO_FEW_ITEMS",
+        "`{path}` needs at least {min} items, found {actual}",
+    ),
+    (
+        "E_TOO_MANY_ITEMS",
+        "`{path}` allows at most {max} items, found {actual}",
+    ),
+    ("E_DEPRECATED", "`{path}` is deprecated: {note}"),
+    (
+        "E_FIELD_NOT_YET_AVAILABLE",
+        "`{path}` is not available before version {since}",
+    ),
+    ("E_FIELD_REMOVED", "`{path}` was removed in version {until}"),
+    (
+        "E_CONTROL_CHARACTER",
+        "`{path}` contains the control character {codepoint}",
+    ),
+    ("E_EMPTY_STRING", "`{path}` must not be blank"),
+    (
+        "E_PATTERN_MISMATCH",
+        "`{path}` does not match the pattern `{pattern}`",
+    ),
+    (
+        "E_VALIDATOR_FAILED",
+        "`{path}` fails the `{validator}` check",
+    ),
+    (
+        "E_INVALID_DURATION",
+        "`{path}` is not a duration such as `30s`",
+    ),
+    (
+        "E_INVALID_BYTE_SIZE",
+        "`{path}` is not a size such as `64KiB`",
+    ),
+    (
+        "E_MAX_DEPTH_EXCEEDED",
+        "`{path}` is nested more than {max_depth} levels deep",
+    ),
+    ("E_INVALID_EMAIL", "`{path}` is not an email address"),
+    ("E_INVALID_URL", "`{path}` is not an http or https URL"),
+    ("E_INVALID_FORMAT", "`{path}` is not a valid {expected}"),
+    (
+        "E_INLINE_CREDENTIAL",
+        "`{path}` is written inline; read it from the environment",
+    ),
+    (
+        "E_INSECURE_MANAGEMENT_API",
+        "the management API is enabled without authentication",
+    ),
+    (
+        "E_IMPLICIT_SECURITY_DEFAULT",
+        "`{path}` is not set, so its default applies",
+    ),
+    ("E_MISSING_ONE_OF", "`{path}` must set one of {fields}"),
+    (
+        "E_MULTIPLE_EXCLUSIVE",
+        "`{path}` sets more than one of {fields}",
+    ),
+    (
+        "E_INCOMPLETE_PARTIAL_GROUP",
+        "`{path}` sets only some of a group of keys; missing {missing}",
+    ),
+    ("E_UNKNOWN_KEY", "`{path}` is not a known key"),
+    ("E_DUPLICATE_KEY", "`{key}` is defined more than once"),
+    ("E_DUPLICATE_NAME", "`{name}` is listed more than once"),
+    (
+        "E_LISTENER_ADDRESS_CONFLICT",
+        "`{path}` binds `{address}`, which `{first_listener}` already listens on",
+    ),
+    (
+        "E_DUPLICATE_PROXY_ID",
+        "proxy id `{id}` is already used by `{first_file}`",
+    ),
+    (
+        "E_DUPLICATE_PEER_KEY",
+        "`{path}` is also the public key of peer `{first_peer}`",
+    ),
+    (
+        "E_PEER_KEY_EQUALS_INTERFACE",
+        "`{path}` is the interface's own public key",
+    ),
+    (
+        "E_UNKNOWN_MIDDLEWARE_TYPE",
+        "`{reference}` is not a known middleware type",
+    ),
+    (
+        "E_INCOMPATIBLE_PROTOCOLS",
+        "endpoint `{endpoint}` ({endpoint_protocol}) cannot forward to backend \
+         `{backend}` ({backend_protocol})",
+    ),
+    (
+        "E_MIDDLEWARE_ORDER_VIOLATION",
+        "middleware `{middleware}` ({middleware_type}) must run before \
+         `{successor}` ({successor_type})",
+    ),
+    (
+        "E_UNSUPPORTED_MIDDLEWARE_VERSION",
+        "`{middleware_type}` middleware has no version `{version}`; supported versions \
+         are {supported}",
+    ),
+    (
+        "E_UNKNOWN_SERVICE_TYPE",
+        "service type `{reference}` is not registered in the config",
+    ),
+    (
+        "E_UNKNOWN_PIPELINE_REFERENCE",
+        "`{reference}` is not defined by any pipeline",
+    ),
+    (
+        "E_UNKNOWN_PROVIDER",
+        "`{path}` names the provider `{reference}`, which the config does not define",
+    ),
+    (
+        "E_UNREACHABLE_PIPELINE",
+        "pipeline `{pipeline}` is unreachable",
+    ),
+    (
+        "E_INCOMPLETE_TLS",
+        "`{path}` is missing; TLS needs both `cert_path` and `key_path`",
+    ),
+    (
+        "E_INVALID_TLS_VERSION_RANGE",
+        "`{path}` does not form a valid TLS version range",
+    ),
+    (
+        "E_INVALID_RETRY_POLICY",
+        "`{path}` does not form a valid retry policy",
+    ),
+    (
+        "E_INVALID_EGRESS_DESTINATION",
+        "`{path}` must be an `http` or `https` URL",
+    ),
+    (
+        "E_INVALID_CORS_COMBINATION",
+        "`{path}` cannot be true with the `*` origin",
+    ),
+    (
+        "E_INVALID_WEIGHT_DISTRIBUTION",
+        "the weights at `{path}` are invalid; they sum to {sum}",
+    ),
+    (
+        "E_INCOMPLETE_EGRESS_AUTH",
+        "`{path}` signs egress requests but sets none of {fields}",
+    ),
+    ("E_CUSTOM", "`{path}` violates the `{rule}` policy"),
+];
+
+/// Message templates by locale and error code, for
+/// [`ValidationError::message_with`].
+///
+/// A template is looked up for the exact locale, then for its language
+/// (`fr` for `fr-CA`), then in English. English templates added with
+/// [`insert`](MessageCatalog::insert) replace the built-in ones.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MessageCatalog {
+    templates: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+impl MessageCatalog {
+    /// A catalog with only the built-in English templates.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Use `template` for errors with `code`, e.g. `E_UNKNOWN_KEY`, in
+    /// `locale`, replacing any template already set.
+    pub fn insert(&mut self, locale: &str, code: &str, template: &str) {
+        self.templates
+            .entry(locale.to_owned())
+            .or_default()
+            .insert(code.to_owned(), template.to_owned());
+    }
+
+    /// The template for `code` in `locale`, following the fallbacks
+    /// described on [`MessageCatalog`].
+    pub fn template(&self, locale: &str, code: &str) -> Option<&str> {
+        let language = locale.split(['-', '_']).next().unwrap_or(locale);
+        [locale, language, "en"]
+            .into_iter()
+            .find_map(|locale| self.templates.get(locale)?.get(code))
+            .map(String::as_str)
+            .or_else(|| {
+                ENGLISH
+                    .iter()
+                    .find(|(english, _)| *english == code)
+                    .map(|(_, template)| *template)
+            })
+    }
+}
+
+impl ValidationError {
+    /// This error's message in `locale`, from the built-in templates; see
+    /// [`message_with`](ValidationError::message_with). Only English is
+    /// built in, so other locales render in English too.
+    pub fn message(&self, locale: &str) -> String {
+        self.message_with(locale, &MessageCatalog::new())
+    }
+
+    /// This error's message in `locale`, rendered from `catalog`'s template
+    /// for its code. `{path}`, `{code}` and the fields of its
+    /// [`ValidationErrorKind`] are filled in; lists are joined with `, ` and
+    /// absent values are left empty. Falls back to the `message` field when
+    /// no template exists.
+    pub fn message_with(&self, locale: &str, catalog: &MessageCatalog) -> String {
+        let code = self.kind.code();
+        let Some(template) = catalog.template(locale, code) else {
+            return self.message.clone();
+        };
+
+        let mut fields = match serde_json::to_value(&self.kind) {
+            Ok(Value::Object(fields)) => fields,
+            _ => serde_json::Map::new(),
+        };
+        match &self.kind {
+            ValidationErrorKind::ConditionallyRequired { condition } => {
+                fields.insert("condition".to_owned(), condition.to_string().into());
+            }
+            ValidationErrorKind::TypeMismatch { expected, .. } => {
+                fields.insert("expected".to_owned(), expected.as_str().into());
+            }
+            _ => {}
+        }
+        fields.insert("path".to_owned(), self.path.clone().into());
+        fields.insert("code".to_owned(), code.into());
+        interpolate(template, &fields)
+    }
+}
+
+/// Replace each `{name}` in `template` with the field `name`. Unknown names
+/// are kept as written.
+fn interpolate(template: &str, fields: &serde_json::Map<String, Value>) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        match after.find('}').map(|end| (&after[..end], end)) {
+            Some((name, end)) if fields.contains_key(name) => {
+                out.push_str(&render(&fields[name]));
+                rest = &after[end + 1..];
+            }
+            _ => {
+                out.push('{');
+                rest = after;
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+fn render(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(text) => text.clone(),
+        Value::Array(items) => items.iter().map(render).collect::<Vec<_>>().join(", "),
+        other => other.to_string(),
+    }
+}
+
+#[cfg(all(test, feature = "schema-config"))]
+mod tests {
+    use super::*;
+    use crate::validate_config;
+
+    #[test]
+    fn messages_render_from_the_locale_template() {
+        let errors = validate_config("[proxy]\nid = 7\n").unwrap_err();
+        let error = &errors[0];
+        assert_eq!(
+            error.message("en"),
+            "`proxy.id` must be of type string, found integer"
+        );
+
+        let mut french = MessageCatalog::new();
+        french.insert(
+            "fr",
+            "E_TYPE_MISMATCH",
+            "`{path}` doit être de type {expected}, pas {found} ({code})",
+        );
+        let mut spanish = MessageCatalog::new();
+        spanish.insert("es", "E_TYPE_MISMATCH", "`{path}` debe ser {expected}");
+        assert_eq!(
+            error.message_with("fr-CA", &french),
+            "`proxy.id` doit être de type string, pas integer (E_TYPE_MISMATCH)"
+        );
+        assert_eq!(
+            error.message_with("es", &spanish),
+            "`proxy.id` debe ser string"
+        );
+        // Locales without a template fall back to English.
+        assert_eq!(error.message_with("es", &french), error.message("en"));
+    }
+
+    #[test]
+    fn english_templates_can_be_replaced() {
+        for (code, _) in ENGLISH {
+            let count = ENGLISH.iter().filter(|(other, _)| other == code).count();
+            assert_eq!(count, 1, "{code}");
+        }
+
+        let overridden = {
+            let mut catalog = MessageCatalog::new();
+            catalog.insert("en", "E_UNKNOWN_KEY", "`{path}`: unknown ({suggestion})");
+            catalog
+        };
+        let errors = validate_config("[proxy]\nid = \"gw\"\nidd = 1\n").unwrap_err();
+        assert_eq!(
+            errors[0].message_with("en-GB", &overridden),
+            "`proxy.idd`: unknown (id)"
+        );
+        assert_eq!(errors[0].message("en"), "`proxy.idd` is not a known key");
+    }
+}
@@ -0,0 +1,278 @@
+//! Mechanical upgrades of config files between schema versions.
+//!
+//! Each schema release that renames keys or moves tables registers one
+//! upgrade step keyed by version. [`migrate_config`] applies every step
+//! between two versions in order, editing the document in place with
+//! `toml_edit` so comments and layout survive the upgrade.
+
+use std::fmt;
+
+use semver::Version;
+use toml_edit::{value, DocumentMut, Item, Table, Value};
+
+use crate::compat::VERSION_KEY;
+use crate::fix::rename_key;
+use crate::schema::schema_version;
+use crate::SchemaKind;
+
+/// Reasons [`migrate_config`] can fail.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum MigrateError {
+    /// The input could not be parsed as TOML.
+    InvalidToml(String),
+    /// `to` is older than `from`; migrations only run forwards.
+    Downgrade { from: Version, to: Version },
+    /// `to` is newer than the bundled config schema.
+    UnknownVersion(Version),
+}
+
+impl fmt::Display for MigrateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MigrateError::InvalidToml(message) => write!(f, "config is not valid TOML: {message}"),
+            MigrateError::Downgrade { from, to } => {
+                write!(f, "cannot migrate from schema {from} down to {to}")
+            }
+            MigrateError::UnknownVersion(version) => write!(
+                f,
+                "schema {version} is newer than the bundled config schema {}",
+                schema_version(SchemaKind::Config)
+            ),
+        }
+    }
+}
+
+impl std::error::Error for MigrateError {}
+
+/// One upgrade step, from the config schema `from` to the next version `to`.
+pub(crate) struct Migration {
+    pub(crate) from: Version,
+    pub(crate) to: Version,
+    /// What the step changes, for [`plan`](crate::plan()).
+    pub(crate) summary: &'static str,
+    apply: fn(&mut DocumentMut),
+}
+
+/// Every registered step, oldest first.
+const MIGRATIONS: &[Migration] = &[Migration {
+    from: Version::new(1, 10, 0),
+    to: Version::new(1, 11, 0),
+    summary: "move `[runbeam]` to `[provider.runbeam]` and rename `type` to `protocol` \
+              and `leeway_seconds` to `leeway_secs`",
+    apply: v1_10_to_v1_11,
+}];
+
+/// The steps that upgrade a config from schema `from` to `to`, in order.
+pub(crate) fn steps(from: &Version, to: &Version) -> impl Iterator<Item = &'static Migration> {
+    let from = Version::new(from.major, from.minor, 0);
+    let to = to.clone();
+    MIGRATIONS
+        .iter()
+        .filter(move |step| step.from >= from && step.to <= to)
+}
+
+/// Upgrade a config file written for schema `from` to schema `to`.
+///
+/// Only the major and minor components of `from` select migrations, since
+/// patch releases never change the config layout. Versions without a
+/// registered step are passed through unchanged, except that a declared
+/// `schema_version` is set to `to`.
+pub fn migrate_config(
+    toml_input: &str,
+    from: Version,
+    to: Version,
+) -> Result<String, MigrateError> {
+    if to < from {
+        return Err(MigrateError::Downgrade { from, to });
+    }
+    if to > schema_version(SchemaKind::Config) {
+        return Err(MigrateError::UnknownVersion(to));
+    }
+
+    let mut doc: DocumentMut = toml_input
+        .parse()
+        .map_err(|err: toml_edit::TomlError| MigrateError::InvalidToml(err.message().to_owned()))?;
+    for step in steps(&from, &to) {
+        (step.apply)(&mut doc);
+    }
+    if let Some(Item::Value(version @ Value::String(_))) = doc.get_mut(VERSION_KEY) {
+        let decor = version.decor().clone();
+        *version = Value::from(to.to_string());
+        *version.decor_mut() = decor;
+    }
+    Ok(doc.to_string())
+}
+
+/// 1.10 → 1.11:
+/// - `[runbeam]` moves to `[provider.runbeam]`, with `cloud_api_base_url`
+///   renamed to `api` and `enabled` replaced by `proxy.primary_provider`;
+/// - the `type` of peers and targets is renamed to `protocol`;
+/// - `leeway_seconds` in authentication options is renamed to `leeway_secs`.
+fn v1_10_to_v1_11(doc: &mut DocumentMut) {
+    move_runbeam_to_provider(doc);
+
+    for section in ["peers", "targets"] {
+        for (_, entry) in instances(doc, section) {
+            if let Some(entry) = entry.as_table_like_mut() {
+                rename_key(entry, "type", "protocol");
+            }
+        }
+    }
+    for (_, auth) in instances(doc, "authentications") {
+        if let Some(options) = auth.get_mut("options").and_then(Item::as_table_like_mut) {
+            rename_key(options, "leeway_seconds", "leeway_secs");
+        }
+    }
+}
+
+fn move_runbeam_to_provider(doc: &mut DocumentMut) {
+    let Some(Item::Table(runbeam)) = doc.get_mut("runbeam") else {
+        return;
+    };
+    rename_key(runbeam, "cloud_api_base_url", "api");
+    let Some(Item::Table(mut runbeam)) = doc.remove("runbeam") else {
+        return;
+    };
+    let enabled = runbeam
+        .remove("enabled")
+        .and_then(|item| item.as_bool())
+        .unwrap_or(false);
+
+    let provider = doc.entry("provider").or_insert_with(|| {
+        let mut table = Table::new();
+        table.set_implicit(true);
+        Item::Table(table)
+    });
+    if let Some(provider) = provider.as_table_like_mut() {
+        if provider.get("runbeam").is_none() {
+            provider.insert("runbeam", Item::Table(runbeam));
+        }
+    }
+
+    if let Some(proxy) = doc.get_mut("proxy").and_then(Item::as_table_like_mut) {
+        if proxy.get("primary_provider").is_none() {
+            let primary = if enabled { "runbeam" } else { "local" };
+            proxy.insert("primary_provider", value(primary));
+        }
+    }
+}
+
+/// The entries of a wildcard section such as `peers.*`.
+fn instances<'a>(
+    doc: &'a mut DocumentMut,
+    section: &str,
+) -> impl Iterator<Item = (toml_edit::KeyMut<'a>, &'a mut Item)> {
+    doc.get_mut(section)
+        .and_then(Item::as_table_like_mut)
+        .into_iter()
+        .flat_map(|table| table.iter_mut())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::validate_config;
+
+    const V1_10: &str = r#"# Edge gateway
+[proxy]
+id = "edge"
+
+# Cloud polling
+[runbeam]
+enabled = true
+cloud_api_base_url = "https://api.runbeam.io" # production
+poll_interval_secs = 60
+
+[peers.hospital]
+# DICOM modality
+type = "dicom"
+enabled = true
+
+[peers.hospital.connection]
+host = "pacs.example.com"
+
+[authentications.jwt]
+id = "jwt"
+method = "jwt"
+options = { issuer = "runbeam", leeway_seconds = 30 }
+"#;
+
+    const V1_11: &str = r#"# Edge gateway
+[proxy]
+id = "edge"
+primary_provider = "runbeam"
+
+# Cloud polling
+[provider.runbeam]
+api = "https://api.runbeam.io" # production
+poll_interval_secs = 60
+
+[peers.hospital]
+# DICOM modality
+protocol = "dicom"
+enabled = true
+
+[peers.hospital.connection]
+host = "pacs.example.com"
+
+[authentications.jwt]
+id = "jwt"
+method = "jwt"
+options = { issuer = "runbeam", leeway_secs = 30 }
+"#;
+
+    #[test]
+    fn migrates_a_1_10_config_preserving_comments() {
+        assert!(validate_config(V1_10).is_err());
+
+        let migrated =
+            migrate_config(V1_10, Version::new(1, 10, 2), Version::new(1, 11, 0)).unwrap();
+        assert_eq!(migrated, V1_11);
+        assert_eq!(validate_config(&migrated), Ok(()));
+    }
+
+    #[test]
+    fn disabled_runbeam_selects_the_local_provider() {
+        let migrated = migrate_config(
+            "[proxy]\nid = \"edge\"\n\n[runbeam]\nenabled = false\n",
+            Version::new(1, 10, 0),
+            Version::new(1, 11, 0),
+        )
+        .unwrap();
+        assert!(migrated.contains("primary_provider = \"local\""));
+        assert!(migrated.contains("[provider.runbeam]"));
+    }
+
+    #[test]
+    fn versions_without_steps_pass_through() {
+        let input = "[proxy]\nid = \"edge\" # keep\n";
+        assert_eq!(
+            migrate_config(input, Version::new(1, 11, 0), Version::new(1, 11, 0)).as_deref(),
+            Ok(input)
+        );
+    }
+
+    #[test]
+    fn declared_schema_versions_are_updated() {
+        let migrated = migrate_config(
+            "schema_version = \"1.10.0\" # pinned\n\n[proxy]\nid = \"edge\"\n",
+            Version::new(1, 10, 0),
+            Version::new(1, 11, 0),
+        )
+        .unwrap();
+        assert!(migrated.starts_with("schema_version = \"1.11.0\" # pinned\n"));
+    }
+
+    #[test]
+    fn invalid_version_ranges_are_rejected() {
+        assert!(matches!(
+            migrate_config("", Version::new(1, 11, 0), Version::new(1, 10, 0)),
+            Err(MigrateError::Downgrade { .. })
+        ));
+        assert_eq!(
+            migrate_config("", Version::new(1, 11, 0), Version::new(9, 0, 0)),
+            Err(MigrateError::UnknownVersion(Version::new(9, 0, 0)))
+        );
+    }
+}
@@ -0,0 +1,868 @@
+//! Typed representation of a gateway configuration file.
+//!
+//! These structs mirror [`CONFIG_SCHEMA`](crate::CONFIG_SCHEMA) one table at a
+//! time, with the schema's defaults applied to omitted fields, so
+//! harmony-proxy can read config values without maintaining its own copy of
+//! the schema. Wildcard tables such as `network.*` become maps keyed by the
+//! user-chosen name. The tests check every schema field against these
+//! structs, so a field added to one but not the other fails the test suite.
+
+use alloc::collections::BTreeMap;
+use core::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use toml::{Table, Value};
+
+use crate::defaults::resolved_default;
+use crate::prelude::*;
+use crate::schema::bundled_schema;
+use crate::validate::{
+    validate_config, validate_report_with, Severity, ValidationError, ValidationErrorKind,
+    ValidationErrors, ValidationMode, ValidationOptions,
+};
+use crate::SchemaKind;
+
+/// A whole `config.toml` document.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProxyConfig {
+    /// The schema version the file declares it was written against; see
+    /// [`check_compatibility`](crate::check_compatibility).
+    pub schema_version: Option<String>,
+    pub proxy: ProxySettings,
+    #[serde(default)]
+    pub provider: BTreeMap<String, Provider>,
+    #[serde(default)]
+    pub runbeam: RunbeamConfig,
+    #[serde(default)]
+    pub management: ManagementConfig,
+    #[serde(default)]
+    pub network: BTreeMap<String, NetworkConfig>,
+    /// The `[labels]` table, whose keys are chosen by the operator.
+    #[serde(default)]
+    pub labels: toml::Table,
+    #[serde(default)]
+    pub logging: LoggingConfig,
+    #[serde(default)]
+    pub storage: StorageConfig,
+    #[serde(default)]
+    pub peers: BTreeMap<String, RemoteSystem>,
+    #[serde(default)]
+    pub targets: BTreeMap<String, RemoteSystem>,
+    #[serde(default)]
+    pub services: BTreeMap<String, ServiceType>,
+    #[serde(default)]
+    pub authentications: BTreeMap<String, Authentication>,
+    #[serde(default)]
+    pub policies: BTreeMap<String, Policy>,
+    #[serde(default)]
+    pub rules: BTreeMap<String, Rule>,
+    #[serde(default)]
+    pub middleware_types: BTreeMap<String, MiddlewareType>,
+}
+
+/// The `[proxy]` table.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProxySettings {
+    pub id: Option<String>,
+    pub name: Option<String>,
+    #[serde(default = "default_pipelines_path")]
+    pub pipelines_path: String,
+    #[serde(default = "default_transforms_path")]
+    pub transforms_path: String,
+    #[serde(default = "default_jwks_cache_duration_hours")]
+    pub jwks_cache_duration_hours: u64,
+    /// A duration such as `30s`.
+    #[serde(default = "default_shutdown_timeout")]
+    pub shutdown_timeout: String,
+    #[serde(default)]
+    pub content_limits: ContentLimits,
+    #[serde(default)]
+    pub required_env_vars: Vec<String>,
+    #[serde(default)]
+    pub sensitive_field_patterns: Vec<String>,
+    #[serde(default = "default_primary_provider")]
+    pub primary_provider: String,
+}
+
+/// `[proxy.content_limits]`: size limits applied to request bodies.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ContentLimits {
+    pub max_body_size: u64,
+    pub max_csv_rows: u64,
+    pub max_xml_depth: u64,
+    pub max_multipart_files: u64,
+    pub max_form_fields: u64,
+}
+
+/// A `[provider.*]` table.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Provider {
+    pub api: Option<String>,
+    #[serde(default = "default_poll_interval_secs")]
+    pub poll_interval_secs: u64,
+}
+
+/// The `[runbeam]` table.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RunbeamConfig {
+    pub enabled: bool,
+    pub cloud_api_base_url: Option<String>,
+    pub poll_interval_secs: u64,
+}
+
+/// The `[management]` table.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ManagementConfig {
+    pub enabled: bool,
+    pub base_path: String,
+    /// Name of the `network.*` the management API listens on. Required when
+    /// `enabled` is set.
+    pub network: Option<String>,
+    pub auth_token: Option<String>,
+    /// CA bundle for mutual TLS client certificates.
+    pub client_ca_path: Option<String>,
+    pub allow_unauthenticated: bool,
+}
+
+/// A `[network.*]` table.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct NetworkConfig {
+    pub id: Option<String>,
+    #[serde(default)]
+    pub enable_wireguard: bool,
+    #[serde(default = "default_interface")]
+    pub interface: String,
+    pub private_key: Option<String>,
+    #[serde(default = "default_wireguard_port")]
+    pub listen_port: u16,
+    #[serde(default)]
+    pub wireguard_peers: BTreeMap<String, WireGuardPeer>,
+    pub tcp_config: Option<HttpListener>,
+    pub http3: Option<Http3Listener>,
+}
+
+/// A `[network.*.wireguard_peers.*]` table.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct WireGuardPeer {
+    pub public_key: String,
+    pub allowed_ips: Vec<String>,
+    pub endpoint: Option<String>,
+}
+
+/// `[network.*.tcp_config]`: an HTTP/1.1 and HTTP/2 listener, served over
+/// TLS when both `cert_path` and `key_path` are set.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct HttpListener {
+    #[serde(default = "default_bind_address")]
+    pub bind_address: String,
+    #[serde(default = "default_http_port")]
+    pub bind_port: u16,
+    pub cert_path: Option<String>,
+    pub key_path: Option<String>,
+    /// The lowest TLS version accepted, `1.2` or `1.3`.
+    pub tls_min_version: Option<String>,
+    /// The highest TLS version accepted, `1.2` or `1.3`.
+    pub tls_max_version: Option<String>,
+    #[serde(default)]
+    pub force_https: bool,
+    /// A byte size such as `64KiB`.
+    #[serde(default = "default_read_buffer_size")]
+    pub read_buffer_size: String,
+    pub cors: Option<CorsConfig>,
+}
+
+impl HttpListener {
+    /// A plain-HTTP listener on `bind_address:bind_port`.
+    pub fn new(bind_address: &str, bind_port: u16) -> Self {
+        Self {
+            bind_address: bind_address.to_owned(),
+            bind_port,
+            cert_path: None,
+            key_path: None,
+            tls_min_version: None,
+            tls_max_version: None,
+            force_https: false,
+            read_buffer_size: default_read_buffer_size(),
+            cors: None,
+        }
+    }
+}
+
+/// `[network.*.tcp_config.cors]`: the browser origins that may call the
+/// listener.
+#[derive(Debug, Clone, PartialEq, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CorsConfig {
+    /// Origins such as `https://app.example.com`, or `*` for any.
+    pub allowed_origins: Vec<String>,
+    pub allow_credentials: bool,
+}
+
+/// `[network.*.http3]`: an HTTP/3 (QUIC) listener.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Http3Listener {
+    #[serde(default = "default_bind_address")]
+    pub bind_address: String,
+    #[serde(default = "default_http3_port")]
+    pub bind_port: u16,
+    pub cert_path: String,
+    pub key_path: String,
+}
+
+/// The `[logging]` table.
+#[derive(Debug, Clone, PartialEq, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LoggingConfig {
+    pub log_level: LogLevel,
+    pub log_to_file: bool,
+    /// Defaults to `<proxy.id>.log`.
+    pub log_file_path: Option<String>,
+    /// A [`FieldType::ByteSize`](crate::FieldType::ByteSize) string such as
+    /// `100MB`; unset disables rotation.
+    pub log_rotation_size: Option<String>,
+}
+
+/// Values of `logging.log_level`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    Trace,
+    Debug,
+    Info,
+    Warn,
+    #[default]
+    Error,
+}
+
+/// The `[storage]` table.
+#[derive(Debug, Clone, PartialEq, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StorageConfig {
+    pub backend: StorageBackend,
+    pub options: StorageOptions,
+}
+
+/// Values of `storage.backend`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageBackend {
+    #[default]
+    Filesystem,
+    S3,
+    Memory,
+}
+
+/// `[storage.options]`. The S3 fields apply to the `s3` backend only.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StorageOptions {
+    pub path: String,
+    pub bucket: Option<String>,
+    pub region: Option<String>,
+    pub access_key_id: Option<String>,
+    pub secret_access_key: Option<String>,
+    pub endpoint: Option<String>,
+}
+
+/// A `[peers.*]` or `[targets.*]` table: an external system that sends
+/// requests to, or receives requests from, the gateway.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct RemoteSystem {
+    pub id: Option<String>,
+    pub name: Option<String>,
+    pub connection: Connection,
+    /// Accepts the deprecated `type` key as an alias.
+    #[serde(alias = "type")]
+    pub protocol: Protocol,
+    pub description: Option<String>,
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    pub authentication: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default = "default_timeout_secs")]
+    pub timeout_secs: u64,
+    #[serde(default = "default_max_retries")]
+    pub max_retries: u64,
+}
+
+/// A `connection` table (`common.connection` in the schema).
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Connection {
+    pub host: String,
+    pub port: Option<u16>,
+    pub base_path: Option<String>,
+    pub ca_cert_path: Option<String>,
+}
+
+/// Protocols spoken by peers and targets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Protocol {
+    Http,
+    Https,
+    H3,
+    Dicom,
+    Harmony,
+    Fhir,
+    Hl7v2,
+    Custom,
+}
+
+/// A `[services.*]` table registering a service type.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ServiceType {
+    pub id: Option<String>,
+    pub module: String,
+    /// Whether the service can act as an endpoint, a backend, or both.
+    #[serde(rename = "type", default)]
+    pub roles: Vec<ServiceRole>,
+}
+
+/// Values of `services.*.type`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ServiceRole {
+    Endpoint,
+    Backend,
+}
+
+/// The service types a config registers under `[services.*]`, keyed by
+/// name; see [`parse_service_types`].
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(transparent)]
+pub struct ServiceTypeRegistry(pub BTreeMap<String, ServiceType>);
+
+impl ServiceTypeRegistry {
+    /// The service type registered as `name`, e.g. `fhir`.
+    pub fn get(&self, name: &str) -> Option<&ServiceType> {
+        self.0.get(name)
+    }
+
+    /// The names of the registered service types that can act as `role`.
+    /// A type that lists no roles is taken to support both.
+    pub fn with_role(&self, role: ServiceRole) -> impl Iterator<Item = &str> {
+        self.0
+            .iter()
+            .filter(move |(_, service)| service.roles.is_empty() || service.roles.contains(&role))
+            .map(|(name, _)| name.as_str())
+    }
+}
+
+/// An `[authentications.*]` table.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Authentication {
+    pub id: String,
+    pub method: AuthMethod,
+    #[serde(default)]
+    pub options: AuthOptions,
+}
+
+/// Values of `authentications.*.method`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuthMethod {
+    Jwt,
+    Basic,
+    Bearer,
+    ApiKey,
+    MutualTls,
+    Custom,
+    None,
+}
+
+/// `[authentications.*.options]`. Which fields apply depends on the method.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuthOptions {
+    pub credentials_path: Option<String>,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub issuer: Option<String>,
+    pub audience: Option<String>,
+    pub trusted_issuers: Vec<String>,
+    pub jwks_uri: Option<String>,
+    pub algorithms: Vec<String>,
+    pub required_claims: Vec<String>,
+    pub public_key_path: Option<String>,
+    pub use_hs256: bool,
+    pub hs256_secret: Option<String>,
+    /// Accepts `leeway_seconds` as an alias.
+    #[serde(alias = "leeway_seconds")]
+    pub leeway_secs: u64,
+    pub validate_expiry: bool,
+}
+
+/// A `[policies.*]` table.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Policy {
+    pub id: String,
+    pub name: Option<String>,
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// Names of the `rules.*` the policy applies.
+    #[serde(default)]
+    pub rules: Vec<String>,
+}
+
+/// A `[rules.*]` table.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Rule {
+    pub id: String,
+    pub name: Option<String>,
+    #[serde(rename = "type")]
+    pub rule_type: String,
+    #[serde(default = "default_weight")]
+    pub weight: i64,
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// Rule-specific options; the schema leaves these free-form.
+    pub options: Option<toml::Table>,
+}
+
+/// A `[middleware_types.*]` table registering a middleware type.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct MiddlewareType {
+    pub id: Option<String>,
+    pub module: String,
+}
+
+/// Reasons [`parse_config`] and [`load_config`] can fail.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum ConfigError {
+    /// The document violates the config schema.
+    Invalid(ValidationErrors),
+    /// The document is valid but could not be mapped onto [`ProxyConfig`].
+    Deserialize(String),
+}
+
+/// Validate a gateway configuration file and read it into a [`ProxyConfig`].
+pub fn parse_config(toml_input: &str) -> Result<ProxyConfig, ConfigError> {
+    validate_config(toml_input).map_err(|errors| ConfigError::Invalid(errors.into()))?;
+    deserialize(toml_input)
+}
+
+/// Read a gateway configuration file into a [`ProxyConfig`], also returning
+/// the warnings found along the way, e.g. deprecated fields to log at startup.
+///
+/// Deprecated fields are warnings, as in [`ValidationMode::Lenient`], but
+/// unknown keys are still errors since [`ProxyConfig`] has nowhere to put
+/// them. Fails with every error-severity diagnostic if there are any.
+pub fn load_config(toml_input: &str) -> Result<(ProxyConfig, Vec<ValidationError>), ConfigError> {
+    let options = ValidationOptions {
+        mode: ValidationMode::Lenient,
+        ..ValidationOptions::default()
+    };
+    let report = validate_report_with(SchemaKind::Config, toml_input, options);
+    let (errors, warnings): (Vec<_>, Vec<_>) =
+        report
+            .into_diagnostics()
+            .into_iter()
+            .partition(|diagnostic| {
+                diagnostic.is_error()
+                    || matches!(diagnostic.kind, ValidationErrorKind::UnknownKey { .. })
+            });
+    if !errors.is_empty() {
+        let errors: Vec<_> = errors
+            .into_iter()
+            .map(|error| error.with_severity(Severity::Error))
+            .collect();
+        return Err(ConfigError::Invalid(errors.into()));
+    }
+    Ok((deserialize(toml_input)?, warnings))
+}
+
+/// Validate a gateway configuration file and read just its service type
+/// registry, for callers that only need to know which services they can
+/// instantiate. The whole document is validated, as in [`parse_config`].
+pub fn parse_service_types(config_toml: &str) -> Result<ServiceTypeRegistry, ConfigError> {
+    parse_config(config_toml).map(|config| ServiceTypeRegistry(config.services))
+}
+
+fn deserialize(toml_input: &str) -> Result<ProxyConfig, ConfigError> {
+    let invalid = |err: toml::de::Error| ConfigError::Deserialize(err.message().to_owned());
+    let doc: Table = toml::from_str(toml_input).map_err(invalid)?;
+    // The schema's templated default, which serde cannot express.
+    let log_file_path = bundled_schema(SchemaKind::Config)
+        .field("logging.log_file_path")
+        .and_then(|rule| resolved_default(rule, &doc));
+    let mut config: ProxyConfig = Value::Table(doc).try_into().map_err(invalid)?;
+    if config.logging.log_file_path.is_none() {
+        config.logging.log_file_path = log_file_path
+            .as_ref()
+            .and_then(Value::as_str)
+            .map(str::to_owned);
+    }
+    Ok(config)
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Invalid(errors) => match errors.0.len() {
+                1 => f.write_str("config failed validation with 1 error"),
+                n => write!(f, "config failed validation with {n} errors"),
+            },
+            ConfigError::Deserialize(message) => {
+                write!(f, "config could not be deserialized: {message}")
+            }
+        }
+    }
+}
+
+impl core::error::Error for ConfigError {
+    /// The validation errors, for [`ConfigError::Invalid`].
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            ConfigError::Invalid(errors) => Some(errors),
+            ConfigError::Deserialize(_) => None,
+        }
+    }
+}
+
+impl Default for ContentLimits {
+    fn default() -> Self {
+        Self {
+            max_body_size: 10_485_760,
+            max_csv_rows: 10_000,
+            max_xml_depth: 100,
+            max_multipart_files: 10,
+            max_form_fields: 1000,
+        }
+    }
+}
+
+impl Default for RunbeamConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            cloud_api_base_url: None,
+            poll_interval_secs: default_poll_interval_secs(),
+        }
+    }
+}
+
+impl Default for ManagementConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            base_path: "admin".to_owned(),
+            network: None,
+            auth_token: None,
+            client_ca_path: None,
+            allow_unauthenticated: false,
+        }
+    }
+}
+
+impl Default for StorageOptions {
+    fn default() -> Self {
+        Self {
+            path: "./tmp".to_owned(),
+            bucket: None,
+            region: None,
+            access_key_id: None,
+            secret_access_key: None,
+            endpoint: None,
+        }
+    }
+}
+
+impl Default for AuthOptions {
+    fn default() -> Self {
+        Self {
+            credentials_path: None,
+            username: None,
+            password: None,
+            issuer: None,
+            audience: None,
+            trusted_issuers: Vec::new(),
+            jwks_uri: None,
+            algorithms: Vec::new(),
+            required_claims: Vec::new(),
+            public_key_path: None,
+            use_hs256: false,
+            hs256_secret: None,
+            leeway_secs: 0,
+            validate_expiry: true,
+        }
+    }
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_pipelines_path() -> String {
+    "pipelines".to_owned()
+}
+
+fn default_transforms_path() -> String {
+    "transforms".to_owned()
+}
+
+fn default_jwks_cache_duration_hours() -> u64 {
+    24
+}
+
+fn default_shutdown_timeout() -> String {
+    "30s".to_owned()
+}
+
+fn default_primary_provider() -> String {
+    "runbeam".to_owned()
+}
+
+fn default_poll_interval_secs() -> u64 {
+    30
+}
+
+fn default_interface() -> String {
+    "wg0".to_owned()
+}
+
+fn default_wireguard_port() -> u16 {
+    51820
+}
+
+fn default_bind_address() -> String {
+    "0.0.0.0".to_owned()
+}
+
+fn default_read_buffer_size() -> String {
+    "64KiB".to_owned()
+}
+
+fn default_http_port() -> u16 {
+    8080
+}
+
+fn default_http3_port() -> u16 {
+    443
+}
+
+fn default_timeout_secs() -> u64 {
+    30
+}
+
+fn default_max_retries() -> u64 {
+    3
+}
+
+fn default_weight() -> i64 {
+    100
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::{bundled_schema, FieldType};
+    use crate::{scaffold, SchemaKind};
+    use toml::{Table, Value};
+
+    /// Values for fields whose placeholder would not deserialize, such as
+    /// enums without a default. `None` skips the field.
+    const OVERRIDES: &[(&str, Option<&str>)] = &[
+        ("peers.*.protocol", Some("http")),
+        ("targets.*.protocol", Some("http")),
+        // Aliases of `protocol` and `leeway_secs`; setting both is a duplicate.
+        ("peers.*.type", None),
+        ("targets.*.type", None),
+        ("authentications.*.options.leeway_seconds", None),
+        ("authentications.*.method", Some("jwt")),
+    ];
+
+    /// A document setting every field the schema declares.
+    fn every_field() -> Table {
+        let mut doc = Table::new();
+        for rule in &bundled_schema(SchemaKind::Config).fields {
+            let value = match OVERRIDES.iter().find(|(path, _)| *path == rule.path) {
+                Some((_, Some(value))) => Value::String((*value).to_owned()),
+                Some((_, None)) => continue,
+                None => rule.default.clone().unwrap_or_else(|| match rule.ty {
+                    FieldType::String => Value::String("value".to_owned()),
+                    FieldType::Integer => Value::Integer(1),
+                    FieldType::Float => Value::Float(1.0),
+                    FieldType::Boolean => Value::Boolean(false),
+                    FieldType::Duration => Value::String("1s".to_owned()),
+                    FieldType::ByteSize => Value::String("1KB".to_owned()),
+                    FieldType::IpAddr => Value::String("10.0.0.1".to_owned()),
+                    FieldType::SocketAddr => Value::String("10.0.0.1:51820".to_owned()),
+                    FieldType::Cidr => Value::String("10.0.0.0/8".to_owned()),
+                    FieldType::Email => Value::String("ops@example.com".to_owned()),
+                    FieldType::Url => Value::String("https://example.com".to_owned()),
+                    FieldType::Array if rule.path == "services.*.type" => {
+                        Value::Array(vec![Value::String("endpoint".to_owned())])
+                    }
+                    FieldType::Array => Value::Array(Vec::new()),
+                    FieldType::Table { .. } => Value::Table(Table::new()),
+                }),
+            };
+
+            let segments: Vec<&str> = rule
+                .segments()
+                .map(|seg| if seg == "*" { "example" } else { seg })
+                .collect();
+            let (last, parents) = segments.split_last().unwrap();
+            let mut table = &mut doc;
+            for segment in parents {
+                table = table
+                    .entry(*segment)
+                    .or_insert_with(|| Value::Table(Table::new()))
+                    .as_table_mut()
+                    .unwrap();
+            }
+            table.entry(*last).or_insert(value);
+        }
+        doc
+    }
+
+    #[test]
+    fn scaffold_output_deserializes() {
+        let config: ProxyConfig = toml::from_str(&scaffold(SchemaKind::Config)).unwrap();
+        assert_eq!(config.logging.log_level, LogLevel::Error);
+        assert_eq!(config.proxy.pipelines_path, "pipelines");
+    }
+
+    #[test]
+    fn every_schema_field_has_a_struct_field() {
+        let doc = toml::to_string(&every_field()).unwrap();
+        if let Err(err) = toml::from_str::<ProxyConfig>(&doc) {
+            panic!("schema and model disagree: {}", err.message());
+        }
+    }
+
+    #[test]
+    fn struct_defaults_match_schema_defaults() {
+        let config = parse_config("[proxy]\nid = \"gw\"\n").unwrap();
+        let defaults = Value::try_from(&config).unwrap();
+
+        for rule in &bundled_schema(SchemaKind::Config).fields {
+            let Some(expected) = &rule.default else {
+                continue;
+            };
+            if rule.path.contains('*') || !rule.default_references().is_empty() {
+                continue;
+            }
+            let actual = rule
+                .segments()
+                .try_fold(&defaults, |value, segment| value.get(segment));
+            assert_eq!(actual, Some(expected), "{}", rule.path);
+        }
+        assert_eq!(config.logging.log_file_path.as_deref(), Some("gw.log"));
+    }
+
+    #[test]
+    fn parse_config_reads_values_and_rejects_invalid_documents() {
+        let config = parse_config(
+            r#"
+[proxy]
+id = "gateway"
+
+[network.default.tcp_config]
+bind_address = "127.0.0.1"
+bind_port = 9090
+
+[peers.hospital]
+protocol = "dicom"
+
+[peers.hospital.connection]
+host = "pacs.example.com"
+"#,
+        )
+        .unwrap();
+        let listener = config.network["default"].tcp_config.as_ref().unwrap();
+        assert_eq!(listener.bind_port, 9090);
+        assert!(!listener.force_https);
+        assert_eq!(config.peers["hospital"].protocol, Protocol::Dicom);
+        assert_eq!(config.peers["hospital"].timeout_secs, 30);
+
+        assert!(matches!(
+            parse_config("[logging]\n"),
+            Err(ConfigError::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn service_types_are_read_from_the_registry() {
+        let registry = parse_service_types(
+            r#"
+[proxy]
+id = "gw"
+
+[services.http]
+module = ""
+
+[services.fhir]
+module = "harmony_fhir"
+type = ["backend"]
+"#,
+        )
+        .unwrap();
+        assert_eq!(registry.0.len(), 2);
+        assert_eq!(registry.get("http").unwrap().module, "");
+        assert_eq!(registry.get("fhir").unwrap().roles, [ServiceRole::Backend]);
+        assert_eq!(
+            registry.with_role(ServiceRole::Backend).collect::<Vec<_>>(),
+            ["fhir", "http"]
+        );
+        assert_eq!(
+            registry
+                .with_role(ServiceRole::Endpoint)
+                .collect::<Vec<_>>(),
+            ["http"]
+        );
+        assert!(registry.get("dicom").is_none());
+
+        assert!(matches!(
+            parse_service_types("[services.http]\n"),
+            Err(ConfigError::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn invalid_configs_carry_their_errors_as_the_source() {
+        let err = parse_config("[logging]\n").unwrap_err();
+        assert_eq!(err.to_string(), "config failed validation with 1 error");
+        let source = core::error::Error::source(&err).unwrap();
+        assert_eq!(source.to_string(), "proxy: missing required table `proxy`");
+    }
+
+    #[test]
+    fn load_config_returns_warnings_alongside_the_model() {
+        let input = "[proxy]\nid = \"gw\"\n\n[runbeam]\nenabled = true\n";
+        let (config, warnings) = load_config(input).unwrap();
+        assert_eq!(config.proxy.id.as_deref(), Some("gw"));
+        assert!(config.runbeam.enabled);
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].path, "runbeam");
+        assert_eq!(warnings[0].severity, Severity::Warning);
+
+        let err = load_config("[proxy]\nid = \"Edge GW\"\nlog = 1\n\n[runbeam]\n").unwrap_err();
+        let ConfigError::Invalid(errors) = err else {
+            panic!("expected validation errors, got {err:?}");
+        };
+        let paths: Vec<&str> = errors.0.iter().map(|error| error.path.as_str()).collect();
+        assert_eq!(paths, ["proxy.id", "proxy.log"]);
+        assert!(errors.0.iter().all(ValidationError::is_error));
+    }
+}
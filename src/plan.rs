@@ -0,0 +1,172 @@
+//! Previewing what defaults and migrations would change.
+//!
+//! Before an operator commits a migrated or defaulted config, [`plan`] lists
+//! what [`migrate_config`] and [`apply_defaults`](crate::apply_defaults)
+//! would do to it, without changing anything.
+
+use semver::Version;
+use toml::{Table, Value};
+
+use crate::defaults::fill_defaults;
+use crate::migrate::{migrate_config, steps};
+use crate::schema::schema_version;
+use crate::validate::join;
+use crate::SchemaKind;
+
+/// Settings for [`plan`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlanOptions {
+    /// List the defaults that would be filled in. On by default.
+    pub defaults: bool,
+    /// List the migration steps from this schema version to the bundled
+    /// one. Only configs have migrations; other kinds ignore this.
+    pub migrate_from: Option<Version>,
+}
+
+impl Default for PlanOptions {
+    fn default() -> Self {
+        Self {
+            defaults: true,
+            migrate_from: None,
+        }
+    }
+}
+
+/// The changes [`plan`] found, in the order they would be made: migration
+/// steps first, then defaults.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Plan {
+    /// Every change, in order.
+    pub changes: Vec<PlannedChange>,
+}
+
+impl Plan {
+    /// Whether nothing would change.
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+/// One change in a [`Plan`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum PlannedChange {
+    /// The migration step from schema `from` to `to` would run.
+    Migration {
+        /// The schema version the step migrates from.
+        from: Version,
+        /// The schema version the step migrates to.
+        to: Version,
+        /// What the step changes, e.g. which keys it renames.
+        summary: String,
+    },
+    /// The absent field at `path` would be set to its default.
+    Default {
+        /// Dotted path of the field.
+        path: String,
+        /// The default it would be set to.
+        value: Value,
+    },
+}
+
+/// Preview the migration steps and defaults that would apply to
+/// `toml_input`, a document of `kind`, without changing it.
+///
+/// Defaults are planned against the migrated document, so fields a
+/// migration introduces get theirs. As with
+/// [`apply_defaults`](crate::apply_defaults), defaults never create wildcard
+/// instances or tables whose presence is meaningful. Input that is not
+/// valid TOML plans no changes; the document is not otherwise validated.
+pub fn plan(kind: SchemaKind, toml_input: &str, options: PlanOptions) -> Plan {
+    let mut changes = Vec::new();
+    let mut migrated = None;
+    if let (SchemaKind::Config, Some(from)) = (kind, &options.migrate_from) {
+        let to = schema_version(kind);
+        changes.extend(steps(from, &to).map(|step| PlannedChange::Migration {
+            from: step.from.clone(),
+            to: step.to.clone(),
+            summary: step.summary.to_owned(),
+        }));
+        migrated = migrate_config(toml_input, from.clone(), to).ok();
+    }
+
+    if options.defaults {
+        let input = migrated.as_deref().unwrap_or(toml_input);
+        if let Ok(doc) = toml::from_str::<Table>(input) {
+            let mut filled = doc.clone();
+            fill_defaults(kind, &mut filled);
+            added(&doc, &filled, "", &mut changes);
+        }
+    }
+    Plan { changes }
+}
+
+/// The values in `after` that `before` lacks, as [`PlannedChange::Default`]s.
+fn added(before: &Table, after: &Table, path: &str, changes: &mut Vec<PlannedChange>) {
+    for (key, value) in after {
+        let path = join(path, key);
+        match (before.get(key), value) {
+            (Some(Value::Table(before)), Value::Table(after)) => {
+                added(before, after, &path, changes);
+            }
+            (Some(_), _) => {}
+            // A table created to hold defaults.
+            (None, Value::Table(after)) => added(&Table::new(), after, &path, changes),
+            (None, value) => changes.push(PlannedChange::Default {
+                path,
+                value: value.clone(),
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn omitted_fields_are_planned_with_their_defaults() {
+        let config = "[proxy]\nid = \"gw\"\npipelines_path = \"custom\"\n";
+        let plan = plan(SchemaKind::Config, config, PlanOptions::default());
+        assert!(plan.changes.contains(&PlannedChange::Default {
+            path: "proxy.transforms_path".to_owned(),
+            value: Value::String("transforms".to_owned()),
+        }));
+        let paths: Vec<&str> = plan
+            .changes
+            .iter()
+            .filter_map(|change| match change {
+                PlannedChange::Default { path, .. } => Some(path.as_str()),
+                PlannedChange::Migration { .. } => None,
+            })
+            .collect();
+        assert!(!paths.contains(&"proxy.pipelines_path"));
+        assert!(paths.contains(&"logging.log_level"));
+        assert!(paths.iter().all(|path| !path.starts_with("network.")));
+
+        let options = PlanOptions {
+            defaults: false,
+            ..PlanOptions::default()
+        };
+        assert!(super::plan(SchemaKind::Config, config, options).is_empty());
+        assert!(super::plan(SchemaKind::Config, "[proxy", PlanOptions::default()).is_empty());
+    }
+
+    #[test]
+    fn migrations_are_planned_before_the_defaults_they_need() {
+        let config = "[proxy]\nid = \"gw\"\n\n[runbeam]\nenabled = true\n";
+        let options = PlanOptions {
+            migrate_from: Some(Version::new(1, 10, 0)),
+            ..PlanOptions::default()
+        };
+        let plan = plan(SchemaKind::Config, config, options);
+        assert!(matches!(
+            &plan.changes[0],
+            PlannedChange::Migration { from, to, .. }
+                if *from == Version::new(1, 10, 0) && *to == Version::new(1, 11, 0)
+        ));
+        assert!(plan.changes.iter().any(|change| matches!(
+            change,
+            PlannedChange::Default { path, .. } if path.starts_with("provider.runbeam.")
+        )));
+    }
+}
@@ -0,0 +1,132 @@
+//! Masking credentials before a document is logged or shared.
+//!
+//! A config attached to a support ticket must not carry the gateway's
+//! passwords and keys. [`redact`] replaces the value of every field the
+//! schema marks `secret = true` with `"***"`, keeping comments, layout and
+//! every other value as written.
+
+use toml::Table;
+use toml_edit::{DocumentMut, Item, TableLike, Value};
+
+use crate::model::ConfigError;
+use crate::schema::{bundled_schema, Schema};
+use crate::validate::{element, join, parse_error, ValidationError, ValidationErrorKind};
+use crate::SchemaKind;
+
+/// What secret values are replaced with.
+const MASK: &str = "***";
+
+/// `toml_input`, a document of `kind`, with the value of every `secret`
+/// field replaced by `"***"`.
+///
+/// The document is not validated, so a broken config can still be redacted
+/// and shared; only input that is not valid TOML fails, with
+/// [`ConfigError::Invalid`]. A redacted document keeps its structure and
+/// types, but a masked field with a `format`, such as a WireGuard key, no
+/// longer passes it.
+pub fn redact(kind: SchemaKind, toml_input: &str) -> Result<String, ConfigError> {
+    let invalid = |error: ValidationError| ConfigError::Invalid(vec![error].into());
+    toml::from_str::<Table>(toml_input).map_err(|err| invalid(parse_error(toml_input, &err)))?;
+    let mut doc: DocumentMut = toml_input.parse().map_err(|err: toml_edit::TomlError| {
+        invalid(ValidationError::new(
+            "",
+            ValidationErrorKind::InvalidToml,
+            err.message(),
+        ))
+    })?;
+    redact_table(&bundled_schema(kind), doc.as_table_mut(), "");
+    Ok(doc.to_string())
+}
+
+fn redact_table(schema: &Schema, table: &mut dyn TableLike, path: &str) {
+    for (key, item) in table.iter_mut() {
+        let path = join(path, key.get());
+        if schema.field(&path).is_some_and(|rule| rule.secret) {
+            mask(item);
+            continue;
+        }
+        match item {
+            Item::Table(child) => redact_table(schema, child, &path),
+            Item::ArrayOfTables(tables) => {
+                for (index, child) in tables.iter_mut().enumerate() {
+                    redact_table(schema, child, &element(&path, index));
+                }
+            }
+            Item::Value(Value::InlineTable(child)) => redact_table(schema, child, &path),
+            Item::Value(Value::Array(items)) => {
+                for (index, item) in items.iter_mut().enumerate() {
+                    if let Value::InlineTable(child) = item {
+                        redact_table(schema, child, &element(&path, index));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Replace a secret, keeping the comments around it.
+fn mask(item: &mut Item) {
+    match item {
+        Item::Value(value) => {
+            let decor = value.decor().clone();
+            *value = Value::from(MASK);
+            *value.decor_mut() = decor;
+        }
+        // A secret written as a table has no decor worth keeping.
+        _ => *item = toml_edit::value(MASK),
+    }
+}
+
+#[cfg(all(test, feature = "schema-pipeline"))]
+mod tests {
+    use super::*;
+    use crate::validate::validate;
+
+    #[test]
+    fn redacted_configs_keep_their_shape_but_not_their_secrets() {
+        let config = r#"
+[proxy]
+id = "gw"
+
+[management]
+enabled = true
+network = "default"
+auth_token = "hunter2" # rotate monthly
+
+[network.default]
+enable_wireguard = true
+interface = "wg0"
+private_key = "yAnz5TF+lXXJte14tji3zlMNq+hd2rYUIgJBgB3fBmk="
+"#;
+        validate(SchemaKind::Config, config).unwrap();
+        let redacted = redact(SchemaKind::Config, config).unwrap();
+        assert!(!redacted.contains("hunter2"));
+        assert!(!redacted.contains("yAnz5TF+lXXJte14tji3zlMNq+hd2rYUIgJBgB3fBmk="));
+        assert!(redacted.contains("auth_token = \"***\" # rotate monthly"));
+        assert!(redacted.contains("id = \"gw\""));
+        // Only the mask's format is wrong: it is no WireGuard key.
+        let errors = validate(SchemaKind::Config, &redacted).unwrap_err();
+        let errors: Vec<_> = errors.iter().map(|e| (e.path.as_str(), &e.kind)).collect();
+        assert!(matches!(
+            errors[..],
+            [(
+                "network.default.private_key",
+                ValidationErrorKind::InvalidFormat { .. }
+            )]
+        ));
+
+        let pipeline = r#"
+[pipelines.imaging.mesh.egress.archive]
+type = "s3"
+options = { bucket = "scans", access_key_id = "AKIA", secret_access_key = "wJalrXUtnFEMI" }
+"#;
+        let redacted = redact(SchemaKind::Pipeline, pipeline).unwrap();
+        assert!(redacted.contains("access_key_id = \"AKIA\", secret_access_key = \"***\""));
+
+        assert!(matches!(
+            redact(SchemaKind::Config, "[proxy"),
+            Err(ConfigError::Invalid(_))
+        ));
+    }
+}
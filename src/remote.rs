@@ -0,0 +1,185 @@
+//! Fetching published schemas over HTTP(S).
+//!
+//! The cloud API can publish schemas newer than the ones bundled with this
+//! crate. [`fetch_schema`] downloads one to pass to
+//! [`validate_with_schema`](crate::validate_with_schema), so a proxy that is
+//! online validates against the authoritative schema and one that is offline
+//! falls back to the bundled one:
+//!
+//! ```no_run
+//! # async fn example(base_url: url::Url, config: &str) {
+//! use harmony_dsl::{fetch_schema, validate, validate_with_schema, SchemaKind};
+//!
+//! let result = match fetch_schema(SchemaKind::Config, &base_url).await {
+//!     Ok(schema) => validate_with_schema(&schema, config),
+//!     Err(_) => validate(SchemaKind::Config, config),
+//! };
+//! # }
+//! ```
+
+use core::fmt;
+use core::time::Duration;
+
+use url::Url;
+
+use crate::schema::{parse_schema, SchemaParseError};
+use crate::SchemaKind;
+
+/// Reasons [`fetch_schema`] can fail.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum FetchError {
+    /// The schema's URL could not be formed from the base URL.
+    InvalidUrl(url::ParseError),
+    /// The request failed, e.g. because the server could not be reached.
+    Request(reqwest::Error),
+    /// The server answered with a status other than success.
+    Status {
+        /// The URL the schema was requested from.
+        url: Url,
+        /// The HTTP status code of the response.
+        status: u16,
+    },
+    /// The response is not a valid schema.
+    InvalidSchema(SchemaParseError),
+}
+
+impl fmt::Display for FetchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FetchError::InvalidUrl(err) => write!(f, "invalid schema URL: {err}"),
+            FetchError::Request(err) => write!(f, "could not fetch schema: {err}"),
+            FetchError::Status { url, status } => {
+                write!(f, "fetching {url} failed with status {status}")
+            }
+            FetchError::InvalidSchema(err) => write!(f, "fetched schema is invalid: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for FetchError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            FetchError::InvalidUrl(err) => Some(err),
+            FetchError::Request(err) => Some(err),
+            FetchError::Status { .. } => None,
+            FetchError::InvalidSchema(err) => Some(err),
+        }
+    }
+}
+
+/// How long [`fetch_schema`] waits to connect to the server.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long [`fetch_schema`] waits for the whole response.
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Download the schema for `kind` published under `base_url`.
+///
+/// `base_url` is the directory of one schema version, e.g.
+/// `https://api.example.com/schemas/1.12.0/`, holding the files under the
+/// names they are bundled with, such as `harmony-config-schema.toml`. The
+/// body is checked with [`parse_schema`](crate::parse_schema) before it is
+/// returned, so an error page served with a success status is reported as
+/// [`FetchError::InvalidSchema`].
+///
+/// The request gives up after 10 seconds without a connection, or 30 without
+/// the complete response, with a [`FetchError::Request`], so a caller falling
+/// back to the bundled schema is not left waiting on an unresponsive server.
+pub async fn fetch_schema(kind: SchemaKind, base_url: &Url) -> Result<String, FetchError> {
+    let mut base = base_url.clone();
+    if !base.path().ends_with('/') {
+        let path = format!("{}/", base.path());
+        base.set_path(&path);
+    }
+    let url = base
+        .join(&format!("harmony-{}-schema.toml", kind.name()))
+        .map_err(FetchError::InvalidUrl)?;
+
+    let client = reqwest::Client::builder()
+        .connect_timeout(CONNECT_TIMEOUT)
+        .timeout(TIMEOUT)
+        .build()
+        .map_err(FetchError::Request)?;
+    let response = client
+        .get(url.clone())
+        .send()
+        .await
+        .map_err(FetchError::Request)?;
+    let status = response.status();
+    if !status.is_success() {
+        return Err(FetchError::Status {
+            url,
+            status: status.as_u16(),
+        });
+    }
+    let body = response.text().await.map_err(FetchError::Request)?;
+    parse_schema(&body).map_err(FetchError::InvalidSchema)?;
+    Ok(body)
+}
+
+#[cfg(all(test, feature = "schema-config"))]
+mod tests {
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::thread;
+
+    use super::*;
+    use crate::validate::validate_with_schema;
+
+    /// Serve one request with `status` and `body`, returning the base URL
+    /// and the request line received.
+    fn serve(status: &'static str, body: &'static str) -> (Url, thread::JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let base = format!("http://{}/schemas/1.12.0", listener.local_addr().unwrap());
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut request = String::new();
+            reader.read_line(&mut request).unwrap();
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap() > 2 {
+                line.clear();
+            }
+            let response = format!(
+                "HTTP/1.1 {status}\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+                body.len()
+            );
+            reader.get_mut().write_all(response.as_bytes()).unwrap();
+            request
+        });
+        (Url::parse(&base).unwrap(), server)
+    }
+
+    fn fetch(base: &Url) -> Result<String, FetchError> {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        runtime.block_on(fetch_schema(SchemaKind::Config, base))
+    }
+
+    #[test]
+    fn schemas_are_fetched_by_kind_under_the_base_url() {
+        const SCHEMA: &str = "[schema]\nversion = \"1.12.0\"\n\n[[table]]\nname = \"proxy\"\n\n\
+                              [[table.field]]\nname = \"id\"\ntype = \"string\"\n";
+        let (base, server) = serve("200 OK", SCHEMA);
+        let schema = fetch(&base).unwrap();
+        assert_eq!(
+            server.join().unwrap().trim_end(),
+            "GET /schemas/1.12.0/harmony-config-schema.toml HTTP/1.1"
+        );
+        assert_eq!(schema, SCHEMA);
+        assert!(validate_with_schema(&schema, "[proxy]\nid = \"gw\"\n").is_ok());
+
+        let (base, server) = serve("404 Not Found", "");
+        let errors = fetch(&base).unwrap_err();
+        server.join().unwrap();
+        assert!(matches!(errors, FetchError::Status { status: 404, .. }));
+
+        let (base, server) = serve("200 OK", "<html>maintenance</html>");
+        let errors = fetch(&base).unwrap_err();
+        server.join().unwrap();
+        assert!(matches!(errors, FetchError::InvalidSchema(_)));
+    }
+}
@@ -0,0 +1,481 @@
+//! Rendering of validation errors for people and for tools.
+//!
+//! [`render_terminal`] prints errors the way `rustc` does: a headline, the
+//! location, and the offending source line with the token underlined.
+//! [`render_json`] emits them as JSON for CI and other programs, and
+//! [`to_sarif`] as SARIF for code scanning services.
+//!
+//! ```text
+//! error: proxy.id: `Edge GW` does not match the pattern `^[a-z0-9-]+$`
+//!  --> 2:6
+//!   |
+//! 2 | id = "Edge GW"
+//!   |      ^^^^^^^^^
+//! ```
+
+use std::io::{self, Write};
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::validate::{Severity, ValidationError, ValidationReport};
+
+const RESET: &str = "\x1b[0m";
+const BOLD: &str = "\x1b[1m";
+const RED: &str = "\x1b[1;31m";
+const YELLOW: &str = "\x1b[1;33m";
+const BLUE: &str = "\x1b[1;34m";
+
+/// Write `errors`, found in the document `input`, to `writer`, which may be
+/// a terminal or a buffer.
+///
+/// Output is colored only when `color` is set, e.g. because `writer` is a
+/// terminal, and the `NO_COLOR` environment variable is unset or empty.
+pub fn render_terminal(
+    input: &str,
+    errors: &[ValidationError],
+    writer: &mut impl Write,
+    color: bool,
+) -> io::Result<()> {
+    let color = color && std::env::var_os("NO_COLOR").is_none_or(|value| value.is_empty());
+    render(input, errors, writer, color)
+}
+
+fn render(
+    input: &str,
+    errors: &[ValidationError],
+    writer: &mut impl Write,
+    color: bool,
+) -> io::Result<()> {
+    let paint = |style: &'static str| if color { style } else { "" };
+    let reset = paint(RESET);
+
+    for (index, error) in errors.iter().enumerate() {
+        if index > 0 {
+            writeln!(writer)?;
+        }
+        let (label, style) = match error.severity {
+            Severity::Error => ("error", RED),
+            Severity::Warning => ("warning", YELLOW),
+            Severity::Info => ("info", BLUE),
+        };
+        write!(writer, "{}{label}{reset}{}: ", paint(style), paint(BOLD))?;
+        if !error.path.is_empty() {
+            write!(writer, "{}: ", error.path)?;
+        }
+        writeln!(writer, "{}{reset}", error.message)?;
+
+        let Some((line, column)) = error.span else {
+            if let Some(file) = &error.file {
+                writeln!(writer, " {}-->{reset} {}", paint(BLUE), file.display())?;
+            }
+            continue;
+        };
+        let gutter = " ".repeat(line.to_string().len());
+        let blue = paint(BLUE);
+        write!(writer, "{gutter}{blue}-->{reset} ")?;
+        if let Some(file) = &error.file {
+            write!(writer, "{}:", file.display())?;
+        }
+        writeln!(writer, "{line}:{column}")?;
+
+        let Some(source) = line.checked_sub(1).and_then(|line| input.lines().nth(line)) else {
+            continue;
+        };
+        let (indent, marked) = underline(source, column);
+        writeln!(writer, "{gutter} {blue}|{reset}")?;
+        writeln!(writer, "{blue}{line} |{reset} {source}")?;
+        writeln!(
+            writer,
+            "{gutter} {blue}|{reset} {indent}{}{}{reset}",
+            paint(style),
+            "^".repeat(marked)
+        )?;
+    }
+    Ok(())
+}
+
+/// One entry of [`render_json`]'s output.
+#[derive(Serialize)]
+struct JsonDiagnostic<'a> {
+    file: Option<&'a Path>,
+    path: &'a str,
+    line: Option<usize>,
+    column: Option<usize>,
+    severity: Severity,
+    code: &'static str,
+    message: &'a str,
+}
+
+/// Render `report` as a JSON array with one object per diagnostic, in the
+/// report's order:
+///
+/// ```json
+/// [{"file": null, "path": "proxy.id", "line": 2, "column": 6, "severity": "error", "code": "E_PATTERN_MISMATCH", "message": "..."}]
+/// ```
+///
+/// `file`, `line` and `column` are `null` when unknown; `line` and `column`
+/// are 1-based. `code` is [`ErrorCode::as_str`], which stays the same across
+/// releases while messages may be reworded.
+///
+/// [`ErrorCode::as_str`]: crate::ErrorCode::as_str
+pub fn render_json(report: &ValidationReport) -> String {
+    let diagnostics: Vec<JsonDiagnostic<'_>> = report
+        .diagnostics()
+        .iter()
+        .map(|error| JsonDiagnostic {
+            file: error.file.as_deref(),
+            path: &error.path,
+            line: error.span.map(|(line, _)| line),
+            column: error.span.map(|(_, column)| column),
+            severity: error.severity,
+            code: error.code.as_str(),
+            message: &error.message,
+        })
+        .collect();
+    serde_json::to_string(&diagnostics).expect("diagnostics serialize to JSON")
+}
+
+/// Convert the errors and warnings of a validated deployment into a SARIF
+/// 2.1.0 log, for code scanning services such as GitHub's.
+///
+/// The log has one run with one result per diagnostic of
+/// [`DirectoryReport::report`], in its order. Each result's `ruleId` is its
+/// [`ErrorCode::as_str`], and its location is the file it was found in, as
+/// a relative URI if the directory was given as a relative path, with the
+/// 1-based line and column of its span when known. A diagnostic without a
+/// file, such as an archive that could not be read, has no location. Missing
+/// required files are not diagnostics, so they have no results; check
+/// [`DirectoryReport::missing`] for them.
+///
+/// [`ErrorCode::as_str`]: crate::ErrorCode::as_str
+#[cfg(all(
+    feature = "schema-config",
+    feature = "schema-pipeline",
+    feature = "schema-mesh",
+    feature = "schema-remote-ingress"
+))]
+pub fn to_sarif(reports: &crate::DirectoryReport) -> serde_json::Value {
+    use serde_json::json;
+
+    let report = reports.report();
+    let mut rules: Vec<&'static str> = Vec::new();
+    let results: Vec<serde_json::Value> = report
+        .diagnostics()
+        .iter()
+        .map(|error| {
+            let code = error.code.as_str();
+            if !rules.contains(&code) {
+                rules.push(code);
+            }
+            // SARIF requires a physical location to name its artifact.
+            let file = error
+                .file
+                .as_deref()
+                .filter(|file| !file.as_os_str().is_empty());
+            let locations: Vec<serde_json::Value> = file
+                .map(|file| {
+                    let mut location = json!({ "artifactLocation": { "uri": file_uri(file) } });
+                    if let Some((line, column)) = error.span {
+                        location["region"] = json!({ "startLine": line, "startColumn": column });
+                    }
+                    json!({ "physicalLocation": location })
+                })
+                .into_iter()
+                .collect();
+            let level = match error.severity {
+                Severity::Error => "error",
+                Severity::Warning => "warning",
+                Severity::Info => "note",
+            };
+            let message = match error.path.as_str() {
+                "" => error.message.clone(),
+                path => format!("{path}: {}", error.message),
+            };
+            let mut result = json!({
+                "ruleId": code,
+                "level": level,
+                "message": { "text": message },
+            });
+            if !locations.is_empty() {
+                result["locations"] = json!(locations);
+            }
+            result
+        })
+        .collect();
+    let rules: Vec<serde_json::Value> = rules.iter().map(|id| json!({ "id": id })).collect();
+
+    json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": env!("CARGO_PKG_NAME"),
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "rules": rules,
+                },
+            },
+            "columnKind": "unicodeCodePoints",
+            "results": results,
+        }],
+    })
+}
+
+/// `path` as a SARIF artifact URI: a `file://` URL if it is absolute, else
+/// its components joined with `/`.
+#[cfg(all(
+    feature = "schema-config",
+    feature = "schema-pipeline",
+    feature = "schema-mesh",
+    feature = "schema-remote-ingress"
+))]
+fn file_uri(path: &Path) -> String {
+    if let Ok(url) = url::Url::from_file_path(path) {
+        return url.to_string();
+    }
+    let components: Vec<_> = path
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect();
+    components.join("/")
+}
+
+/// The padding before the token starting at the 1-based character `column`
+/// of `source`, and the token's length in characters. Tabs in the padding
+/// are kept so the carets line up however the terminal renders them.
+fn underline(source: &str, column: usize) -> (String, usize) {
+    let indent = source
+        .chars()
+        .take(column.saturating_sub(1))
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .collect();
+    let rest: String = source.chars().skip(column.saturating_sub(1)).collect();
+    (indent, token_len(&rest).max(1))
+}
+
+/// The length in characters of the TOML token at the start of `rest`: a
+/// quoted string, a bracketed header, array or inline table closed on the
+/// same line, or a bare word.
+fn token_len(rest: &str) -> usize {
+    let mut chars = rest.chars();
+    let closing = match chars.next() {
+        Some(quote @ ('"' | '\'')) => quote,
+        Some('[') => ']',
+        Some('{') => '}',
+        _ => {
+            return rest
+                .chars()
+                .take_while(|c| !c.is_whitespace() && !"=,]}#".contains(*c))
+                .count();
+        }
+    };
+    let mut depth = 0;
+    let mut escaped = false;
+    for (index, c) in rest.chars().enumerate().skip(1) {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if closing == '"' => escaped = true,
+            c if c == closing && depth == 0 => return index + 1,
+            c if c == closing => depth -= 1,
+            '[' if closing == ']' => depth += 1,
+            '{' if closing == '}' => depth += 1,
+            _ => {}
+        }
+    }
+    rest.chars().count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "schema-config")]
+    use crate::validate::{validate_report_with, ValidationMode, ValidationOptions};
+    #[cfg(feature = "schema-config")]
+    use crate::{validate_config, SchemaKind};
+
+    #[test]
+    #[cfg(feature = "schema-config")]
+    fn errors_are_rendered_with_the_offending_token_underlined() {
+        let input = "[proxy]\nid = \"Edge GW\"\n\n[logging]\nlog_level = 3\n";
+        let errors = validate_config(input).unwrap_err();
+
+        let mut out = Vec::new();
+        render(input, &errors, &mut out, false).unwrap();
+        let out = String::from_utf8(out).unwrap();
+
+        let expected = format!(
+            "error: proxy.id: {}\n \
+             --> 2:6\n  \
+             |\n\
+             2 | id = \"Edge GW\"\n  \
+             |      ^^^^^^^^^\n\
+             \n\
+             error: logging.log_level: {}\n \
+             --> 5:13\n  \
+             |\n\
+             5 | log_level = 3\n  \
+             |             ^\n",
+            errors[0].message, errors[1].message
+        );
+        assert_eq!(out, expected);
+    }
+
+    #[test]
+    #[cfg(feature = "schema-config")]
+    fn color_is_only_used_when_requested() {
+        let errors = validate_config("[logging]\n").unwrap_err();
+        let mut plain = Vec::new();
+        render("[logging]\n", &errors, &mut plain, false).unwrap();
+        assert!(!plain.contains(&0x1b));
+
+        let mut colored = Vec::new();
+        render("[logging]\n", &errors, &mut colored, true).unwrap();
+        assert!(String::from_utf8(colored).unwrap().starts_with(RED));
+    }
+
+    #[test]
+    #[cfg(feature = "schema-config")]
+    fn spans_at_column_zero_render_without_panicking() {
+        let input = "{\"a\": \"x\ny\"}";
+        let errors = crate::validate_json(SchemaKind::Config, input).unwrap_err();
+        assert_eq!(errors[0].span, Some((2, 1)));
+
+        let at_zero = ValidationError {
+            span: Some((2, 0)),
+            ..errors[0].clone()
+        };
+        let mut out = Vec::new();
+        render_terminal(input, &[at_zero], &mut out, false).unwrap();
+        assert!(String::from_utf8(out).unwrap().contains("2 | y\"}"));
+    }
+
+    #[test]
+    #[cfg(feature = "schema-config")]
+    fn json_output_is_stable() {
+        let input = "[proxy]\nid = \"Edge GW\"\nlog = 1\n\n[runbeam]\nenabled = true\n";
+        let options = ValidationOptions {
+            mode: ValidationMode::Lenient,
+            ..ValidationOptions::default()
+        };
+        let report = validate_report_with(SchemaKind::Config, input, options);
+        let json: serde_json::Value = serde_json::from_str(&render_json(&report)).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!([
+                {
+                    "file": null,
+                    "path": "proxy.id",
+                    "line": 2,
+                    "column": 6,
+                    "severity": "error",
+                    "code": "E_PATTERN_MISMATCH",
+                    "message": "`Edge GW` does not match the pattern `^[a-z0-9-]+$`, e.g. `\"edge-gw-01\"`",
+                },
+                {
+                    "file": null,
+                    "path": "proxy.log",
+                    "line": 3,
+                    "column": 7,
+                    "severity": "warning",
+                    "code": "E_UNKNOWN_KEY",
+                    "message": "unknown key `log`",
+                },
+                {
+                    "file": null,
+                    "path": "runbeam",
+                    "line": 5,
+                    "column": 1,
+                    "severity": "warning",
+                    "code": "E_DEPRECATED",
+                    "message": "`runbeam` is deprecated: use [provider.runbeam] and \
+                                proxy.primary_provider instead (see migrate_config)",
+                },
+            ])
+        );
+    }
+
+    #[test]
+    fn tokens_span_strings_brackets_and_bare_words() {
+        assert_eq!(token_len(r#""a \" b" # note"#), 8);
+        assert_eq!(token_len("[a, [b]] x"), 8);
+        assert_eq!(token_len("{ a = 1 }"), 9);
+        assert_eq!(token_len("8080, 9090"), 4);
+        assert_eq!(token_len("\"unterminated"), 13);
+    }
+
+    #[cfg(all(
+        feature = "schema-config",
+        feature = "schema-pipeline",
+        feature = "schema-mesh",
+        feature = "schema-remote-ingress"
+    ))]
+    #[test]
+    fn sarif_has_one_result_per_diagnostic() {
+        use std::path::PathBuf;
+
+        use crate::{DirectoryReport, FileReport};
+
+        let mut reports = DirectoryReport::default();
+        for (path, kind, input) in [
+            (
+                "config.toml",
+                SchemaKind::Config,
+                "[proxy]\nid = \"Edge GW\"\n\n[logging]\nlog_level = 3\n",
+            ),
+            (
+                "pipelines/a.toml",
+                SchemaKind::Pipeline,
+                "[endpoints.in]\nservice = \"http\"\n",
+            ),
+        ] {
+            reports.files.push(FileReport {
+                path: PathBuf::from(path),
+                kind,
+                errors: crate::validate_report(kind, input).into_diagnostics(),
+            });
+        }
+        // An unreadable archive is reported on a file with no path.
+        reports.files.push(FileReport {
+            path: PathBuf::new(),
+            kind: SchemaKind::Config,
+            errors: vec![crate::ValidationError::new(
+                "",
+                crate::ValidationErrorKind::Io,
+                "not an archive",
+            )],
+        });
+
+        let sarif = to_sarif(&reports);
+        let schema: serde_json::Value = serde_json::from_str(include_str!(
+            "../tests/fixtures/sarif/sarif-2.1.0-excerpt.json"
+        ))
+        .unwrap();
+        let validator = jsonschema::validator_for(&schema).unwrap();
+        let problems: Vec<String> = validator
+            .iter_errors(&sarif)
+            .map(|error| error.to_string())
+            .collect();
+        assert!(problems.is_empty(), "{problems:?}");
+        assert_eq!(sarif["version"], "2.1.0");
+        let results = sarif["runs"][0]["results"].as_array().unwrap();
+        assert_eq!(results.len(), reports.report().diagnostics().len());
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0]["ruleId"], "E_IO");
+        assert!(results[0].get("locations").is_none());
+        assert_eq!(results[1]["ruleId"], "E_PATTERN_MISMATCH");
+        assert_eq!(results[1]["level"], "error");
+        let location = &results[1]["locations"][0]["physicalLocation"];
+        assert_eq!(location["artifactLocation"]["uri"], "config.toml");
+        assert_eq!(
+            location["region"],
+            serde_json::json!({ "startLine": 2, "startColumn": 6 })
+        );
+        assert!(results[2]["message"]["text"]
+            .as_str()
+            .unwrap()
+            .starts_with("logging.log_level: "));
+        let rules = &sarif["runs"][0]["tool"]["driver"]["rules"];
+        assert_eq!(rules.as_array().unwrap().len(), 3);
+    }
+}
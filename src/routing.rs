@@ -0,0 +1,210 @@
+//! Which pipeline a request would be routed to.
+//!
+//! A pipeline receives requests through the endpoints it lists. An HTTP
+//! endpoint accepts the requests under its `options.path_prefix`, the only
+//! routing rule the pipeline schema declares. [`pipeline_routes`] reads these
+//! rules from pipeline files as [`PipelineRoute`]s, and [`match_route`] picks
+//! the one a request path hits, to debug routing without running the
+//! gateway.
+
+use alloc::collections::BTreeMap;
+
+use toml::{Table, Value};
+
+use crate::prelude::*;
+
+/// One way into a pipeline: an HTTP endpoint it lists, with the path prefix
+/// the endpoint accepts requests under.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PipelineRoute {
+    /// The pipeline's name, e.g. `imaging` for `[pipelines.imaging]`.
+    pub pipeline: String,
+    /// The endpoint's name.
+    pub endpoint: String,
+    /// `options.path_prefix` with a leading and no trailing `/`, or `/`
+    /// when unset.
+    pub path_prefix: String,
+}
+
+/// The route a request hits, from [`match_route`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RouteMatch {
+    /// Index into the `pipelines` slice of the file defining the pipeline.
+    pub file: usize,
+    /// The matched pipeline's name.
+    pub pipeline: String,
+    /// The rule that won.
+    pub route: PipelineRoute,
+}
+
+impl PipelineRoute {
+    /// Whether a request for `path` falls under this route's prefix, taken
+    /// segment by segment, so `/api` covers `/api/fhir` but not `/apis`.
+    pub fn matches(&self, path: &str) -> bool {
+        let path = path.split(['?', '#']).next().unwrap_or_default();
+        self.path_prefix == "/"
+            || path
+                .strip_prefix(self.path_prefix.as_str())
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+    }
+
+    /// The length of the prefix, with `/` the least specific.
+    fn specificity(&self) -> usize {
+        if self.path_prefix == "/" {
+            0
+        } else {
+            self.path_prefix.len()
+        }
+    }
+}
+
+/// The routes of the pipelines in `pipelines`, one pipeline file each, with
+/// the index of the file defining each pipeline. Routes come in file order,
+/// then by pipeline name, then in the order a pipeline lists its endpoints.
+///
+/// A pipeline may list endpoints defined in any of the files. Endpoints of
+/// the `dicom` service, which listen for DIMSE rather than HTTP, and
+/// endpoints no file defines have no route. Files that are not valid TOML
+/// define nothing; the pipelines are not otherwise validated.
+pub fn pipeline_routes(pipelines: &[&str]) -> Vec<(usize, PipelineRoute)> {
+    let docs: Vec<Option<Table>> = pipelines
+        .iter()
+        .map(|source| toml::from_str(source).ok())
+        .collect();
+    let tables = |doc: &Option<Table>, key: &str| {
+        doc.as_ref()
+            .and_then(|doc| doc.get(key))
+            .and_then(Value::as_table)
+            .cloned()
+            .unwrap_or_default()
+    };
+    let endpoints: BTreeMap<String, Value> = docs
+        .iter()
+        .flat_map(|doc| tables(doc, "endpoints"))
+        .collect();
+
+    let mut routes = Vec::new();
+    for (file, doc) in docs.iter().enumerate() {
+        for (pipeline, definition) in tables(doc, "pipelines") {
+            let listed = definition.get("endpoints").and_then(Value::as_array);
+            for name in listed.into_iter().flatten().filter_map(Value::as_str) {
+                let Some(endpoint) = endpoints.get(name) else {
+                    continue;
+                };
+                if endpoint.get("service").and_then(Value::as_str) == Some("dicom") {
+                    continue;
+                }
+                routes.push((file, route(&pipeline, name, endpoint)));
+            }
+        }
+    }
+    routes
+}
+
+/// Find the route a request for `method`, `path` and `host` hits among the
+/// [`pipeline_routes`] of `pipelines`.
+///
+/// Of the routes whose prefix covers `path`, the one with the longest
+/// prefix wins, and equal prefixes go to the route that comes first. The
+/// schema declares no routing by method or host, so `method` and `host` do
+/// not affect the match; nor does this model how the gateway breaks ties
+/// between endpoints sharing a prefix.
+pub fn match_route(
+    pipelines: &[&str],
+    _method: &str,
+    path: &str,
+    _host: &str,
+) -> Option<RouteMatch> {
+    let mut best: Option<(usize, PipelineRoute)> = None;
+    for (file, route) in pipeline_routes(pipelines) {
+        if !route.matches(path) {
+            continue;
+        }
+        if best
+            .as_ref()
+            .is_none_or(|(_, best)| route.specificity() > best.specificity())
+        {
+            best = Some((file, route));
+        }
+    }
+    best.map(|(file, route)| RouteMatch {
+        file,
+        pipeline: route.pipeline.clone(),
+        route,
+    })
+}
+
+fn route(pipeline: &str, endpoint: &str, definition: &Value) -> PipelineRoute {
+    let prefix = definition
+        .get("options")
+        .and_then(|options| options.get("path_prefix"))
+        .and_then(Value::as_str)
+        .unwrap_or_default()
+        .trim_matches('/');
+    PipelineRoute {
+        pipeline: pipeline.to_owned(),
+        endpoint: endpoint.to_owned(),
+        path_prefix: format!("/{prefix}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const API: &str = r#"
+[pipelines.api]
+networks = ["default"]
+endpoints = ["web"]
+backends = ["app"]
+
+[endpoints.web]
+service = "http"
+options = { path_prefix = "/api/" }
+
+[endpoints.scp]
+service = "dicom"
+options = { local_aet = "HARMONY_SCP" }
+"#;
+
+    const FHIR: &str = r#"
+[pipelines.fhir]
+networks = ["default"]
+endpoints = ["fhir", "scp"]
+backends = ["store"]
+
+[endpoints.fhir]
+service = "http"
+options = { path_prefix = "api/fhir" }
+"#;
+
+    #[test]
+    fn the_most_specific_route_wins() {
+        let pipelines = [API, FHIR];
+        let routed = |path| {
+            match_route(&pipelines, "GET", path, "gw.example.com")
+                .map(|found| (found.file, found.pipeline))
+        };
+        assert_eq!(
+            routed("/api/fhir/Patient?id=1"),
+            Some((1, "fhir".to_owned()))
+        );
+        let found = match_route(&pipelines, "GET", "/api/fhir", "gw.example.com").unwrap();
+        assert_eq!(found.route.endpoint, "fhir");
+        assert_eq!(found.route.path_prefix, "/api/fhir");
+
+        // Outside the FHIR route's path segments, the broader API route
+        // applies.
+        assert_eq!(routed("/api/fhirish"), Some((0, "api".to_owned())));
+        assert_eq!(routed("/api"), Some((0, "api".to_owned())));
+        assert_eq!(routed("/status"), None);
+
+        // The DICOM endpoint listed by the FHIR pipeline has no route.
+        let routes = pipeline_routes(&pipelines);
+        let endpoints: Vec<_> = routes
+            .iter()
+            .map(|(_, route)| route.endpoint.as_str())
+            .collect();
+        assert_eq!(endpoints, ["web", "fhir"]);
+    }
+}
@@ -0,0 +1,140 @@
+//! Deployment-specific policies on top of schema validation.
+//!
+//! The bundled schemas describe what Harmony accepts; a deployment may be
+//! stricter ("every listener uses TLS", "no public S3 buckets"). Such a
+//! policy is a [`ValidationRule`], run by [`validate_config_with_rules`]
+//! after the schema checks, so policies don't require patching the schema.
+
+use toml::{Table, Value};
+
+use crate::prelude::*;
+use crate::span::SpanIndex;
+use crate::validate::{validate_report, ValidationError, ValidationErrorKind, ValidationReport};
+use crate::SchemaKind;
+
+/// A custom check run over a parsed document.
+///
+/// Rules report violations with [`ValidationReport::push`], typically as
+/// [`ValidationError::custom`] diagnostics. A diagnostic pushed without a
+/// span is given the span of its path, when the document has one.
+pub trait ValidationRule {
+    /// Check `doc`, the document as a TOML table, adding any violations to
+    /// `report`.
+    fn check(&self, doc: &Value, report: &mut ValidationReport);
+}
+
+/// [`validate_config`](crate::validate_config), then every rule in `rules`,
+/// in order.
+///
+/// Fails if the schema or any rule reports an error, returning every
+/// diagnostic in the order of [`ValidationReport`]. Rules only run over
+/// documents that parse as TOML.
+pub fn validate_config_with_rules(
+    toml_input: &str,
+    rules: &[Box<dyn ValidationRule>],
+) -> Result<(), Vec<ValidationError>> {
+    let mut report = validate_report(SchemaKind::Config, toml_input);
+    let Ok(doc) = toml::from_str::<Table>(toml_input) else {
+        return report.into_result();
+    };
+    let doc = Value::Table(doc);
+
+    let builtin = report.diagnostics().len();
+    for rule in rules {
+        rule.check(&doc, &mut report);
+    }
+    let mut diagnostics = report.into_diagnostics();
+    if let Some(spans) = SpanIndex::build(toml_input) {
+        for diagnostic in &mut diagnostics[builtin..] {
+            if diagnostic.span.is_none() {
+                diagnostic.span = spans.position(&diagnostic.path);
+            }
+        }
+    }
+    ValidationReport::new(diagnostics).into_result()
+}
+
+impl ValidationError {
+    /// An error reported by the [`ValidationRule`] named `rule`, of kind
+    /// [`Custom`](ValidationErrorKind::Custom). Set
+    /// [`severity`](ValidationError::severity) to make it a warning.
+    pub fn custom(rule: &str, path: impl Into<String>, message: impl Into<String>) -> Self {
+        ValidationError::new(
+            path,
+            ValidationErrorKind::Custom {
+                rule: rule.to_owned(),
+            },
+            message,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::validate::instances;
+    use crate::Severity;
+
+    /// Every TCP listener must serve TLS.
+    struct ListenersUseTls;
+
+    impl ValidationRule for ListenersUseTls {
+        fn check(&self, doc: &Value, report: &mut ValidationReport) {
+            let Some(doc) = doc.as_table() else {
+                return;
+            };
+            for (path, listener) in instances(doc, &["network", "*", "tcp_config"], "") {
+                if !listener.contains_key("cert_path") {
+                    report.push(ValidationError::custom(
+                        "listeners_use_tls",
+                        format!("{path}.bind_address"),
+                        "listeners must use TLS; set `cert_path` and `key_path`",
+                    ));
+                }
+            }
+        }
+    }
+
+    const CONFIG: &str = r#"[proxy]
+id = "gw"
+
+[network.public.tcp_config]
+bind_address = "0.0.0.0"
+bind_port = 443
+cert_path = "tests/fixtures/tls/cert.pem"
+key_path = "tests/fixtures/tls/key.pem"
+
+[network.internal.tcp_config]
+bind_address = "10.0.0.1"
+bind_port = 8080
+"#;
+
+    #[test]
+    fn custom_rules_run_after_the_schema() {
+        assert_eq!(crate::validate_config(CONFIG), Ok(()));
+
+        let rules: Vec<Box<dyn ValidationRule>> = vec![Box::new(ListenersUseTls)];
+        let errors = validate_config_with_rules(CONFIG, &rules).unwrap_err();
+        assert_eq!(errors.len(), 1, "{errors:?}");
+        assert_eq!(errors[0].path, "network.internal.tcp_config.bind_address");
+        assert_eq!(
+            errors[0].kind,
+            ValidationErrorKind::Custom {
+                rule: "listeners_use_tls".to_owned()
+            }
+        );
+        assert_eq!(errors[0].span, Some((11, 16)));
+        assert_eq!(errors[0].kind.code(), "E_CUSTOM");
+
+        let secured = CONFIG.replace(
+            "bind_port = 8080",
+            "bind_port = 8080\ncert_path = \"tests/fixtures/tls/cert.pem\"\n\
+             key_path = \"tests/fixtures/tls/key.pem\"",
+        );
+        assert_eq!(validate_config_with_rules(&secured, &rules), Ok(()));
+
+        let errors = validate_config_with_rules("[proxy", &rules).unwrap_err();
+        assert_eq!(errors[0].kind, ValidationErrorKind::InvalidToml);
+        assert_eq!(errors[0].severity, Severity::Error);
+    }
+}
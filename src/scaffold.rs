@@ -0,0 +1,405 @@
+//! Commented TOML templates generated from the schemas.
+//!
+//! [`scaffold`] gives new users a starting point instead of a blank file:
+//! required fields are emitted with placeholder values, optional fields and
+//! tables are emitted commented out, and each line carries the field's
+//! description. A field with a `required_if` condition is emitted when the
+//! fields already emitted above it satisfy the condition.
+//!
+//! [`minimal_valid`] instead emits only what validation requires, for tests
+//! and examples that want the smallest valid document.
+
+use core::fmt::Write;
+
+use toml::{Table, Value};
+
+#[cfg(feature = "schema-config")]
+use crate::model::ConfigError;
+use crate::prelude::*;
+use crate::schema::{bundled_schema, schema_version, FieldRule, FieldType, Schema};
+use crate::SchemaKind;
+
+/// Name used in place of `*` when emitting an example wildcard table instance.
+const EXAMPLE_NAME: &str = "example";
+
+/// Generate a commented TOML template for the given schema.
+///
+/// Declared defaults are used as placeholder values; fields without a default
+/// get their `example`, their first allowed value, or else an empty value of
+/// their type. Wildcard tables such as `provider.*` are
+/// emitted once as `provider.example`.
+pub fn scaffold(kind: SchemaKind) -> String {
+    let schema = bundled_schema(kind);
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "# Harmony {} file generated from schema {}",
+        kind.name(),
+        schema_version(kind)
+    );
+    render(&schema, &mut out);
+    out
+}
+
+fn render(schema: &Schema, out: &mut String) {
+    for table in schema.fields.iter().filter(|rule| rule.is_table()) {
+        let active = table.required && !table.is_pattern();
+        let prefix = if active { "" } else { "# " };
+
+        out.push('\n');
+        if let Some(description) = &table.description {
+            let _ = writeln!(out, "# {description}");
+        }
+        let _ = writeln!(out, "{prefix}[{}]", instance_path(&table.path));
+
+        // The uncommented fields of this table, for evaluating conditions.
+        let mut emitted = Table::new();
+        for field in schema.fields.iter().filter(|rule| {
+            rule.table == table.path && !rule.is_table() && !is_container(schema, rule)
+        }) {
+            let required = active
+                && match &field.required_if {
+                    None => field.required,
+                    Some(condition) => field.default.is_none() && condition.holds(&emitted),
+                };
+            let prefix = if required { "" } else { "# " };
+            let value = field.default.clone().unwrap_or_else(|| placeholder(field));
+            if required {
+                insert(&mut emitted, field.relative_path(), value.clone());
+            }
+            let _ = write!(out, "{prefix}{} = {value}", field.relative_path());
+            if let Some(description) = &field.description {
+                let _ = write!(out, "  # {description}");
+            }
+            out.push('\n');
+        }
+    }
+}
+
+/// The smallest document of `kind` that passes [`validate`](crate::validate):
+/// the required tables and fields, each set to its `example`, else its
+/// default, else a placeholder as in [`scaffold`], with no optional fields
+/// and no comments besides a header line, which keeps a document with
+/// nothing required from being empty input. A `required_if` field is
+/// emitted when the values already emitted satisfy its condition. Wildcard
+/// tables have no required instances, so none are emitted.
+///
+/// Fails with [`ConfigError::Invalid`] if the document does not validate,
+/// which the crate's tests rule out for the bundled schemas.
+#[cfg(feature = "schema-config")]
+pub fn minimal_valid(kind: SchemaKind) -> Result<String, ConfigError> {
+    let schema = bundled_schema(kind);
+    let mut doc = Table::new();
+    let mut emitted: Vec<&str> = Vec::new();
+    for rule in &schema.fields {
+        let ancestors_emitted = schema.fields.iter().all(|parent| {
+            let is_ancestor = rule
+                .path
+                .strip_prefix(parent.path.as_str())
+                .is_some_and(|rest| rest.starts_with('.'));
+            !is_ancestor || emitted.contains(&parent.path.as_str())
+        });
+        if rule.path.contains('*') || rule.is_pattern() || !ancestors_emitted {
+            continue;
+        }
+        let required = match &rule.required_if {
+            None => rule.required,
+            Some(condition) => {
+                let instance = rule.table.split('.').try_fold(&doc, |table, segment| {
+                    table.get(segment).and_then(Value::as_table)
+                });
+                instance.is_some_and(|instance| condition.holds(instance))
+            }
+        };
+        if !required {
+            continue;
+        }
+        let value = match (&rule.example, &rule.default) {
+            (Some(example), _) => example.clone(),
+            (None, Some(default)) if rule.default_references().is_empty() => default.clone(),
+            _ => placeholder(rule),
+        };
+        insert(&mut doc, &rule.path, value);
+        emitted.push(&rule.path);
+    }
+
+    let out = format!(
+        "# Minimal Harmony {} file for schema {}\n{}",
+        kind.name(),
+        schema_version(kind),
+        toml::to_string(&doc).expect("tables serialize")
+    );
+    crate::validate(kind, &out).map_err(|errors| ConfigError::Invalid(errors.into()))?;
+    Ok(out)
+}
+
+/// Table fields whose keys are spelled out by their own child rules, and
+/// children of arrays, which the array's own line already describes.
+fn is_container(schema: &Schema, rule: &FieldRule) -> bool {
+    let is_under = |parent: &FieldRule, child: &FieldRule| {
+        child
+            .path
+            .strip_prefix(parent.path.as_str())
+            .is_some_and(|rest| rest.starts_with('.'))
+    };
+
+    let under_array = schema
+        .fields
+        .iter()
+        .any(|parent| parent.ty == FieldType::Array && is_under(parent, rule));
+    let has_children = schema.fields.iter().any(|child| is_under(rule, child));
+
+    under_array || (matches!(rule.ty, FieldType::Table { .. }) && has_children)
+}
+
+fn instance_path(path: &str) -> String {
+    path.split('.')
+        .map(|seg| if seg == "*" { EXAMPLE_NAME } else { seg })
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+/// Set the value at a dotted path, creating intermediate tables.
+fn insert(table: &mut Table, path: &str, value: Value) {
+    match path.split_once('.') {
+        Some((first, rest)) => {
+            if let Value::Table(child) = table
+                .entry(first)
+                .or_insert_with(|| Value::Table(Table::new()))
+            {
+                insert(child, rest, value);
+            }
+        }
+        None => {
+            table.insert(path.to_owned(), value);
+        }
+    }
+}
+
+/// The field's `example`, or else an empty value of its type (`0s` for
+/// durations, `0B` for byte sizes, unspecified addresses for address types)
+/// or its first allowed value. Strings
+/// with a `pattern` that rejects the empty string use [`EXAMPLE_NAME`] if
+/// the pattern accepts it.
+pub(crate) fn placeholder(field: &FieldRule) -> Value {
+    if let Some(example) = &field.example {
+        return example.clone();
+    }
+    let first_allowed = field
+        .allowed_values
+        .as_ref()
+        .and_then(|values| values.first().cloned());
+    let example = field
+        .pattern
+        .as_ref()
+        .filter(|pattern| !pattern.is_match("") && pattern.is_match(EXAMPLE_NAME))
+        .map(|_| EXAMPLE_NAME.to_owned());
+    match field.ty {
+        FieldType::String => Value::String(first_allowed.or(example).unwrap_or_default()),
+        FieldType::Integer => Value::Integer(0),
+        FieldType::Float => Value::Float(0.0),
+        FieldType::Boolean => Value::Boolean(false),
+        FieldType::Array => Value::Array(Vec::new()),
+        FieldType::Duration => Value::String("0s".to_owned()),
+        FieldType::ByteSize => Value::String("0B".to_owned()),
+        FieldType::IpAddr => Value::String("0.0.0.0".to_owned()),
+        FieldType::SocketAddr => Value::String("127.0.0.1:0".to_owned()),
+        FieldType::Cidr => Value::String("0.0.0.0/0".to_owned()),
+        FieldType::Email => Value::String("admin@example.com".to_owned()),
+        FieldType::Url => Value::String("https://example.com".to_owned()),
+        FieldType::Table { .. } => Value::Table(Default::default()),
+    }
+}
+
+#[cfg(all(
+    test,
+    any(
+        feature = "schema-config",
+        feature = "schema-pipeline",
+        feature = "schema-mesh",
+        feature = "schema-remote-ingress"
+    )
+))]
+mod tests {
+    use proptest::prelude::*;
+    use proptest::string::string_regex;
+
+    use super::*;
+    use crate::visit::{walk, ConfigVisitor};
+
+    #[test]
+    fn every_scaffold_is_valid_toml() {
+        for kind in SchemaKind::all() {
+            let out = scaffold(*kind);
+            assert!(
+                toml::from_str::<toml::Table>(&out).is_ok(),
+                "{}",
+                kind.name()
+            );
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "schema-config")]
+    fn required_tables_are_active_and_optional_ones_commented() {
+        let out = scaffold(SchemaKind::Config);
+        assert!(out.contains("\n[proxy]\n"));
+        assert!(out.contains("\n# [logging]\n"));
+    }
+
+    #[test]
+    #[cfg(feature = "schema-config")]
+    fn defaults_are_used_as_placeholders() {
+        let out = scaffold(SchemaKind::Config);
+        assert!(out.contains("# log_level = \"error\"  # Logging verbosity level\n"));
+        assert!(out.contains("# pipelines_path = \"pipelines\""));
+        assert!(out.contains("# content_limits.max_body_size = 10485760"));
+    }
+
+    #[test]
+    #[cfg(all(feature = "schema-config", feature = "schema-pipeline"))]
+    fn wildcard_tables_emit_one_example_instance() {
+        let out = scaffold(SchemaKind::Config);
+        assert!(out.contains("# [provider.example]\n"));
+        assert!(!out.contains("provider.*"));
+
+        let out = scaffold(SchemaKind::Pipeline);
+        assert!(out.contains("# [pipelines.example.mesh.ingress.example]\n"));
+    }
+
+    #[test]
+    #[cfg(feature = "schema-config")]
+    fn config_scaffold_validates() {
+        let out = scaffold(SchemaKind::Config);
+        assert_eq!(crate::validate_config(&out), Ok(()));
+        // `id`, filled in from its `example`, satisfies `name`'s
+        // `required_if = "id not exists"`.
+        assert!(out.contains("\nid = \"edge-gw-01\"  # "));
+        assert!(out.contains("\n# name = \"\"  # "));
+    }
+
+    /// The scaffold's uncommented values, which a user would fill in.
+    #[derive(Default)]
+    struct Placeholders(Vec<(String, FieldRule)>);
+
+    impl ConfigVisitor for Placeholders {
+        fn visit_field(&mut self, path: &str, value: &Value, rule: Option<&FieldRule>) {
+            if let (Some(rule), false) = (rule, value.is_table()) {
+                self.0.push((path.to_owned(), rule.clone()));
+            }
+        }
+    }
+
+    /// A value `rule` accepts: one of its `values`, a number within its
+    /// bounds, a string matching its `pattern`, or else its placeholder.
+    fn accepted(rule: &FieldRule) -> BoxedStrategy<Value> {
+        if let Some(values) = rule.allowed_values.clone() {
+            return prop::sample::select(values).prop_map(Value::String).boxed();
+        }
+        match rule.ty {
+            // Unbounded integers stay small enough for the model's
+            // narrowest integer fields.
+            FieldType::Integer => (rule.min.unwrap_or(0)..=rule.max.unwrap_or(u16::MAX.into()))
+                .prop_map(Value::Integer)
+                .boxed(),
+            FieldType::Float => (0.0..1e6).prop_map(Value::Float).boxed(),
+            FieldType::Boolean => any::<bool>().prop_map(Value::Boolean).boxed(),
+            FieldType::Duration => (0u32..86_400)
+                .prop_map(|secs| Value::String(format!("{secs}s")))
+                .boxed(),
+            FieldType::String => {
+                let pattern = rule
+                    .pattern
+                    .as_ref()
+                    .map_or("[a-z][a-z0-9 _-]{0,15}", |pattern| {
+                        pattern
+                            .as_str()
+                            .trim_start_matches('^')
+                            .trim_end_matches('$')
+                    });
+                match string_regex(pattern) {
+                    Ok(strings) => strings.prop_map(Value::String).boxed(),
+                    Err(_) => Just(placeholder(rule)).boxed(),
+                }
+            }
+            _ => Just(placeholder(rule)).boxed(),
+        }
+    }
+
+    /// The scaffold of `kind` with every placeholder replaced by a random
+    /// value its rule accepts.
+    fn filled_scaffold(kind: SchemaKind) -> impl Strategy<Value = String> {
+        let out = scaffold(kind);
+        let mut placeholders = Placeholders::default();
+        walk(kind, &out, &mut placeholders);
+        let doc: Table = toml::from_str(&out).expect("scaffolds are valid TOML");
+        let values: Vec<_> = placeholders
+            .0
+            .iter()
+            .map(|(_, rule)| accepted(rule))
+            .collect();
+        values.prop_map(move |values| {
+            let mut doc = doc.clone();
+            for ((path, _), value) in placeholders.0.iter().zip(values) {
+                insert(&mut doc, path, value);
+            }
+            // Keep the header comment, so scaffolds with nothing to fill in
+            // are not empty input.
+            let header = out.lines().next().unwrap_or_default();
+            format!(
+                "{header}\n{}",
+                toml::to_string(&doc).expect("tables serialize")
+            )
+        })
+    }
+
+    proptest! {
+        #[test]
+        fn filled_scaffolds_validate_and_parse(
+            (kind, doc) in prop::sample::select(SchemaKind::all())
+                .prop_flat_map(|kind| (Just(kind), filled_scaffold(kind)))
+        ) {
+            prop_assert_eq!(crate::validate(kind, &doc), Ok(()), "{}", doc);
+            #[cfg(feature = "schema-config")]
+            if kind == SchemaKind::Config {
+                let parsed = crate::parse_config(&doc);
+                prop_assert!(parsed.is_ok(), "{:?}\n{}", parsed, doc);
+            }
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "schema-config")]
+    fn minimal_documents_validate_with_only_required_fields() {
+        for kind in SchemaKind::all() {
+            let out = minimal_valid(*kind).unwrap();
+            let doc: Table = toml::from_str(&out).unwrap();
+            let schema = bundled_schema(*kind);
+            let mut placeholders = Placeholders::default();
+            walk(*kind, &out, &mut placeholders);
+            for (path, rule) in &placeholders.0 {
+                let required = rule.required || rule.required_if.is_some();
+                assert!(required, "{}: optional `{path}` emitted", kind.name());
+            }
+            assert!(doc
+                .keys()
+                .all(|key| schema.field(key).is_some_and(|rule| rule.required)));
+        }
+        let config = minimal_valid(SchemaKind::Config).unwrap();
+        assert_eq!(
+            config.split_once('\n').unwrap().1,
+            "[proxy]\nid = \"edge-gw-01\"\n"
+        );
+    }
+
+    #[test]
+    fn required_fields_in_active_tables_are_uncommented() {
+        let schema = crate::parse_schema(
+            "[[table]]\nname = \"proxy\"\nrequired = true\n\n[[table.field]]\nname = \"id\"\ntype = \"string\"\nrequired = true\ndescription = \"Unique proxy identifier\"\n",
+        )
+        .unwrap();
+        let mut out = String::new();
+        render(&schema, &mut out);
+        assert!(out.contains("\nid = \"\"  # Unique proxy identifier\n"));
+    }
+}
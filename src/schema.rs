@@ -0,0 +1,2280 @@
+//! Typed representation of the schema DSL.
+//!
+//! [`parse_schema`] turns a schema file such as [`CONFIG_SCHEMA`](crate::CONFIG_SCHEMA)
+//! into a [`Schema`]: a flat list of [`FieldRule`]s, one per `[[table]]` and
+//! per `[[table.field]]`. Field paths are the table name joined with the field
+//! name, so the `bind_port` field of the `network.*` table becomes
+//! `network.*.tcp_config.bind_port`.
+//!
+//! A field may declare `ref = "common.<name>"` instead of listing its
+//! children; the fields of that definition in
+//! [`COMMON_SCHEMA`] are inlined beneath it, so the
+//! resulting [`Schema`] never contains unresolved refs.
+//!
+//! A table may also list `[[table.extension]]` blocks of fields that only
+//! apply to some of its instances, selected by a `when` condition written
+//! like `required_if`:
+//!
+//! ```toml
+//! [[table.extension]]
+//! when = "service == 'dicom'"
+//!
+//! [[table.extension.field]]
+//! name = "options.local_aet"
+//! required = true
+//! ```
+//!
+//! Their rules carry the condition in [`FieldRule::extension`].
+
+use alloc::borrow::Cow;
+use alloc::collections::BTreeMap;
+use core::fmt;
+#[cfg(feature = "std")]
+use std::sync::OnceLock;
+
+use regex_automata::meta::Regex;
+use semver::Version;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use toml::{Table, Value};
+
+use crate::prelude::*;
+use crate::validate::check_value;
+use crate::validators::validator;
+use crate::{SchemaKind, COMMON_SCHEMA};
+
+/// A parsed schema DSL document.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Schema {
+    /// The `[schema] version`, if the schema declares a valid one.
+    pub version: Option<Version>,
+    /// Every table and field rule, in declaration order.
+    pub fields: Vec<FieldRule>,
+    /// Named value sets from the `[enumerations]` table, such as the built-in
+    /// `middleware_types` of the pipeline schema.
+    pub enumerations: BTreeMap<String, Vec<String>>,
+    /// The `[protocol_compatibility]` table: for each endpoint protocol, the
+    /// backend protocols a pipeline may forward it to. Protocols without an
+    /// entry are unrestricted.
+    pub protocol_compatibility: BTreeMap<String, Vec<String>>,
+    /// The `[middleware_order]` table: for each middleware type, the types
+    /// it must precede when both are in one pipeline.
+    pub middleware_order: BTreeMap<String, Vec<String>>,
+    /// The `[middleware_versions]` table: for each middleware type, the
+    /// versions a middleware may pin. Types without an entry accept any.
+    /// The bundled schemas leave it empty, so only custom schemas that also
+    /// declare a middleware `version` field use it.
+    pub middleware_versions: BTreeMap<String, Vec<String>>,
+}
+
+/// A single rule declared by the schema: either a `[[table]]` or one of its fields.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldRule {
+    /// Dotted path of the rule, with `*` for wildcard segments (e.g. `provider.*.api`).
+    pub path: String,
+    /// Declared type of the value.
+    pub ty: FieldType,
+    /// Whether the schema marks the rule `required = true`.
+    pub required: bool,
+    /// Human-readable description from the schema.
+    pub description: Option<String>,
+    /// Default value applied when the field is omitted. A string default
+    /// may refer to other fields as `{proxy.id}`, filled in by
+    /// [`apply_defaults`](crate::apply_defaults) from the document.
+    pub default: Option<Value>,
+    /// A realistic value from `example = ...`, shown in templates and error
+    /// messages. Unlike `default` it is never applied.
+    pub example: Option<Value>,
+    /// Type of each element for `array` fields (`array_item_type`).
+    pub item_type: Option<FieldType>,
+    /// The only accepted values, from `values = [...]` (or its older spelling
+    /// `enum`). For arrays the constraint applies to each element.
+    pub allowed_values: Option<Vec<String>>,
+    /// Inclusive lower bound for `integer` fields (`min`).
+    pub min: Option<i64>,
+    /// Inclusive upper bound for `integer` fields (`max`).
+    pub max: Option<i64>,
+    /// The fewest elements an `array` field may hold (`min_items`).
+    pub min_items: Option<usize>,
+    /// The most elements an `array` field may hold (`max_items`).
+    pub max_items: Option<usize>,
+    /// Regular expression string values must match (`pattern = "..."`).
+    pub pattern: Option<Pattern>,
+    /// Name of the [named validator](crate::validators) that checks the
+    /// value (`validator = "dicom_ae_title"`), for constraints the other
+    /// directives cannot express.
+    pub validator: Option<String>,
+    /// Whether a string, or each string of an array, must have something
+    /// besides whitespace (`non_empty = true`).
+    pub non_empty: bool,
+    /// Whether the value is a credential, such as a password or private key,
+    /// that [`redact`](crate::redact()) masks (`secret = true`).
+    pub secret: bool,
+    /// Whether leaving the field to its default is worth an advisory
+    /// (`security_relevant = true`), because the default decides something
+    /// like authentication or transport security.
+    pub security_relevant: bool,
+    /// Whether a table accepts keys besides the ones the schema declares
+    /// under it (`additional_keys = true`), such as user-chosen labels.
+    /// Declared keys are still checked. Tables that declare no keys at all
+    /// are free-form either way.
+    pub additional_keys: bool,
+    /// The `required_if` condition, if the requirement depends on other
+    /// fields. When set, the field is required exactly when the condition
+    /// holds, whatever `required` says.
+    pub required_if: Option<Condition>,
+    /// Guidance from `deprecated = "..."` if the rule is still accepted but
+    /// slated for removal.
+    pub deprecation: Option<String>,
+    /// The path to use instead of a deprecated rule, from
+    /// `replaced_by = "..."`.
+    pub replacement: Option<String>,
+    /// The schema version that introduced the rule (`since = "1.11.0"`).
+    pub since: Option<Version>,
+    /// The schema version that removed the rule (`until = "2.0.0"`); it
+    /// applies to earlier versions only.
+    pub until: Option<Version>,
+    /// Keys of which a table must set exactly one, from `one_of = [...]`.
+    /// For arrays of tables the constraint applies to each element.
+    pub one_of: Option<Vec<String>>,
+    /// Keys a table must set all or none of, from `all_or_none = [...]`,
+    /// such as a username and its password. For arrays of tables the
+    /// constraint applies to each element.
+    pub all_or_none: Option<Vec<String>>,
+    /// For tables whose values are traffic weights, keyed by backend, how the
+    /// weights must add up (`weights = "percent"` or `"relative"`).
+    pub weights: Option<WeightSum>,
+    /// For fields of a `[[table.extension]]`, the extension's `when`
+    /// condition: the field only applies to instances of the declaring table
+    /// where it holds, e.g. endpoints whose `service` is `dicom`.
+    pub extension: Option<Condition>,
+    /// Name of the `[[table]]` that declares this rule. Equal to `path` for the
+    /// table rule itself.
+    pub table: String,
+}
+
+/// One node of a schema's [field tree](Schema::tree): a path segment, the
+/// rule declared for it if any, and the segments beneath it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldNode {
+    /// The segment, e.g. `tcp_config`, or `*` for the single node standing
+    /// for every instance of a wildcard table. Empty for the root.
+    pub name: String,
+    /// The rule for the path ending here. `None` for the root and for
+    /// tables only implied by the rules beneath them.
+    pub rule: Option<FieldRule>,
+    /// The nodes beneath this one, in schema order.
+    pub children: Vec<FieldNode>,
+}
+
+impl FieldNode {
+    /// The child named `name`, if there is one.
+    pub fn child(&self, name: &str) -> Option<&FieldNode> {
+        self.children.iter().find(|child| child.name == name)
+    }
+}
+
+/// How the weights of a [`FieldRule::weights`] table must add up. Either way,
+/// no weight may be negative.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WeightSum {
+    /// The weights are percentages and total exactly 100.
+    Percent,
+    /// The weights are shares of any positive total.
+    Relative,
+}
+
+/// A `required_if` condition, evaluated against the instance of the declaring
+/// `[[table]]` (e.g. one `network.*` table), with field paths relative to it.
+///
+/// The DSL accepts either an inline table or the older string shorthand:
+///
+/// ```toml
+/// required_if = { field = "backend", equals = "s3" }  # or "backend == 's3'"
+/// required_if = { field = "http3", exists = true }    # or "http3 exists"
+/// required_if = { field = "name", exists = false }    # or "name not exists"
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Condition {
+    /// `field` is set to `value`.
+    Equals { field: String, value: Value },
+    /// `field` is set.
+    Exists { field: String },
+    /// `field` is not set.
+    Missing { field: String },
+}
+
+impl Condition {
+    fn parse(path: &str, value: &Value) -> Result<Self, SchemaParseError> {
+        let invalid = || SchemaParseError::InvalidCondition {
+            path: path.to_owned(),
+            condition: value.to_string(),
+        };
+        let condition = match value {
+            Value::Table(table) => {
+                let field = str_key(table, "field").ok_or_else(invalid)?.to_owned();
+                match (table.get("equals"), table.get("exists")) {
+                    (Some(value), None) => Condition::Equals {
+                        field,
+                        value: value.clone(),
+                    },
+                    (None, Some(Value::Boolean(true))) => Condition::Exists { field },
+                    (None, Some(Value::Boolean(false))) => Condition::Missing { field },
+                    _ => return Err(invalid()),
+                }
+            }
+            Value::String(src) => {
+                if let Some(field) = src.strip_suffix(" not exists") {
+                    Condition::Missing {
+                        field: field.trim().to_owned(),
+                    }
+                } else if let Some(field) = src.strip_suffix(" exists") {
+                    Condition::Exists {
+                        field: field.trim().to_owned(),
+                    }
+                } else {
+                    let (field, literal) = src.split_once("==").ok_or_else(invalid)?;
+                    // The right-hand side is a TOML literal: `true`, `1`, `'s3'`.
+                    let value = toml::from_str::<Table>(&format!("v = {}", literal.trim()))
+                        .ok()
+                        .and_then(|mut table| table.remove("v"))
+                        .ok_or_else(invalid)?;
+                    Condition::Equals {
+                        field: field.trim().to_owned(),
+                        value,
+                    }
+                }
+            }
+            _ => return Err(invalid()),
+        };
+        Ok(condition)
+    }
+
+    /// The field the condition inspects, relative to the declaring table.
+    pub fn field(&self) -> &str {
+        match self {
+            Condition::Equals { field, .. }
+            | Condition::Exists { field }
+            | Condition::Missing { field } => field,
+        }
+    }
+
+    /// Whether the condition holds for `table`, an instance of the declaring
+    /// table.
+    pub fn holds(&self, table: &Table) -> bool {
+        let actual = lookup(table, self.field());
+        match self {
+            Condition::Equals { value, .. } => actual == Some(value),
+            Condition::Exists { .. } => actual.is_some(),
+            Condition::Missing { .. } => actual.is_none(),
+        }
+    }
+}
+
+impl fmt::Display for SchemaParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SchemaParseError::InvalidToml(message) => {
+                write!(f, "schema is not valid TOML: {message}")
+            }
+            SchemaParseError::MissingName { table: None } => {
+                f.write_str("a `[[table]]` entry has no `name`")
+            }
+            SchemaParseError::MissingName { table: Some(table) } => {
+                write!(f, "a field of table `{table}` has no `name`")
+            }
+            SchemaParseError::UnknownType { path, ty } => {
+                write!(f, "`{path}` has unknown type `{ty}`")
+            }
+            SchemaParseError::MissingVersion => {
+                f.write_str("the `[schema]` table has no `version`")
+            }
+            SchemaParseError::InvalidVersion(message) => {
+                write!(f, "schema version is not valid semver: {message}")
+            }
+            SchemaParseError::InvalidRuleVersion { path, version } => {
+                write!(
+                    f,
+                    "`{path}` has a version that is not valid semver: {version}"
+                )
+            }
+            SchemaParseError::InvalidWeights { path, weights } => write!(
+                f,
+                "`{path}` has weights `{weights}`; expected `percent` or `relative`"
+            ),
+            SchemaParseError::UnknownValidator { path, validator } => {
+                write!(f, "`{path}` names the unknown validator `{validator}`")
+            }
+            SchemaParseError::InvalidPattern { path, message } => {
+                write!(f, "`{path}` has an invalid pattern: {message}")
+            }
+            SchemaParseError::InvalidCondition { path, condition } => {
+                write!(
+                    f,
+                    "`{path}` has an invalid `required_if` condition: {condition}"
+                )
+            }
+            SchemaParseError::InvalidExtension {
+                table,
+                condition: Some(condition),
+            } => write!(
+                f,
+                "an extension of `{table}` has an invalid `when` condition: {condition}"
+            ),
+            SchemaParseError::InvalidExtension {
+                table,
+                condition: None,
+            } => write!(f, "an extension of `{table}` has no `when` condition"),
+            SchemaParseError::UnresolvedRef { path, reference } => {
+                write!(f, "`{path}` refers to `{reference}`, which is not defined")
+            }
+            SchemaParseError::CyclicRef { chain } => {
+                write!(f, "refs form a cycle: {}", chain.join(" -> "))
+            }
+            SchemaParseError::CyclicDefault { chain } => {
+                write!(f, "defaults refer to each other: {}", chain.join(" -> "))
+            }
+            SchemaParseError::InvalidBytes(message) => {
+                write!(f, "schema bytes are not usable: {message}")
+            }
+        }
+    }
+}
+
+impl core::error::Error for SchemaParseError {}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Condition::Equals { field, value } => write!(f, "`{field}` is {value}"),
+            Condition::Exists { field } => write!(f, "`{field}` is set"),
+            Condition::Missing { field } => write!(f, "`{field}` is not set"),
+        }
+    }
+}
+
+/// The value at a dotted path beneath `table`.
+pub(crate) fn lookup<'a>(table: &'a Table, path: &str) -> Option<&'a Value> {
+    let (first, rest) = match path.split_once('.') {
+        Some((first, rest)) => (first, Some(rest)),
+        None => (path, None),
+    };
+    let value = table.get(first)?;
+    match rest {
+        Some(rest) => lookup(value.as_table()?, rest),
+        None => Some(value),
+    }
+}
+
+/// A field's `pattern`, compiled once when the schema is parsed.
+#[derive(Debug, Clone)]
+pub struct Pattern {
+    source: String,
+    regex: Regex,
+}
+
+impl Pattern {
+    pub(crate) fn compile(path: &str, pattern: &str) -> Result<Self, SchemaParseError> {
+        Regex::new(pattern)
+            .map(|regex| Pattern {
+                source: pattern.to_owned(),
+                regex,
+            })
+            .map_err(|err| SchemaParseError::InvalidPattern {
+                path: path.to_owned(),
+                message: err.to_string(),
+            })
+    }
+
+    /// The pattern as written in the schema.
+    pub fn as_str(&self) -> &str {
+        &self.source
+    }
+
+    /// Whether `value` matches the pattern.
+    pub fn is_match(&self, value: &str) -> bool {
+        self.regex.is_match(value)
+    }
+}
+
+impl PartialEq for Pattern {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+/// The value types understood by the schema DSL.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FieldType {
+    String,
+    Integer,
+    Float,
+    Boolean,
+    Array,
+    /// A string holding a duration such as `30s` or `1h 30m`: one or more
+    /// amounts, each followed by a unit from `ns` up to `w` (weeks).
+    Duration,
+    /// A string holding a size such as `10MB` or `64 KiB`: an amount followed
+    /// by an optional decimal (`KB`) or binary (`KiB`) unit, bytes if omitted.
+    ByteSize,
+    /// A string holding an IPv4 or IPv6 address, e.g. `10.0.0.1` or `fd00::1`.
+    IpAddr,
+    /// A string holding a `host:port` address, where the host is an IP
+    /// address (IPv6 in brackets) or a DNS name, e.g. `vpn.example.com:51820`.
+    SocketAddr,
+    /// A string holding an address range in CIDR notation, e.g. `10.0.0.0/8`.
+    Cidr,
+    /// A string holding an email address, e.g. `ops@example.com`.
+    Email,
+    /// A string holding an absolute `http` or `https` URL, e.g.
+    /// `https://hooks.example.com/notify`.
+    Url,
+    /// A table. `pattern` is set for wildcard tables such as `provider.*`
+    /// whose last segment matches any user-chosen name.
+    Table {
+        pattern: bool,
+    },
+}
+
+/// Reasons a schema document could not be parsed.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum SchemaParseError {
+    /// The schema is not valid TOML.
+    InvalidToml(String),
+    /// A `[[table]]` or `[[table.field]]` entry has no `name`.
+    MissingName {
+        /// The table the nameless entry belongs to, if known.
+        table: Option<String>,
+    },
+    /// A field declares a `type` the DSL does not know.
+    UnknownType {
+        /// Path of the offending rule.
+        path: String,
+        /// The unrecognised type name.
+        ty: String,
+    },
+    /// The `[schema]` table has no `version` key.
+    MissingVersion,
+    /// The `[schema]` version is not valid semver.
+    InvalidVersion(String),
+    /// A rule's `since` or `until` is not valid semver.
+    InvalidRuleVersion {
+        /// Path of the offending rule.
+        path: String,
+        /// The version as written.
+        version: String,
+    },
+    /// A field's `pattern` is not a valid regular expression.
+    InvalidPattern {
+        /// Path of the offending rule.
+        path: String,
+        /// Why the pattern failed to compile.
+        message: String,
+    },
+    /// A field's `required_if` is neither a `{ field, equals }` /
+    /// `{ field, exists }` table nor a recognised shorthand string.
+    InvalidCondition {
+        /// Path of the offending rule.
+        path: String,
+        /// The condition as written.
+        condition: String,
+    },
+    /// A `[[table.extension]]` has no `when` condition, or one that does not
+    /// parse.
+    InvalidExtension {
+        /// The table the extension belongs to.
+        table: String,
+        /// The condition as written, if there is one.
+        condition: Option<String>,
+    },
+    /// A field's `ref` names a definition that does not exist.
+    UnresolvedRef {
+        /// Path of the referencing rule.
+        path: String,
+        /// The unresolved reference (e.g. `common.endpoint`).
+        reference: String,
+    },
+    /// Expanding refs led back to a ref that was already being expanded.
+    CyclicRef {
+        /// The refs involved, ending with the one that closes the cycle.
+        chain: Vec<String>,
+    },
+    /// Templated defaults refer to each other in a cycle, so none of them
+    /// can be filled in.
+    CyclicDefault {
+        /// The paths of the fields involved, ending with the one that
+        /// closes the cycle.
+        chain: Vec<String>,
+    },
+    /// A rule's `weights` is neither `"percent"` nor `"relative"`.
+    InvalidWeights {
+        /// Path of the offending rule.
+        path: String,
+        /// The value as written.
+        weights: String,
+    },
+    /// A field's `validator` names no [registered validator](crate::validators).
+    UnknownValidator {
+        /// Path of the offending rule.
+        path: String,
+        /// The name as written.
+        validator: String,
+    },
+    /// The input to [`Schema::from_bytes`] is not a schema encoded by this
+    /// version of the crate.
+    InvalidBytes(String),
+}
+
+impl FieldType {
+    /// The DSL spelling of the type (`"string"`, `"table"`, ...).
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FieldType::String => "string",
+            FieldType::Integer => "integer",
+            FieldType::Float => "float",
+            FieldType::Boolean => "boolean",
+            FieldType::Array => "array",
+            FieldType::Duration => "duration",
+            FieldType::ByteSize => "byte_size",
+            FieldType::IpAddr => "ip_addr",
+            FieldType::SocketAddr => "socket_addr",
+            FieldType::Cidr => "cidr",
+            FieldType::Email => "email",
+            FieldType::Url => "url",
+            FieldType::Table { .. } => "table",
+        }
+    }
+
+    fn parse(name: &str) -> Option<Self> {
+        Some(match name {
+            "string" => FieldType::String,
+            "integer" => FieldType::Integer,
+            "float" => FieldType::Float,
+            "boolean" => FieldType::Boolean,
+            "array" => FieldType::Array,
+            "duration" => FieldType::Duration,
+            "byte_size" => FieldType::ByteSize,
+            "ip_addr" => FieldType::IpAddr,
+            "socket_addr" => FieldType::SocketAddr,
+            "cidr" => FieldType::Cidr,
+            "email" => FieldType::Email,
+            "url" => FieldType::Url,
+            "table" => FieldType::Table { pattern: false },
+            _ => return None,
+        })
+    }
+
+    /// Whether `value` is of this type. Integers are accepted where floats are
+    /// expected, since `1` reads naturally as `1.0`. Durations, byte sizes,
+    /// addresses and URLs are strings; whether they parse is checked
+    /// separately.
+    pub fn matches(&self, value: &Value) -> bool {
+        match self {
+            FieldType::String
+            | FieldType::Duration
+            | FieldType::ByteSize
+            | FieldType::IpAddr
+            | FieldType::SocketAddr
+            | FieldType::Cidr
+            | FieldType::Email
+            | FieldType::Url => value.is_str(),
+            FieldType::Integer => value.is_integer(),
+            FieldType::Float => value.is_float() || value.is_integer(),
+            FieldType::Boolean => value.is_bool(),
+            FieldType::Array => value.is_array(),
+            FieldType::Table { .. } => value.is_table(),
+        }
+    }
+}
+
+impl FieldRule {
+    /// The dot-separated segments of [`path`](Self::path).
+    pub fn segments(&self) -> impl Iterator<Item = &str> {
+        self.path.split('.')
+    }
+
+    /// The last segment of the path, i.e. the key this rule describes.
+    pub fn name(&self) -> &str {
+        self.path.rsplit('.').next().unwrap_or(&self.path)
+    }
+
+    /// Whether this rule is a `[[table]]` entry rather than one of its fields.
+    pub fn is_table(&self) -> bool {
+        self.path == self.table
+    }
+
+    /// The path relative to the declaring table (e.g. `tcp_config.bind_port`).
+    pub fn relative_path(&self) -> &str {
+        self.path
+            .strip_prefix(self.table.as_str())
+            .and_then(|rest| rest.strip_prefix('.'))
+            .unwrap_or(&self.path)
+    }
+
+    /// The fields a templated string `default` refers to, such as
+    /// `proxy.id` for `"{proxy.id}.log"`. `${NAME}` environment placeholders
+    /// are not references; they are left for the runtime to expand.
+    pub fn default_references(&self) -> Vec<&str> {
+        let mut references = Vec::new();
+        if let Some(Value::String(template)) = &self.default {
+            expand_references(template, |path| {
+                references.push(path);
+                Some(String::new())
+            });
+        }
+        references
+    }
+
+    /// Whether this rule is a wildcard table such as `provider.*`.
+    pub fn is_pattern(&self) -> bool {
+        matches!(self.ty, FieldType::Table { pattern: true })
+    }
+
+    pub(crate) fn depth(&self) -> usize {
+        self.segments().count()
+    }
+
+    /// Whether the first `segments.len()` segments of this rule match `segments`.
+    pub(crate) fn matches_prefix<S: AsRef<str>>(&self, segments: &[S]) -> bool {
+        let mut own = self.segments();
+        segments.iter().all(|seg| match own.next() {
+            Some(rule) => rule == "*" || rule == seg.as_ref(),
+            None => false,
+        })
+    }
+
+    pub(crate) fn matches<S: AsRef<str>>(&self, segments: &[S]) -> bool {
+        self.depth() == segments.len() && self.matches_prefix(segments)
+    }
+
+    fn wildcards(&self) -> usize {
+        self.segments().filter(|seg| *seg == "*").count()
+    }
+}
+
+impl Schema {
+    /// Look up the rule describing a concrete field path such as
+    /// `provider.runbeam.api`, resolving wildcard segments. Array indices
+    /// (`rules[0]` or `rules.0`) are skipped, since every element of an array
+    /// shares one rule.
+    pub fn field(&self, path: &str) -> Option<&FieldRule> {
+        let segments: Vec<&str> = path
+            .split('.')
+            .map(|seg| seg.split('[').next().unwrap_or(seg))
+            .filter(|seg| !seg.is_empty() && !seg.bytes().all(|b| b.is_ascii_digit()))
+            .collect();
+        self.find(&segments)
+    }
+
+    /// Every table and field rule, sorted by path, so e.g. `network.*` comes
+    /// just before the fields declared under it.
+    pub fn field_paths(&self) -> impl Iterator<Item = &FieldRule> {
+        let mut rules: Vec<&FieldRule> = self.fields.iter().collect();
+        rules.sort_by(|a, b| a.path.cmp(&b.path));
+        rules.into_iter()
+    }
+
+    /// The rules at or beneath the table path `prefix`, e.g. `storage` and
+    /// `storage.backend` for `storage` but not `storage_path`, in schema
+    /// order. Paths are compared as written, so `network.*` is under
+    /// `network` but not under `network.default`.
+    pub fn fields_under(&self, prefix: &str) -> Vec<&FieldRule> {
+        self.fields
+            .iter()
+            .filter(|rule| {
+                prefix.is_empty()
+                    || rule
+                        .path
+                        .strip_prefix(prefix)
+                        .is_some_and(|rest| rest.is_empty() || rest.starts_with('.'))
+            })
+            .collect()
+    }
+
+    /// The paths of the fields an instance of `service_type` must set, for
+    /// the tables that declare a `service` field (e.g. `endpoints.*`): their
+    /// required fields, plus those of every `[[table.extension]]` whose
+    /// `when` is `service == '<service_type>'`. Conditionally required fields
+    /// are included, as by [`required_fields`]; the paths keep their
+    /// wildcards and are in schema order.
+    pub fn required_for_service_type(&self, service_type: &str) -> Vec<String> {
+        let typed: Vec<&str> = self
+            .fields
+            .iter()
+            .filter(|rule| rule.relative_path() == "service" && !rule.is_table())
+            .map(|rule| rule.table.as_str())
+            .collect();
+        self.fields
+            .iter()
+            .filter(|rule| {
+                !rule.is_table()
+                    && (rule.required || rule.required_if.is_some())
+                    && typed.contains(&rule.table.as_str())
+            })
+            .filter(|rule| match &rule.extension {
+                None => true,
+                Some(Condition::Equals { field, value }) => {
+                    field == "service" && value.as_str() == Some(service_type)
+                }
+                Some(_) => false,
+            })
+            .map(|rule| rule.path.clone())
+            .collect()
+    }
+
+    /// The rules with templated defaults, each after the ones whose fields it
+    /// refers to, or the chain of paths of a cycle between them.
+    pub(crate) fn templated_defaults(&self) -> Result<Vec<&FieldRule>, Vec<String>> {
+        fn visit<'s>(
+            schema: &'s Schema,
+            rule: &'s FieldRule,
+            stack: &mut Vec<&'s str>,
+            order: &mut Vec<&'s FieldRule>,
+        ) -> Result<(), Vec<String>> {
+            if order.iter().any(|done| done.path == rule.path) {
+                return Ok(());
+            }
+            if stack.contains(&rule.path.as_str()) {
+                let mut chain: Vec<String> = stack.iter().map(|path| (*path).to_owned()).collect();
+                chain.push(rule.path.clone());
+                return Err(chain);
+            }
+            stack.push(&rule.path);
+            for reference in rule.default_references() {
+                let segments: Vec<&str> = reference.split('.').collect();
+                if let Some(target) = schema.find(&segments) {
+                    visit(schema, target, stack, order)?;
+                }
+            }
+            stack.pop();
+            if !rule.default_references().is_empty() {
+                order.push(rule);
+            }
+            Ok(())
+        }
+
+        let mut order = Vec::new();
+        for rule in &self.fields {
+            visit(self, rule, &mut Vec::new(), &mut order)?;
+        }
+        Ok(order)
+    }
+
+    /// The rules as a tree following their dotted paths, for rendering, e.g.
+    /// as a settings UI. The root node stands for the whole document.
+    pub fn tree(&self) -> FieldNode {
+        let mut root = FieldNode {
+            name: String::new(),
+            rule: None,
+            children: Vec::new(),
+        };
+        for rule in &self.fields {
+            let mut node = &mut root;
+            for segment in rule.segments() {
+                let index = match node.children.iter().position(|child| child.name == segment) {
+                    Some(index) => index,
+                    None => {
+                        node.children.push(FieldNode {
+                            name: segment.to_owned(),
+                            rule: None,
+                            children: Vec::new(),
+                        });
+                        node.children.len() - 1
+                    }
+                };
+                node = &mut node.children[index];
+            }
+            node.rule.get_or_insert_with(|| rule.clone());
+        }
+        root
+    }
+
+    /// Find the rule for a concrete path, preferring literal segments over wildcards.
+    pub(crate) fn find<S: AsRef<str>>(&self, segments: &[S]) -> Option<&FieldRule> {
+        self.fields
+            .iter()
+            .filter(|rule| rule.matches(segments))
+            .min_by_key(|rule| rule.wildcards())
+    }
+
+    /// Whether some rule lives underneath `segments`, making it an implicit table
+    /// (e.g. `network` for the `network.*` rule).
+    pub(crate) fn is_ancestor<S: AsRef<str>>(&self, segments: &[S]) -> bool {
+        self.fields
+            .iter()
+            .any(|rule| rule.depth() > segments.len() && rule.matches_prefix(segments))
+    }
+
+    /// The keys the schema declares directly under the table at `segments`,
+    /// including implicit tables such as `network` for `network.*`.
+    pub(crate) fn known_keys<S: AsRef<str>>(&self, segments: &[S]) -> Vec<&str> {
+        let mut keys: Vec<&str> = self
+            .fields
+            .iter()
+            .filter(|rule| rule.depth() > segments.len() && rule.matches_prefix(segments))
+            .filter_map(|rule| rule.segments().nth(segments.len()))
+            .filter(|key| *key != "*")
+            .collect();
+        keys.sort_unstable();
+        keys.dedup();
+        keys
+    }
+
+    /// Rules that describe direct children of the table at `segments`.
+    pub(crate) fn children<'a, S: AsRef<str>>(
+        &'a self,
+        segments: &'a [S],
+    ) -> impl Iterator<Item = &'a FieldRule> + 'a {
+        self.fields
+            .iter()
+            .filter(move |rule| rule.depth() == segments.len() + 1 && rule.matches_prefix(segments))
+    }
+}
+
+/// Parse a schema DSL document into a [`Schema`].
+///
+/// Fields declaring `ref = "common.<name>"` are expanded in place into the
+/// fields of the named definition in [`COMMON_SCHEMA`]. Malformed schemas
+/// are reported as a [`SchemaParseError`], never a panic.
+pub fn parse_schema(src: &str) -> Result<Schema, SchemaParseError> {
+    parse_schema_with(src, COMMON_SCHEMA)
+}
+
+/// [`parse_schema`] with refs resolved against `common_src` instead of the
+/// bundled common schema.
+fn parse_schema_with(src: &str, common_src: &str) -> Result<Schema, SchemaParseError> {
+    let doc = parse_toml(src)?;
+    let common = parse_toml(common_src)?;
+    let mut fields = Vec::new();
+
+    for table in tables(&doc) {
+        let table_name =
+            str_key(table, "name").ok_or(SchemaParseError::MissingName { table: None })?;
+        fields.push(FieldRule {
+            path: table_name.to_owned(),
+            ty: FieldType::Table {
+                pattern: bool_key(table, "pattern") || table_name.ends_with(".*"),
+            },
+            required: bool_key(table, "required"),
+            description: str_key(table, "description").map(str::to_owned),
+            default: None,
+            example: None,
+            item_type: None,
+            allowed_values: None,
+            min: None,
+            max: None,
+            min_items: None,
+            max_items: None,
+            non_empty: false,
+            secret: false,
+            security_relevant: false,
+            additional_keys: bool_key(table, "additional_keys"),
+            pattern: None,
+            validator: None,
+            required_if: None,
+            deprecation: str_key(table, "deprecated").map(str::to_owned),
+            replacement: str_key(table, "replaced_by").map(str::to_owned),
+            since: version_key(table, "since", table_name)?,
+            until: version_key(table, "until", table_name)?,
+            one_of: string_list(table, "one_of"),
+            all_or_none: string_list(table, "all_or_none"),
+            weights: weights_key(table, table_name)?,
+            extension: None,
+            table: table_name.to_owned(),
+        });
+
+        let resolver = Resolver {
+            common: &common,
+            table: table_name,
+        };
+        resolver.push_fields(table, table_name, &mut Vec::new(), &mut fields)?;
+
+        for extension in entries(table, "extension") {
+            let invalid = |condition| SchemaParseError::InvalidExtension {
+                table: table_name.to_owned(),
+                condition,
+            };
+            let when = extension.get("when").ok_or_else(|| invalid(None))?;
+            let when =
+                Condition::parse(table_name, when).map_err(|_| invalid(Some(when.to_string())))?;
+            let start = fields.len();
+            resolver.push_fields(extension, table_name, &mut Vec::new(), &mut fields)?;
+            for rule in &mut fields[start..] {
+                rule.extension = Some(when.clone());
+            }
+        }
+    }
+
+    let schema = Schema {
+        version: parse_version(src).ok(),
+        fields,
+        enumerations: string_lists(&doc, "enumerations"),
+        protocol_compatibility: string_lists(&doc, "protocol_compatibility"),
+        middleware_order: string_lists(&doc, "middleware_order"),
+        middleware_versions: string_lists(&doc, "middleware_versions"),
+    };
+    schema
+        .templated_defaults()
+        .map_err(|chain| SchemaParseError::CyclicDefault { chain })?;
+    Ok(schema)
+}
+
+/// `template` with each `{path}` reference replaced by `lookup(path)`, or
+/// `None` if a lookup fails. `${NAME}` placeholders and braces around
+/// anything but a dotted path are kept as written.
+pub(crate) fn expand_references<'t>(
+    template: &'t str,
+    mut lookup: impl FnMut(&'t str) -> Option<String>,
+) -> Option<String> {
+    let mut out = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        let (before, after) = rest.split_at(start);
+        out.push_str(before);
+        let reference = after[1..].find('}').map(|end| &after[1..=end]);
+        match reference {
+            Some(path)
+                if !before.ends_with('$')
+                    && !path.is_empty()
+                    && path.split('.').all(|segment| {
+                        !segment.is_empty()
+                            && segment
+                                .chars()
+                                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+                    }) =>
+            {
+                out.push_str(&lookup(path)?);
+                rest = &after[path.len() + 2..];
+            }
+            _ => {
+                out.push('{');
+                rest = &after[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    Some(out)
+}
+
+/// The string arrays of the top-level table `key`, such as `[enumerations]`.
+fn string_lists(doc: &Table, key: &str) -> BTreeMap<String, Vec<String>> {
+    doc.get(key)
+        .and_then(Value::as_table)
+        .into_iter()
+        .flatten()
+        .filter_map(|(name, values)| {
+            let values = values.as_array()?.iter().filter_map(Value::as_str);
+            Some((name.clone(), values.map(str::to_owned).collect()))
+        })
+        .collect()
+}
+
+/// Expands the `[[table.field]]` entries of one table, inlining refs.
+struct Resolver<'a> {
+    common: &'a Table,
+    table: &'a str,
+}
+
+impl Resolver<'_> {
+    /// Push a rule for every field of `table`, prefixing paths with `prefix`.
+    /// `stack` holds the refs being expanded, to reject cycles.
+    fn push_fields(
+        &self,
+        table: &Table,
+        prefix: &str,
+        stack: &mut Vec<String>,
+        fields: &mut Vec<FieldRule>,
+    ) -> Result<(), SchemaParseError> {
+        let table_fields = table.get("field").and_then(Value::as_array);
+        for field in table_fields
+            .into_iter()
+            .flatten()
+            .filter_map(Value::as_table)
+        {
+            let name = str_key(field, "name").ok_or_else(|| SchemaParseError::MissingName {
+                table: Some(prefix.to_owned()),
+            })?;
+            let path = format!("{prefix}.{name}");
+            let reference = str_key(field, "ref");
+            let definition = reference
+                .map(|reference| self.definition(&path, reference, stack))
+                .transpose()?;
+
+            let default_type = if reference.is_some() {
+                "table"
+            } else {
+                "string"
+            };
+            let ty = parse_type(&path, str_key(field, "type").unwrap_or(default_type))?;
+            let item_type = str_key(field, "array_item_type")
+                .map(|item| parse_type(&path, item))
+                .transpose()?;
+            let description = str_key(field, "description")
+                .or_else(|| definition.and_then(|def| str_key(def, "description")));
+
+            fields.push(FieldRule {
+                ty,
+                required: bool_key(field, "required"),
+                description: description.map(str::to_owned),
+                default: field.get("default").cloned(),
+                example: field.get("example").cloned(),
+                item_type,
+                allowed_values: string_list(field, "values").or_else(|| string_list(field, "enum")),
+                min: field.get("min").and_then(Value::as_integer),
+                max: field.get("max").and_then(Value::as_integer),
+                min_items: count_key(field, "min_items"),
+                max_items: count_key(field, "max_items"),
+                pattern: str_key(field, "pattern")
+                    .map(|pattern| Pattern::compile(&path, pattern))
+                    .transpose()?,
+                validator: validator_key(field, &path)?,
+                non_empty: bool_key(field, "non_empty"),
+                secret: bool_key(field, "secret"),
+                security_relevant: bool_key(field, "security_relevant"),
+                additional_keys: bool_key(field, "additional_keys"),
+                required_if: field
+                    .get("required_if")
+                    .map(|condition| Condition::parse(&path, condition))
+                    .transpose()?,
+                deprecation: str_key(field, "deprecated").map(str::to_owned),
+                replacement: str_key(field, "replaced_by").map(str::to_owned),
+                since: version_key(field, "since", &path)?,
+                until: version_key(field, "until", &path)?,
+                one_of: string_list(field, "one_of"),
+                all_or_none: string_list(field, "all_or_none"),
+                weights: weights_key(field, &path)?,
+                extension: None,
+                table: self.table.to_owned(),
+                path: path.clone(),
+            });
+
+            if let (Some(reference), Some(definition)) = (reference, definition) {
+                stack.push(reference.to_owned());
+                self.push_fields(definition, &path, stack, fields)?;
+                stack.pop();
+            }
+        }
+        Ok(())
+    }
+
+    /// Look up the definition a `ref` names, rejecting refs already being expanded.
+    fn definition(
+        &self,
+        path: &str,
+        reference: &str,
+        stack: &[String],
+    ) -> Result<&Table, SchemaParseError> {
+        if stack.iter().any(|seen| seen == reference) {
+            let mut chain = stack.to_vec();
+            chain.push(reference.to_owned());
+            return Err(SchemaParseError::CyclicRef { chain });
+        }
+        reference
+            .strip_prefix("common.")
+            .and_then(|name| tables(self.common).find(|def| str_key(def, "name") == Some(name)))
+            .ok_or_else(|| SchemaParseError::UnresolvedRef {
+                path: path.to_owned(),
+                reference: reference.to_owned(),
+            })
+    }
+}
+
+fn tables(doc: &Table) -> impl Iterator<Item = &Table> {
+    entries(doc, "table")
+}
+
+/// The entries of the array of tables `key`, such as `[[table.extension]]`.
+fn entries<'a>(table: &'a Table, key: &str) -> impl Iterator<Item = &'a Table> {
+    let entries = table.get(key).and_then(Value::as_array);
+    entries.into_iter().flatten().filter_map(Value::as_table)
+}
+
+fn parse_toml(src: &str) -> Result<Table, SchemaParseError> {
+    toml::from_str(src).map_err(|err| SchemaParseError::InvalidToml(err.message().to_owned()))
+}
+
+/// The [field tree](Schema::tree) of a bundled schema.
+pub fn field_tree(kind: SchemaKind) -> FieldNode {
+    bundled_schema(kind).tree()
+}
+
+/// Describe a concrete field path in a bundled schema, e.g.
+/// `describe_field(SchemaKind::Config, "provider.runbeam.api")`.
+///
+/// Returns `None` when the schema has no rule for the path.
+pub fn describe_field(kind: SchemaKind, path: &str) -> Option<FieldRule> {
+    bundled_schema(kind).field(path).cloned()
+}
+
+/// The dotted paths of every required field in a bundled schema, in
+/// declaration order. Conditionally required fields (those with a
+/// `required_if`) are included; tables are not. Wildcard segments keep their
+/// pattern, e.g. `network.*.tcp_config.bind_port`.
+pub fn required_fields(kind: SchemaKind) -> Vec<String> {
+    bundled_schema(kind)
+        .fields
+        .iter()
+        .filter(|rule| !rule.is_table() && (rule.required || rule.required_if.is_some()))
+        .map(|rule| rule.path.clone())
+        .collect()
+}
+
+/// Every field of a bundled schema limited to a list of values, with the
+/// values in the order the schema gives them, e.g. to offer as a dropdown.
+/// Fields are in declaration order; for arrays the values apply to each
+/// element. Wildcard segments keep their pattern.
+pub fn enum_fields(kind: SchemaKind) -> Vec<(String, Vec<String>)> {
+    bundled_schema(kind)
+        .fields
+        .iter()
+        .filter_map(|rule| Some((rule.path.clone(), rule.allowed_values.clone()?)))
+        .collect()
+}
+
+/// The dotted path of every table and field in a bundled schema, sorted.
+/// Wildcard segments keep their pattern, e.g. `network.*.http3.bind_port`.
+pub fn all_field_paths(kind: SchemaKind) -> Vec<String> {
+    let schema = bundled_schema(kind);
+    let mut paths: Vec<String> = schema.field_paths().map(|rule| rule.path.clone()).collect();
+    paths.dedup();
+    paths
+}
+
+/// Counts of what a bundled schema declares, from [`schema_summary`].
+///
+/// Formats on one line, e.g. `config schema v1.11.0: 121 fields (28
+/// required, 1 deprecated), 10 wildcard tables`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchemaSummary {
+    /// The schema summarised.
+    pub kind: SchemaKind,
+    /// Its [`schema_version`].
+    pub version: Version,
+    /// Fields declared, not counting tables.
+    pub fields: usize,
+    /// Fields that are required, conditionally or not, as listed by
+    /// [`required_fields`].
+    pub required: usize,
+    /// Tables and fields marked `deprecated`.
+    pub deprecated: usize,
+    /// Wildcard tables such as `network.*`.
+    pub wildcard_tables: usize,
+}
+
+impl fmt::Display for SchemaSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} schema v{}: {} fields ({} required, {} deprecated), {} wildcard tables",
+            self.kind.name(),
+            self.version,
+            self.fields,
+            self.required,
+            self.deprecated,
+            self.wildcard_tables
+        )
+    }
+}
+
+/// Count the fields, required and deprecated fields, and wildcard tables of
+/// a bundled schema, e.g. for a status endpoint reporting what a proxy
+/// validates against.
+pub fn schema_summary(kind: SchemaKind) -> SchemaSummary {
+    let schema = bundled_schema(kind);
+    let fields = || schema.fields.iter().filter(|rule| !rule.is_table());
+    SchemaSummary {
+        kind,
+        version: schema_version(kind),
+        fields: fields().count(),
+        required: fields()
+            .filter(|rule| rule.required || rule.required_if.is_some())
+            .count(),
+        deprecated: schema
+            .fields
+            .iter()
+            .filter(|rule| rule.deprecation.is_some())
+            .count(),
+        wildcard_tables: schema
+            .fields
+            .iter()
+            .filter(|rule| rule.is_pattern())
+            .count(),
+    }
+}
+
+/// One of the bundled schemas, which the crate's tests guarantee is valid.
+///
+/// With `std` each kind is parsed on first use and shared from then on, so
+/// validating many files of a kind parses its schema once; without it every
+/// call parses.
+pub(crate) fn bundled_schema(kind: SchemaKind) -> Cow<'static, Schema> {
+    #[cfg(feature = "std")]
+    {
+        static PARSED: [OnceLock<Schema>; 4] = [const { OnceLock::new() }; 4];
+        Cow::Borrowed(PARSED[kind as usize].get_or_init(|| parse_bundled(kind)))
+    }
+    #[cfg(not(feature = "std"))]
+    Cow::Owned(parse_bundled(kind))
+}
+
+/// How often each bundled schema has been parsed, by `SchemaKind` index.
+#[cfg(test)]
+static BUNDLED_PARSES: [core::sync::atomic::AtomicUsize; 4] =
+    [const { core::sync::atomic::AtomicUsize::new(0) }; 4];
+
+fn parse_bundled(kind: SchemaKind) -> Schema {
+    #[cfg(test)]
+    BUNDLED_PARSES[kind as usize].fetch_add(1, core::sync::atomic::Ordering::Relaxed);
+    match parse_schema(kind.source()) {
+        Ok(schema) => schema,
+        Err(err) => panic!("bundled {} schema is invalid: {err}", kind.name()),
+    }
+}
+
+/// The version declared in the `[schema]` table of a bundled schema.
+///
+/// # Panics
+///
+/// Panics if the bundled schema does not declare a valid semver version, which
+/// the crate's own tests rule out.
+pub fn schema_version(kind: SchemaKind) -> Version {
+    match parse_version(kind.source()) {
+        Ok(version) => version,
+        Err(err) => panic!(
+            "bundled {} schema has no usable version: {err}",
+            kind.name()
+        ),
+    }
+}
+
+/// A hex SHA-256 digest of the bundled schema for `kind`, for keying caches
+/// of validation results. Unlike [`schema_version`] it changes whenever the
+/// schema's content does. Refs resolve against [`COMMON_SCHEMA`], so its
+/// bytes are hashed too.
+pub fn schema_hash(kind: SchemaKind) -> String {
+    digest(&[kind.source(), COMMON_SCHEMA])
+}
+
+/// The hex SHA-256 of `inputs`, each preceded by its length as a
+/// little-endian `u64` so that moving bytes from one input to the next
+/// changes the digest.
+fn digest(inputs: &[&str]) -> String {
+    let mut hasher = Sha256::new();
+    for input in inputs {
+        hasher.update((input.len() as u64).to_le_bytes());
+        hasher.update(input);
+    }
+    format!("{:x}", hasher.finalize())
+}
+
+/// Whether the bundled schema for `kind` has the [`schema_hash`]
+/// `expected_sha256`, e.g. one pinned when the schema was reviewed, so a
+/// deployment can refuse to start with a schema it did not expect. Hex digits
+/// may be in either case. The digests are compared in constant time.
+pub fn verify_schema(kind: SchemaKind, expected_sha256: &str) -> bool {
+    let actual = schema_hash(kind);
+    let expected = expected_sha256.to_ascii_lowercase();
+    actual.len() == expected.len()
+        && actual
+            .bytes()
+            .zip(expected.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+/// Read and parse `[schema].version` from a schema document.
+pub fn parse_version(src: &str) -> Result<Version, SchemaParseError> {
+    let doc = parse_toml(src)?;
+    let version = doc
+        .get("schema")
+        .and_then(|schema| schema.get("version"))
+        .and_then(Value::as_str)
+        .ok_or(SchemaParseError::MissingVersion)?;
+    Version::parse(version).map_err(|err| SchemaParseError::InvalidVersion(err.to_string()))
+}
+
+/// The `[schema]` table of a schema document, from [`schema_metadata`] or
+/// [`parse_metadata`], e.g. to show what a file configures in a UI.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SchemaMetadata {
+    /// `version`, as [`parse_version`] reads it.
+    pub version: Version,
+    /// `title`, a short name such as `Gateway configuration`.
+    pub title: Option<String>,
+    /// `description`, a sentence on what the schema covers.
+    pub description: Option<String>,
+    /// `min_proxy_version`, the oldest proxy release that reads documents
+    /// written against the schema.
+    pub min_proxy_version: Option<Version>,
+    /// Every other key of the table, as written. Known keys that are not
+    /// strings are kept here too.
+    pub extra: Table,
+}
+
+/// The `[schema]` metadata of the bundled schema for `kind`.
+///
+/// # Panics
+///
+/// Panics if the bundled metadata does not parse, which the crate's own
+/// tests rule out.
+pub fn schema_metadata(kind: SchemaKind) -> SchemaMetadata {
+    match parse_metadata(kind.source()) {
+        Ok(metadata) => metadata,
+        Err(err) => panic!(
+            "bundled {} schema has no usable metadata: {err}",
+            kind.name()
+        ),
+    }
+}
+
+/// Read and parse the `[schema]` table of a schema document.
+pub fn parse_metadata(src: &str) -> Result<SchemaMetadata, SchemaParseError> {
+    let version = parse_version(src)?;
+    let doc = parse_toml(src)?;
+    let mut extra = doc
+        .get("schema")
+        .and_then(Value::as_table)
+        .cloned()
+        .unwrap_or_default();
+    extra.remove("version");
+    let mut take = |key: &str| match extra.remove(key) {
+        Some(Value::String(text)) => Some(text),
+        Some(other) => {
+            extra.insert(key.to_owned(), other);
+            None
+        }
+        None => None,
+    };
+    let title = take("title");
+    let description = take("description");
+    let min_proxy_version = take("min_proxy_version")
+        .map(|version| Version::parse(&version))
+        .transpose()
+        .map_err(|err| SchemaParseError::InvalidVersion(err.to_string()))?;
+    Ok(SchemaMetadata {
+        version,
+        title,
+        description,
+        min_proxy_version,
+        extra,
+    })
+}
+
+/// A self-consistency problem in a schema document, found by
+/// [`validate_schema_itself`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct SchemaLint {
+    /// Path of the offending rule, e.g. `proxy.log_level`, or of the
+    /// `[enumerations]` entry (`enumerations.<name>`). Empty when the schema
+    /// does not parse.
+    pub path: String,
+    /// What is wrong.
+    pub kind: SchemaLintKind,
+    /// Human-readable description of the problem.
+    pub message: String,
+}
+
+/// The problem a [`SchemaLint`] reports.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum SchemaLintKind {
+    /// The schema does not parse or has no valid version, e.g. because a
+    /// pattern does not compile or a `ref` names no definition.
+    Unparsable(SchemaParseError),
+    /// A field's `default` breaks the field's own rule: its type, `values`,
+    /// bounds, `pattern` or format.
+    InvalidDefault,
+    /// A field's `example` breaks the field's own rule, like an
+    /// [`InvalidDefault`](SchemaLintKind::InvalidDefault).
+    InvalidExample,
+    /// A value set lists the same value twice.
+    DuplicateValue {
+        /// The repeated value.
+        value: String,
+    },
+    /// A field's `min` is greater than its `max`, or its `min_items` than its
+    /// `max_items`, so no value is accepted.
+    EmptyRange,
+}
+
+/// Formats as `path: message`, or just the message for a schema that does
+/// not parse.
+impl fmt::Display for SchemaLint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.path.is_empty() {
+            write!(f, "{}: ", self.path)?;
+        }
+        f.write_str(&self.message)
+    }
+}
+
+/// Check a schema DSL document for mistakes that parse but can never be
+/// right: defaults and examples that fail their own field's rule, duplicate `values` or
+/// `[enumerations]` entries, and `min` above `max`. A schema that does not
+/// parse, for instance because of an invalid `pattern` or a `ref` to a
+/// missing definition, is reported as a single
+/// [`Unparsable`](SchemaLintKind::Unparsable) lint.
+///
+/// A default on a required field is not a mistake: it is the value
+/// [`apply_fixes`](crate::apply_fixes) inserts when the field is missing.
+pub fn validate_schema_itself(src: &str) -> Result<(), Vec<SchemaLint>> {
+    let unparsable = |err: SchemaParseError| {
+        vec![SchemaLint {
+            path: String::new(),
+            message: err.to_string(),
+            kind: SchemaLintKind::Unparsable(err),
+        }]
+    };
+    parse_version(src).map_err(unparsable)?;
+    let schema = parse_schema(src).map_err(unparsable)?;
+
+    let mut lints = Vec::new();
+    let mut duplicates = |path: &str, values: &[String]| {
+        for (index, value) in values.iter().enumerate() {
+            if values[..index].contains(value) {
+                lints.push(SchemaLint {
+                    path: path.to_owned(),
+                    kind: SchemaLintKind::DuplicateValue {
+                        value: value.clone(),
+                    },
+                    message: format!("`{value}` is listed more than once"),
+                });
+            }
+        }
+    };
+    for (name, values) in &schema.enumerations {
+        duplicates(&format!("enumerations.{name}"), values);
+    }
+    for rule in &schema.fields {
+        if let Some(values) = &rule.allowed_values {
+            duplicates(&rule.path, values);
+        }
+    }
+
+    for rule in &schema.fields {
+        if let Some(error) = rule
+            .default
+            .as_ref()
+            .and_then(|default| check_value(rule, &rule.path, default))
+        {
+            lints.push(SchemaLint {
+                path: rule.path.clone(),
+                kind: SchemaLintKind::InvalidDefault,
+                message: format!("the default breaks the field's rule: {}", error.message),
+            });
+        }
+        if let Some(error) = rule
+            .example
+            .as_ref()
+            .and_then(|example| check_value(rule, &rule.path, example))
+        {
+            lints.push(SchemaLint {
+                path: rule.path.clone(),
+                kind: SchemaLintKind::InvalidExample,
+                message: format!("the example breaks the field's rule: {}", error.message),
+            });
+        }
+        if let (Some(min), Some(max)) = (rule.min, rule.max) {
+            if min > max {
+                lints.push(SchemaLint {
+                    path: rule.path.clone(),
+                    kind: SchemaLintKind::EmptyRange,
+                    message: format!("`min` ({min}) is greater than `max` ({max})"),
+                });
+            }
+        }
+        if let (Some(min), Some(max)) = (rule.min_items, rule.max_items) {
+            if min > max {
+                lints.push(SchemaLint {
+                    path: rule.path.clone(),
+                    kind: SchemaLintKind::EmptyRange,
+                    message: format!("`min_items` ({min}) is greater than `max_items` ({max})"),
+                });
+            }
+        }
+    }
+    lints.sort_by(|a, b| a.path.cmp(&b.path));
+
+    if lints.is_empty() {
+        Ok(())
+    } else {
+        Err(lints)
+    }
+}
+
+fn parse_type(path: &str, name: &str) -> Result<FieldType, SchemaParseError> {
+    FieldType::parse(name).ok_or_else(|| SchemaParseError::UnknownType {
+        path: path.to_owned(),
+        ty: name.to_owned(),
+    })
+}
+
+fn str_key<'a>(table: &'a Table, key: &str) -> Option<&'a str> {
+    table.get(key).and_then(Value::as_str)
+}
+
+fn string_list(table: &Table, key: &str) -> Option<Vec<String>> {
+    let items = table.get(key)?.as_array()?;
+    Some(
+        items
+            .iter()
+            .filter_map(Value::as_str)
+            .map(str::to_owned)
+            .collect(),
+    )
+}
+
+fn bool_key(table: &Table, key: &str) -> bool {
+    table.get(key).and_then(Value::as_bool).unwrap_or(false)
+}
+
+/// A semver version such as `since`, for the rule at `path`.
+fn version_key(table: &Table, key: &str, path: &str) -> Result<Option<Version>, SchemaParseError> {
+    str_key(table, key)
+        .map(|version| {
+            Version::parse(version).map_err(|_| SchemaParseError::InvalidRuleVersion {
+                path: path.to_owned(),
+                version: version.to_owned(),
+            })
+        })
+        .transpose()
+}
+
+fn weights_key(table: &Table, path: &str) -> Result<Option<WeightSum>, SchemaParseError> {
+    str_key(table, "weights")
+        .map(|weights| match weights {
+            "percent" => Ok(WeightSum::Percent),
+            "relative" => Ok(WeightSum::Relative),
+            _ => Err(SchemaParseError::InvalidWeights {
+                path: path.to_owned(),
+                weights: weights.to_owned(),
+            }),
+        })
+        .transpose()
+}
+
+/// A field's `validator`, which must name a registered validator.
+fn validator_key(field: &Table, path: &str) -> Result<Option<String>, SchemaParseError> {
+    str_key(field, "validator")
+        .map(|name| match validator(name) {
+            Some(_) => Ok(name.to_owned()),
+            None => Err(SchemaParseError::UnknownValidator {
+                path: path.to_owned(),
+                validator: name.to_owned(),
+            }),
+        })
+        .transpose()
+}
+
+/// A non-negative integer such as `min_items`. Negative counts are ignored.
+fn count_key(table: &Table, key: &str) -> Option<usize> {
+    let count = table.get(key)?.as_integer()?;
+    usize::try_from(count).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+    #[cfg(feature = "schema-config")]
+    use crate::CONFIG_SCHEMA;
+
+    #[test]
+    fn parses_all_bundled_schemas() {
+        let sources = SchemaKind::all().iter().map(|kind| kind.source());
+        for src in core::iter::once(COMMON_SCHEMA).chain(sources) {
+            let schema = parse_schema(src).expect("bundled schema parses");
+            assert!(!schema.fields.is_empty());
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "schema-config")]
+    fn wildcard_tables_are_marked_as_patterns() {
+        let schema = parse_schema(CONFIG_SCHEMA).unwrap();
+        let provider = schema
+            .fields
+            .iter()
+            .find(|f| f.path == "provider.*")
+            .unwrap();
+        assert_eq!(provider.ty, FieldType::Table { pattern: true });
+
+        let proxy = schema.fields.iter().find(|f| f.path == "proxy").unwrap();
+        assert_eq!(proxy.ty, FieldType::Table { pattern: false });
+        assert!(proxy.required);
+    }
+
+    #[test]
+    #[cfg(feature = "schema-config")]
+    fn fields_under_a_prefix_respect_path_boundaries() {
+        let schema = parse_schema(CONFIG_SCHEMA).unwrap();
+        let paths = |prefix: &str| -> Vec<String> {
+            let rules = schema.fields_under(prefix);
+            rules.into_iter().map(|rule| rule.path.clone()).collect()
+        };
+        let network = paths("network");
+        assert!(network.contains(&"network.*".to_owned()));
+        assert!(network.contains(&"network.*.tcp_config.bind_port".to_owned()));
+        assert!(network.iter().all(|path| path.starts_with("network")));
+        assert!(!network.iter().any(|path| path.starts_with("storage")));
+        assert!(paths("storage").contains(&"storage.backend".to_owned()));
+        assert!(paths("stor").is_empty());
+        assert_eq!(paths("").len(), schema.fields.len());
+    }
+
+    #[test]
+    #[cfg(feature = "schema-config")]
+    fn the_field_tree_nests_rules_by_path() {
+        let tree = field_tree(SchemaKind::Config);
+        assert!(tree.rule.is_none());
+        let storage = tree.child("storage").unwrap();
+        assert_eq!(storage.rule.as_ref().unwrap().path, "storage");
+        let names: Vec<&str> = storage
+            .children
+            .iter()
+            .map(|child| child.name.as_str())
+            .collect();
+        assert_eq!(names, ["backend", "options"]);
+        let options = storage.child("options").unwrap();
+        assert!(options.child("bucket").is_some());
+        assert!(options.child("path").is_some());
+
+        // Every instance of a wildcard table shares one templated node.
+        let network = tree.child("network").unwrap();
+        assert_eq!(network.children.len(), 1);
+        let instance = network.child("*").unwrap();
+        assert_eq!(instance.rule.as_ref().unwrap().path, "network.*");
+        let port = instance.child("tcp_config").unwrap().child("bind_port");
+        assert_eq!(
+            port.unwrap().rule.as_ref().unwrap().path,
+            "network.*.tcp_config.bind_port"
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "schema-config")]
+    fn field_rules_carry_metadata() {
+        let schema = parse_schema(CONFIG_SCHEMA).unwrap();
+        let api = schema
+            .fields
+            .iter()
+            .find(|f| f.path == "provider.*.api")
+            .unwrap();
+        assert_eq!(api.ty, FieldType::Url);
+        assert!(!api.required);
+        assert!(api.description.is_some());
+
+        let port = schema
+            .fields
+            .iter()
+            .find(|f| f.path == "network.*.tcp_config.bind_port")
+            .unwrap();
+        assert_eq!(port.ty, FieldType::Integer);
+        assert_eq!(port.default, Some(Value::Integer(8080)));
+        assert_eq!((port.min, port.max), (Some(1), Some(65535)));
+
+        let level = schema.field("logging.log_level").unwrap();
+        assert_eq!(
+            level.allowed_values.as_deref(),
+            Some(&["trace", "debug", "info", "warn", "error"].map(String::from)[..])
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "schema-config")]
+    fn bundled_versions_match_their_files() {
+        assert_eq!(schema_version(SchemaKind::Config), Version::new(1, 12, 0));
+        for kind in SchemaKind::all() {
+            let declared = format!("version = \"{}\"", schema_version(*kind));
+            assert!(kind.source().contains(&declared), "{}", kind.name());
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "schema-config")]
+    fn schema_metadata_is_parsed_with_unknown_keys_kept() {
+        let config = schema_metadata(SchemaKind::Config);
+        assert_eq!(config.version, schema_version(SchemaKind::Config));
+        assert_eq!(config.title.as_deref(), Some("Gateway configuration"));
+        assert!(config.description.unwrap().contains("gateway"));
+        assert!(config.extra.is_empty());
+        for kind in SchemaKind::all() {
+            assert!(schema_metadata(*kind).title.is_some(), "{}", kind.name());
+        }
+
+        let src = "[schema]\nversion = \"2.0.0\"\ntitle = 7\nmin_proxy_version = \"1.4.0\"\n\
+                   owner = \"platform\"\n";
+        let metadata = parse_metadata(src).unwrap();
+        assert_eq!(metadata.min_proxy_version, Some(Version::new(1, 4, 0)));
+        assert_eq!(metadata.title, None);
+        assert_eq!(metadata.extra.len(), 2);
+        assert_eq!(metadata.extra["owner"].as_str(), Some("platform"));
+        assert!(matches!(
+            parse_metadata("[schema]\nversion = \"2.0.0\"\nmin_proxy_version = \"soon\"\n"),
+            Err(SchemaParseError::InvalidVersion(_))
+        ));
+    }
+
+    #[test]
+    #[cfg(all(feature = "schema-config", feature = "schema-pipeline"))]
+    fn schema_hashes_are_stable_and_distinct() {
+        let hashes: Vec<String> = SchemaKind::all()
+            .iter()
+            .map(|kind| schema_hash(*kind))
+            .collect();
+        for (kind, hash) in SchemaKind::all().iter().zip(&hashes) {
+            assert_eq!(&schema_hash(*kind), hash);
+            assert_eq!(hash.len(), 64);
+            assert!(hash
+                .bytes()
+                .all(|b| b.is_ascii_hexdigit() && !b.is_ascii_uppercase()));
+        }
+        assert_ne!(
+            schema_hash(SchemaKind::Config),
+            schema_hash(SchemaKind::Pipeline)
+        );
+    }
+
+    #[test]
+    fn digests_keep_their_inputs_apart() {
+        assert_ne!(digest(&["ab", "c"]), digest(&["a", "bc"]));
+        assert_ne!(digest(&["abc", ""]), digest(&["", "abc"]));
+    }
+
+    #[test]
+    #[cfg(all(feature = "std", feature = "schema-pipeline"))]
+    fn bundled_schemas_are_parsed_once() {
+        use core::sync::atomic::Ordering;
+
+        let pipeline = "[pipelines.api]\ndescription = \"API\"\nnetworks = [\"default\"]\n\
+                        endpoints = [\"api\"]\nbackends = [\"upstream\"]\n";
+        for _ in 0..10 {
+            let _ = crate::validate(SchemaKind::Pipeline, pipeline);
+        }
+        let parses = &BUNDLED_PARSES[SchemaKind::Pipeline as usize];
+        assert_eq!(parses.load(Ordering::Relaxed), 1);
+        assert!(core::ptr::eq(
+            &*bundled_schema(SchemaKind::Pipeline),
+            &*bundled_schema(SchemaKind::Pipeline)
+        ));
+        assert_eq!(parses.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn bundled_schemas_are_self_consistent() {
+        for kind in SchemaKind::all() {
+            assert_eq!(validate_schema_itself(kind.source()), Ok(()), "{kind:?}");
+        }
+    }
+
+    #[test]
+    fn inconsistent_schemas_are_linted() {
+        let schema = r#"
+[schema]
+version = "1.0.0"
+
+[enumerations]
+levels = ["debug", "info", "debug"]
+
+[[table]]
+name = "proxy"
+
+[[table.field]]
+name = "log_level"
+values = ["info", "warn"]
+default = "trace"
+example = "Info"
+
+[[table.field]]
+name = "workers"
+type = "integer"
+min = 8
+max = 4
+default = "four"
+"#;
+        let lints: Vec<_> = validate_schema_itself(schema)
+            .unwrap_err()
+            .into_iter()
+            .map(|lint| (lint.path, lint.kind))
+            .collect();
+        assert_eq!(
+            lints,
+            [
+                (
+                    "enumerations.levels".to_owned(),
+                    SchemaLintKind::DuplicateValue {
+                        value: "debug".to_owned()
+                    }
+                ),
+                ("proxy.log_level".to_owned(), SchemaLintKind::InvalidDefault),
+                ("proxy.log_level".to_owned(), SchemaLintKind::InvalidExample),
+                ("proxy.workers".to_owned(), SchemaLintKind::InvalidDefault),
+                ("proxy.workers".to_owned(), SchemaLintKind::EmptyRange),
+            ]
+        );
+
+        let dangling = "[schema]\nversion = \"1.0.0\"\n\n[[table]]\nname = \"a\"\n\n\
+                        [[table.field]]\nname = \"b\"\nref = \"common.nope\"\n";
+        let lints = validate_schema_itself(dangling).unwrap_err();
+        assert!(matches!(
+            &lints[0].kind,
+            SchemaLintKind::Unparsable(SchemaParseError::UnresolvedRef { reference, .. })
+                if reference == "common.nope"
+        ));
+        assert!(matches!(
+            validate_schema_itself("[[table]]\nname = \"a\"\n").unwrap_err()[0].kind,
+            SchemaLintKind::Unparsable(SchemaParseError::MissingVersion)
+        ));
+    }
+
+    #[test]
+    #[cfg(all(feature = "schema-config", feature = "schema-pipeline"))]
+    fn pinned_schema_hashes_are_verified() {
+        let pinned = schema_hash(SchemaKind::Config);
+        assert!(verify_schema(SchemaKind::Config, &pinned));
+        assert!(verify_schema(
+            SchemaKind::Config,
+            &pinned.to_ascii_uppercase()
+        ));
+
+        let mut tampered = pinned.clone().into_bytes();
+        tampered[63] = if tampered[63] == b'0' { b'1' } else { b'0' };
+        let tampered = String::from_utf8(tampered).unwrap();
+        assert!(!verify_schema(SchemaKind::Config, &tampered));
+        assert!(!verify_schema(SchemaKind::Config, &pinned[..63]));
+        assert!(!verify_schema(SchemaKind::Pipeline, &pinned));
+    }
+
+    #[test]
+    fn missing_version_is_an_error() {
+        let src = "[schema]\ndescription = \"no version\"\n";
+        assert_eq!(parse_version(src), Err(SchemaParseError::MissingVersion));
+        assert!(matches!(
+            parse_version("[schema]\nversion = \"one\"\n"),
+            Err(SchemaParseError::InvalidVersion(_))
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "schema-config")]
+    fn describe_field_matches_literal_paths() {
+        let rule = describe_field(SchemaKind::Config, "storage.options.bucket").unwrap();
+        assert_eq!(rule.path, "storage.options.bucket");
+        assert_eq!(
+            rule.description.as_deref(),
+            Some("S3 bucket name (for s3 backend)")
+        );
+    }
+
+    #[test]
+    #[cfg(all(feature = "schema-config", feature = "schema-pipeline"))]
+    fn describe_field_resolves_wildcards_and_indices() {
+        let rule = describe_field(SchemaKind::Config, "provider.foo.api").unwrap();
+        assert_eq!(rule.path, "provider.*.api");
+
+        let rule = describe_field(
+            SchemaKind::Pipeline,
+            "middleware.filter.options.rules[1].allow",
+        )
+        .unwrap();
+        assert_eq!(rule.path, "middleware.*.options.rules.allow");
+    }
+
+    #[test]
+    #[cfg(feature = "schema-config")]
+    fn describe_field_returns_none_for_unknown_paths() {
+        assert_eq!(describe_field(SchemaKind::Config, "proxy.nope"), None);
+        assert_eq!(describe_field(SchemaKind::Config, ""), None);
+    }
+
+    #[test]
+    #[cfg(feature = "schema-config")]
+    fn required_fields_include_conditional_ones() {
+        let required = required_fields(SchemaKind::Config);
+        assert!(required.contains(&"proxy.id".to_owned()));
+        assert!(required.contains(&"services.*.module".to_owned()));
+        assert!(required.contains(&"storage.options.bucket".to_owned()));
+        assert!(!required.contains(&"proxy".to_owned()));
+        assert!(!required.contains(&"storage.options.region".to_owned()));
+    }
+
+    #[test]
+    #[cfg(feature = "schema-config")]
+    fn enum_fields_list_their_allowed_values() {
+        let fields = enum_fields(SchemaKind::Config);
+        let level = fields
+            .iter()
+            .find(|(path, _)| path == "logging.log_level")
+            .map(|(_, values)| values);
+        assert_eq!(level.unwrap(), &["trace", "debug", "info", "warn", "error"]);
+        assert!(fields.iter().all(|(_, values)| !values.is_empty()));
+        assert!(!fields.iter().any(|(path, _)| path == "proxy.id"));
+    }
+
+    #[test]
+    #[cfg(feature = "schema-config")]
+    fn summaries_count_the_bundled_schema() {
+        let summary = schema_summary(SchemaKind::Config);
+        assert_eq!(
+            summary.version,
+            parse_version(crate::CONFIG_SCHEMA).unwrap()
+        );
+        assert_eq!(summary.required, required_fields(SchemaKind::Config).len());
+        assert!(summary.required > 0);
+        assert!(summary.fields > summary.required);
+        assert!(summary.wildcard_tables > 0);
+        assert!(summary
+            .to_string()
+            .starts_with(&format!("config schema v{}: ", summary.version)));
+    }
+
+    #[test]
+    #[cfg(feature = "schema-config")]
+    fn field_paths_are_sorted_and_include_nested_wildcards() {
+        let paths = all_field_paths(SchemaKind::Config);
+        assert!(paths.windows(2).all(|pair| pair[0] < pair[1]));
+        for path in [
+            "proxy",
+            "proxy.id",
+            "network.*",
+            "network.*.tcp_config.bind_port",
+            "network.*.wireguard_peers.*.allowed_ips",
+        ] {
+            assert!(paths.contains(&path.to_owned()), "{path}");
+        }
+
+        let schema = bundled_schema(SchemaKind::Config);
+        assert_eq!(schema.field_paths().count(), schema.fields.len());
+    }
+
+    #[test]
+    fn unknown_types_are_rejected() {
+        let src =
+            "[[table]]\nname = \"proxy\"\n\n[[table.field]]\nname = \"id\"\ntype = \"uuid\"\n";
+        assert_eq!(
+            parse_schema(src),
+            Err(SchemaParseError::UnknownType {
+                path: "proxy.id".to_owned(),
+                ty: "uuid".to_owned(),
+            })
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "schema-config")]
+    fn refs_are_inlined_from_the_common_schema() {
+        let schema = parse_schema(CONFIG_SCHEMA).unwrap();
+        let host = schema.field("peers.hospital.connection.host").unwrap();
+        assert_eq!(host.path, "peers.*.connection.host");
+        assert_eq!(host.table, "peers.*");
+        assert_eq!(host.relative_path(), "connection.host");
+        assert!(host.required);
+        assert!(schema
+            .field("targets.api.connection.ca_cert_path")
+            .is_some());
+    }
+
+    #[test]
+    fn unresolved_refs_are_rejected() {
+        let src =
+            "[[table]]\nname = \"a\"\n\n[[table.field]]\nname = \"b\"\nref = \"common.nope\"\n";
+        assert_eq!(
+            parse_schema(src),
+            Err(SchemaParseError::UnresolvedRef {
+                path: "a.b".to_owned(),
+                reference: "common.nope".to_owned(),
+            })
+        );
+    }
+
+    #[test]
+    fn cyclic_refs_are_rejected() {
+        let common = r#"
+[[table]]
+name = "x"
+
+[[table.field]]
+name = "y"
+ref = "common.y"
+
+[[table]]
+name = "y"
+
+[[table.field]]
+name = "x"
+ref = "common.x"
+"#;
+        let src = "[[table]]\nname = \"a\"\n\n[[table.field]]\nname = \"b\"\nref = \"common.x\"\n";
+        assert_eq!(
+            parse_schema_with(src, common),
+            Err(SchemaParseError::CyclicRef {
+                chain: vec!["common.x".into(), "common.y".into(), "common.x".into()],
+            })
+        );
+    }
+
+    #[test]
+    fn templated_defaults_must_not_refer_to_each_other_in_a_cycle() {
+        let src = r#"[schema]
+version = "1.0.0"
+
+[[table]]
+name = "a"
+
+[[table.field]]
+name = "b"
+default = "{a.c}-b"
+
+[[table.field]]
+name = "c"
+default = "c-{a.b}"
+"#;
+        assert_eq!(
+            parse_schema(src),
+            Err(SchemaParseError::CyclicDefault {
+                chain: vec!["a.b".into(), "a.c".into(), "a.b".into()],
+            })
+        );
+
+        let acyclic = src.replace("c-{a.b}", "${C}-c");
+        let schema = parse_schema(&acyclic).unwrap();
+        let rule = |path: &str| schema.fields.iter().find(|rule| rule.path == path).unwrap();
+        assert_eq!(rule("a.b").default_references(), ["a.c"]);
+        assert!(rule("a.c").default_references().is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "schema-config")]
+    fn patterns_are_compiled_at_parse_time() {
+        let rule = describe_field(SchemaKind::Config, "proxy.id").unwrap();
+        let pattern = rule.pattern.unwrap();
+        assert!(pattern.is_match("edge-gw-1"));
+        assert!(!pattern.is_match("Edge GW"));
+
+        let src = "[[table]]\nname = \"a\"\n\n[[table.field]]\nname = \"b\"\npattern = \"[a-\"\n";
+        assert!(matches!(
+            parse_schema(src),
+            Err(SchemaParseError::InvalidPattern { path, .. }) if path == "a.b"
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "schema-pipeline")]
+    fn required_fields_of_a_service_type_include_its_extension() {
+        let schema = bundled_schema(SchemaKind::Pipeline);
+        let dicom = schema.required_for_service_type("dicom");
+        let http = schema.required_for_service_type("http");
+        assert!(dicom.contains(&"endpoints.*.service".to_owned()));
+        assert!(dicom.contains(&"endpoints.*.options.local_aet".to_owned()));
+        assert!(http.contains(&"endpoints.*.service".to_owned()));
+        assert!(!http.contains(&"endpoints.*.options.local_aet".to_owned()));
+        assert!(!dicom.iter().any(|path| path.starts_with("pipelines.")));
+    }
+
+    #[test]
+    #[cfg(feature = "schema-pipeline")]
+    fn extension_fields_carry_their_condition() {
+        let rule = describe_field(SchemaKind::Pipeline, "endpoints.scp.options.local_aet").unwrap();
+        assert_eq!(
+            rule.extension,
+            Some(Condition::Equals {
+                field: "service".to_owned(),
+                value: Value::String("dicom".to_owned()),
+            })
+        );
+        assert_eq!(rule.table, "endpoints.*");
+        let base = describe_field(SchemaKind::Pipeline, "endpoints.scp.options.path_prefix");
+        assert_eq!(base.unwrap().extension, None);
+
+        let src = "[[table]]\nname = \"t\"\n\n[[table.extension]]\n\n[[table.extension.field]]\nname = \"f\"\n";
+        assert_eq!(
+            parse_schema(src),
+            Err(SchemaParseError::InvalidExtension {
+                table: "t".to_owned(),
+                condition: None,
+            })
+        );
+    }
+
+    #[test]
+    fn conditions_parse_from_tables_and_shorthand() {
+        let condition = |src: &str| {
+            let src = format!(
+                "[[table]]\nname = \"t\"\n\n[[table.field]]\nname = \"f\"\nrequired_if = {src}\n"
+            );
+            parse_schema(&src).map(|schema| schema.fields[1].required_if.clone().unwrap())
+        };
+        let backend_is_s3 = Condition::Equals {
+            field: "backend".to_owned(),
+            value: Value::String("s3".to_owned()),
+        };
+        assert_eq!(
+            condition(r#"{ field = "backend", equals = "s3" }"#),
+            Ok(backend_is_s3.clone())
+        );
+        assert_eq!(condition(r#""backend == 's3'""#), Ok(backend_is_s3));
+        assert_eq!(
+            condition(r#""log_to_file == true""#),
+            Ok(Condition::Equals {
+                field: "log_to_file".to_owned(),
+                value: Value::Boolean(true),
+            })
+        );
+        assert_eq!(
+            condition(r#"{ field = "http3", exists = true }"#),
+            Ok(Condition::Exists {
+                field: "http3".to_owned()
+            })
+        );
+        assert_eq!(
+            condition(r#""name not exists""#),
+            Ok(Condition::Missing {
+                field: "name".to_owned()
+            })
+        );
+        assert!(matches!(
+            condition(r#""backend is s3""#),
+            Err(SchemaParseError::InvalidCondition { path, .. }) if path == "t.f"
+        ));
+        assert!(matches!(
+            condition(r#"{ equals = "s3" }"#),
+            Err(SchemaParseError::InvalidCondition { .. })
+        ));
+    }
+
+    /// Schema documents whose entries use the DSL's own keys with values of
+    /// any type, so parsing gets past TOML and into rule construction.
+    fn schema_shaped() -> impl Strategy<Value = String> {
+        let value = prop_oneof![
+            prop::sample::select(
+                &[
+                    "\"string\"",
+                    "\"table\"",
+                    "\"array\"",
+                    "\"t\"",
+                    "\"t.*\"",
+                    "\"\"",
+                    "\"common.connection\"",
+                    "\"common.t\"",
+                    "\"common.\"",
+                    "\"f == 's3'\"",
+                    "\"f exists\"",
+                    "\"f not exists\"",
+                    "\"==\"",
+                    "\"[a-\"",
+                    "\"^[a-z]+$\"",
+                    "{ field = \"f\", equals = 1 }",
+                    "{ field = \"f\", exists = 1 }",
+                    "true",
+                    "0",
+                    "-9223372036854775808",
+                    "[\"a\", 1]",
+                    "[]",
+                    "{}",
+                ][..]
+            )
+            .prop_map(str::to_owned),
+            any::<String>().prop_map(|s| Value::String(s).to_string()),
+        ];
+        let keys = &[
+            "name",
+            "type",
+            "required",
+            "required_if",
+            "default",
+            "example",
+            "values",
+            "enum",
+            "min",
+            "max",
+            "min_items",
+            "max_items",
+            "pattern",
+            "non_empty",
+            "secret",
+            "security_relevant",
+            "array_item_type",
+            "ref",
+            "deprecated",
+            "replaced_by",
+            "since",
+            "until",
+            "description",
+        ][..];
+        let entry = (prop::sample::select(keys), value)
+            .prop_map(|(key, value)| format!("{key} = {value}\n"));
+        let entries = || prop::collection::vec(entry.clone(), 0..5).prop_map(|e| e.concat());
+        let table =
+            (entries(), prop::collection::vec(entries(), 0..4)).prop_map(|(table, fields)| {
+                let fields: String = fields
+                    .iter()
+                    .map(|field| format!("[[table.field]]\n{field}"))
+                    .collect();
+                format!("[[table]]\n{table}{fields}")
+            });
+        prop::collection::vec(table, 0..4)
+            .prop_map(|tables| format!("[schema]\nversion = \"1.0.0\"\n{}", tables.concat()))
+    }
+
+    proptest! {
+        #[test]
+        fn arbitrary_bytes_never_panic(bytes in any::<Vec<u8>>()) {
+            let src = String::from_utf8_lossy(&bytes);
+            let _ = parse_schema(&src);
+            let _ = parse_version(&src);
+        }
+
+        #[test]
+        fn schema_shaped_documents_never_panic(src in schema_shaped(), common in schema_shaped()) {
+            let _ = parse_schema(&src);
+            if let Ok(schema) = parse_schema_with(&src, &common) {
+                for rule in &schema.fields {
+                    let _ = schema.field(&rule.path);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn parse_errors_display_the_offending_rule() {
+        assert_eq!(
+            SchemaParseError::UnresolvedRef {
+                path: "peers.*.connection".to_owned(),
+                reference: "common.endpoint".to_owned(),
+            }
+            .to_string(),
+            "`peers.*.connection` refers to `common.endpoint`, which is not defined"
+        );
+        assert_eq!(
+            SchemaParseError::CyclicRef {
+                chain: vec![
+                    "common.a".to_owned(),
+                    "common.b".to_owned(),
+                    "common.a".to_owned()
+                ],
+            }
+            .to_string(),
+            "refs form a cycle: common.a -> common.b -> common.a"
+        );
+        assert_eq!(
+            SchemaParseError::MissingName { table: None }.to_string(),
+            "a `[[table]]` entry has no `name`"
+        );
+    }
+
+    #[test]
+    fn nameless_fields_are_rejected() {
+        let src = "[[table]]\nname = \"proxy\"\n\n[[table.field]]\ntype = \"string\"\n";
+        assert_eq!(
+            parse_schema(src),
+            Err(SchemaParseError::MissingName {
+                table: Some("proxy".to_owned()),
+            })
+        );
+    }
+}
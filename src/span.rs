@@ -0,0 +1,187 @@
+//! Source locations for values in a TOML document.
+//!
+//! The validator works on [`toml::Value`], which carries no position
+//! information, so a second, span-preserving parse builds a [`SpanIndex`]
+//! mapping each field path to the byte range of its value.
+
+use alloc::collections::BTreeMap;
+use core::ops::Range;
+
+use toml::de::{DeTable, DeValue};
+use toml::Table;
+
+use crate::prelude::*;
+use crate::validate::{element, join};
+
+/// Byte ranges of every value in a document, keyed by validator path.
+pub(crate) struct SpanIndex<'a> {
+    src: &'a str,
+    spans: BTreeMap<String, Range<usize>>,
+}
+
+impl<'a> SpanIndex<'a> {
+    /// Index `src`, or return `None` if it does not parse.
+    pub(crate) fn build(src: &'a str) -> Option<Self> {
+        let root = DeTable::parse(src).ok()?;
+        let mut spans = BTreeMap::new();
+        index_table(root.get_ref(), "", &mut spans);
+        Some(Self { src, spans })
+    }
+
+    /// 1-based `(line, column)` of the value at `path`.
+    pub(crate) fn position(&self, path: &str) -> Option<(usize, usize)> {
+        let span = self.spans.get(path)?;
+        Some(line_col(self.src, span.start))
+    }
+
+    /// Byte range of the value at `path`; the header for tables.
+    pub(crate) fn range(&self, path: &str) -> Option<Range<usize>> {
+        self.spans.get(path).cloned()
+    }
+}
+
+/// A key the parser rejected for being defined twice in the same table.
+pub(crate) struct DuplicateKey {
+    /// The key as written, unquoted.
+    pub(crate) key: String,
+    /// Validator path of the key, or just the key if its table is unknown.
+    pub(crate) path: String,
+    /// 1-based `(line, column)` of the first definition, if it can be found.
+    pub(crate) first: Option<(usize, usize)>,
+}
+
+/// Locate the key behind a duplicate-key parse error, given the byte range
+/// of the second definition. Renaming the repeat to a placeholder lets the
+/// document parse, and the placeholder's path names the table both
+/// definitions share.
+pub(crate) fn duplicate_key(src: &str, second: Range<usize>) -> Option<DuplicateKey> {
+    const PLACEHOLDER: &str = "__duplicate_key__";
+
+    let written = src.get(second.clone())?;
+    let key = toml::from_str::<Table>(&format!("{written} = 0"))
+        .ok()?
+        .into_iter()
+        .next()?
+        .0;
+    let renamed = format!(
+        "{}{PLACEHOLDER}{}",
+        &src[..second.start],
+        &src[second.end..]
+    );
+    let Some(index) = SpanIndex::build(&renamed) else {
+        return Some(DuplicateKey {
+            path: key.clone(),
+            key,
+            first: None,
+        });
+    };
+    let table = index.spans.keys().find_map(|path| {
+        path.strip_suffix(PLACEHOLDER)
+            .filter(|table| table.is_empty() || table.ends_with('.'))
+    });
+    let path = match table {
+        Some(table) => format!("{table}{key}"),
+        None => key.clone(),
+    };
+    // Everything before the repeat is unchanged, so positions there hold for
+    // the original source too.
+    let first = index.position(&path);
+    Some(DuplicateKey { key, path, first })
+}
+
+fn index_table(table: &DeTable<'_>, path: &str, spans: &mut BTreeMap<String, Range<usize>>) {
+    for (key, value) in table.iter() {
+        let child = join(path, key.get_ref());
+        index_value(value.get_ref(), &child, spans);
+        spans.insert(child, value.span());
+    }
+}
+
+fn index_value(value: &DeValue<'_>, path: &str, spans: &mut BTreeMap<String, Range<usize>>) {
+    match value {
+        DeValue::Table(table) => index_table(table, path, spans),
+        DeValue::Array(items) => {
+            for (index, item) in items.iter().enumerate() {
+                let child = element(path, index);
+                index_value(item.get_ref(), &child, spans);
+                spans.insert(child, item.span());
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Convert a byte offset into a 1-based `(line, column)` pair, counting
+/// columns in characters. An offset inside a multi-byte character counts as
+/// that character.
+pub(crate) fn line_col(src: &str, offset: usize) -> (usize, usize) {
+    let mut offset = offset.min(src.len());
+    while !src.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    let before = &src[..offset];
+    let line = before.matches('\n').count() + 1;
+    let line_start = before.rfind('\n').map_or(0, |pos| pos + 1);
+    let column = before[line_start..].chars().count() + 1;
+    (line, column)
+}
+
+/// The byte offset of a 1-based `(line, column)` from [`line_col`], or
+/// `None` if `src` has no such position.
+pub(crate) fn offset(src: &str, (line, column): (usize, usize)) -> Option<usize> {
+    let line_start = match line.checked_sub(2) {
+        None if line == 1 => 0,
+        None => return None,
+        Some(newlines) => src.match_indices('\n').nth(newlines)?.0 + 1,
+    };
+    let rest = src[line_start..].split('\n').next().unwrap_or_default();
+    let mut starts = rest
+        .char_indices()
+        .map(|(index, _)| index)
+        .chain([rest.len()]);
+    Some(line_start + starts.nth(column.checked_sub(1)?)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn line_col_is_one_based() {
+        let src = "a = 1\nbb = 2\n";
+        assert_eq!(line_col(src, 0), (1, 1));
+        assert_eq!(line_col(src, 6), (2, 1));
+        assert_eq!(line_col(src, 11), (2, 6));
+    }
+
+    #[test]
+    fn offsets_inside_a_character_are_clamped_to_it() {
+        let src = "a\né = 1";
+        assert_eq!(line_col(src, 3), (2, 1));
+        assert_eq!(line_col(src, 4), (2, 2));
+        assert_eq!(line_col(src, 99), (2, 6));
+        assert_eq!(offset(src, (2, 2)), Some(4));
+        assert_eq!(offset(src, (2, 6)), Some(src.len()));
+        assert_eq!(offset(src, (3, 1)), None);
+    }
+
+    #[test]
+    fn duplicate_keys_are_traced_to_their_first_definition() {
+        let src = "[proxy]\nid = \"a\"\n\n[network.\"edge\"]\nid = 1\n\"id\" = 2\n";
+        let second = src.rfind("\"id\"").unwrap();
+        let duplicate = duplicate_key(src, second..second + 4).unwrap();
+        assert_eq!(duplicate.key, "id");
+        assert_eq!(duplicate.path, "network.edge.id");
+        assert_eq!(duplicate.first, Some((5, 6)));
+    }
+
+    #[test]
+    fn indexes_nested_values_and_array_elements() {
+        let src = "[proxy]\nid = \"gw\"\n\n[[items]]\nname = \"a\"\n\n[[items]]\nname = \"b\"\n";
+        let index = SpanIndex::build(src).unwrap();
+        assert_eq!(index.position("proxy"), Some((1, 1)));
+        assert_eq!(index.position("proxy.id"), Some((2, 6)));
+        assert_eq!(index.position("items[1].name"), Some((8, 8)));
+        assert_eq!(index.position("missing"), None);
+    }
+}
@@ -0,0 +1,68 @@
+//! Named checks for constraints too specialised for the DSL's directives.
+//!
+//! A field opts in with `validator = "<name>"`, naming one of the
+//! functions registered here:
+//!
+//! ```toml
+//! [[table.field]]
+//! name = "options.local_aet"
+//! validator = "dicom_ae_title"
+//! ```
+//!
+//! Schemas naming an unregistered validator fail to parse with
+//! [`SchemaParseError::UnknownValidator`](crate::SchemaParseError::UnknownValidator).
+//! A validator runs after the field's other checks pass, and its error
+//! message is reported as a [`ValidatorFailed`](crate::ValidationErrorKind::ValidatorFailed)
+//! error.
+
+use alloc::collections::BTreeMap;
+
+use toml::Value;
+
+use crate::prelude::*;
+
+/// A named check: `Err` with a message saying what is wrong with the value.
+pub type Validator = fn(&Value) -> Result<(), String>;
+
+const VALIDATORS: &[(&str, Validator)] = &[("dicom_ae_title", dicom_ae_title)];
+
+/// Every validator the crate ships, by the name schemas refer to it by.
+pub fn validators() -> BTreeMap<&'static str, Validator> {
+    VALIDATORS.iter().copied().collect()
+}
+
+/// The validator registered as `name`, if there is one.
+pub fn validator(name: &str) -> Option<Validator> {
+    VALIDATORS
+        .iter()
+        .find(|(registered, _)| *registered == name)
+        .map(|(_, validator)| *validator)
+}
+
+/// A DICOM Application Entity title (value representation `AE`): at most
+/// 16 characters of the default repertoire, without backslashes or control
+/// characters, and not only spaces. Values that are not strings, or hold a
+/// `${NAME}` placeholder, are left to the other checks.
+pub fn dicom_ae_title(value: &Value) -> Result<(), String> {
+    let Some(title) = value.as_str().filter(|title| !title.contains("${")) else {
+        return Ok(());
+    };
+    if title.trim_matches(' ').is_empty() {
+        return Err("an AE title must not be empty or only spaces".to_owned());
+    }
+    if let Some(c) = title
+        .chars()
+        .find(|c| !matches!(c, ' '..='~') || *c == '\\')
+    {
+        return Err(format!(
+            "`{title}` contains {c:?}; AE titles are printable ASCII without `\\`"
+        ));
+    }
+    if title.len() > 16 {
+        return Err(format!(
+            "`{title}` is {} characters long; AE titles have at most 16",
+            title.len()
+        ));
+    }
+    Ok(())
+}
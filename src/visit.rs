@@ -0,0 +1,122 @@
+//! Schema-aware traversal of a document.
+//!
+//! [`walk`] visits every key of a document with the [`FieldRule`] that
+//! governs it, for tooling that reacts to particular fields, e.g. collecting
+//! every listener port in a config, without matching paths against the
+//! schema itself.
+
+use toml::{Table, Value};
+
+use crate::schema::{bundled_schema, FieldRule, Schema};
+use crate::validate::{element, join};
+use crate::SchemaKind;
+
+/// A callback for [`walk`].
+pub trait ConfigVisitor {
+    /// Called for each key of the document, with its dotted path (e.g.
+    /// `network.default.tcp_config.bind_port`), its value, and the schema's
+    /// rule for it, or `None` if the schema does not declare it.
+    fn visit_field(&mut self, path: &str, value: &Value, rule: Option<&FieldRule>);
+}
+
+/// Visit every key of `toml_input`, a document of `kind`, in key order.
+///
+/// Tables are visited before their keys. Arrays are visited once as a
+/// whole; the keys of tables inside them are visited with paths such as
+/// `ingress[1].name`. Documents that are not valid TOML visit nothing;
+/// [`validate`](crate::validate) reports those. The document is not
+/// validated, so values may break their rules.
+pub fn walk(kind: SchemaKind, toml_input: &str, visitor: &mut impl ConfigVisitor) {
+    let Ok(doc) = toml::from_str::<Table>(toml_input) else {
+        return;
+    };
+    walk_table(&bundled_schema(kind), &doc, "", visitor);
+}
+
+fn walk_table(schema: &Schema, table: &Table, path: &str, visitor: &mut impl ConfigVisitor) {
+    for (key, value) in table {
+        let path = join(path, key);
+        visitor.visit_field(&path, value, schema.field(&path));
+        match value {
+            Value::Table(child) => walk_table(schema, child, &path, visitor),
+            Value::Array(items) => {
+                for (index, item) in items.iter().enumerate() {
+                    if let Value::Table(child) = item {
+                        walk_table(schema, child, &element(&path, index), visitor);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(all(test, feature = "schema-config", feature = "schema-pipeline"))]
+mod tests {
+    use super::*;
+    use crate::prelude::*;
+
+    /// Every integer under a key named `port`.
+    #[derive(Default)]
+    struct Ports(Vec<(String, i64, bool)>);
+
+    impl ConfigVisitor for Ports {
+        fn visit_field(&mut self, path: &str, value: &Value, rule: Option<&FieldRule>) {
+            if let (Some(port), true) = (value.as_integer(), path.ends_with(".port")) {
+                self.0.push((path.to_owned(), port, rule.is_some()));
+            }
+        }
+    }
+
+    #[test]
+    fn visitors_see_every_port() {
+        let pipeline = r#"
+[endpoints.api]
+service = "http"
+connection = { host = "0.0.0.0", port = 8080 }
+
+[backends.pacs]
+service = "dicom"
+options = { aet = "PACS", host = "pacs.local", port = 104 }
+port = 1
+"#;
+        let mut ports = Ports::default();
+        walk(SchemaKind::Pipeline, pipeline, &mut ports);
+        assert_eq!(
+            ports.0,
+            vec![
+                ("backends.pacs.options.port".to_owned(), 104, true),
+                ("backends.pacs.port".to_owned(), 1, false),
+                ("endpoints.api.connection.port".to_owned(), 8080, true),
+            ]
+        );
+
+        let mut ports = Ports::default();
+        walk(SchemaKind::Pipeline, "[endpoints.api", &mut ports);
+        assert!(ports.0.is_empty());
+    }
+
+    #[test]
+    fn array_elements_are_walked_by_index() {
+        #[derive(Default)]
+        struct Paths(Vec<String>);
+        impl ConfigVisitor for Paths {
+            fn visit_field(&mut self, path: &str, _: &Value, rule: Option<&FieldRule>) {
+                self.0.push(format!("{path} {}", rule.is_some()));
+            }
+        }
+
+        let mut paths = Paths::default();
+        let config = "[proxy]\nid = \"gw\"\n\n[[proxy.extra]]\nname = \"a\"\n";
+        walk(SchemaKind::Config, config, &mut paths);
+        assert_eq!(
+            paths.0,
+            [
+                "proxy true",
+                "proxy.extra false",
+                "proxy.extra[0].name false",
+                "proxy.id true",
+            ]
+        );
+    }
+}
@@ -0,0 +1,89 @@
+//! `wasm-bindgen` exports for JavaScript and PHP hosts (feature `wasm`).
+//!
+//! Each export takes the TOML source and returns a JSON array of errors,
+//! empty when the document is valid:
+//!
+//! ```json
+//! [{"path": "proxy", "kind": "missing_required", "severity": "error", "message": "...", "line": 1, "column": 1}]
+//! ```
+//!
+//! `line` and `column` are 1-based, or `null` when no location applies.
+
+use serde_json::{json, Value as Json};
+use wasm_bindgen::prelude::wasm_bindgen;
+
+use crate::validate::{validate_report, Severity, ValidationError};
+use crate::wire::kind_name;
+use crate::SchemaKind;
+
+/// Validate a gateway config (`config.toml`).
+#[cfg(feature = "schema-config")]
+#[wasm_bindgen]
+pub fn validate_config(toml_input: &str) -> String {
+    report(SchemaKind::Config, toml_input)
+}
+
+/// Validate a pipeline file (`pipelines/*.toml`).
+#[cfg(feature = "schema-pipeline")]
+#[wasm_bindgen]
+pub fn validate_pipeline(toml_input: &str) -> String {
+    report(SchemaKind::Pipeline, toml_input)
+}
+
+/// Validate a mesh file (`mesh/*.toml`).
+#[cfg(feature = "schema-mesh")]
+#[wasm_bindgen]
+pub fn validate_mesh(toml_input: &str) -> String {
+    report(SchemaKind::Mesh, toml_input)
+}
+
+/// Validate a remote ingress catalogue.
+#[cfg(feature = "schema-remote-ingress")]
+#[wasm_bindgen]
+pub fn validate_remote_ingress(toml_input: &str) -> String {
+    report(SchemaKind::RemoteIngress, toml_input)
+}
+
+fn report(kind: SchemaKind, toml_input: &str) -> String {
+    let errors = validate_report(kind, toml_input).into_diagnostics();
+    Json::Array(errors.iter().map(to_json).collect()).to_string()
+}
+
+fn to_json(error: &ValidationError) -> Json {
+    let (line, column) = error.span.unzip();
+    json!({
+        "path": error.path,
+        "kind": kind_name(&error.kind),
+        "severity": match error.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Info => "info",
+        },
+        "message": error.message,
+        "line": line,
+        "column": column,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn errors_are_returned_as_a_json_array() {
+        assert_eq!(validate_config("[proxy]\nid = \"gw\"\n"), "[]");
+
+        let errors: Json = serde_json::from_str(&validate_config("[logging]\n")).unwrap();
+        assert_eq!(
+            errors,
+            json!([{
+                "path": "proxy",
+                "kind": "missing_required",
+                "severity": "error",
+                "message": "missing required table `proxy`",
+                "line": null,
+                "column": null,
+            }])
+        );
+    }
+}
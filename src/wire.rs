@@ -0,0 +1,938 @@
+//! A versioned JSON form of [`ValidationReport`] for storing results.
+//!
+//! The serde output of [`ValidationReport`] follows the crate's internal
+//! types and can change with them. [`WireReport`] is the contract instead:
+//! it carries an explicit `format_version`, and [`from_wire`] reads every
+//! version listed below, so reports stored by an older release stay
+//! readable after an upgrade. Kind names and their fields are spelled out
+//! here rather than taken from the [`ValidationErrorKind`] serde derive, so
+//! renaming a variant or field in the crate does not change the format.
+//!
+//! - Version 2, written by [`to_wire`]: each diagnostic gives its `line` and
+//!   `column` separately, and its kind as a `kind` name with the variant's
+//!   fields under `details`.
+//! - Version 1: each diagnostic is a [`ValidationError`] as it serializes,
+//!   with the kind as a tagged object under `kind` and the location as a
+//!   `span` pair.
+//!
+//! ```
+//! # #[cfg(feature = "schema-config")] {
+//! use harmony_dsl::{from_wire, to_wire, validate_report, SchemaKind};
+//!
+//! let report = validate_report(SchemaKind::Config, "[proxy]\nid = 7\n");
+//! let stored = serde_json::to_string(&to_wire(&report)).unwrap();
+//! let restored = from_wire(serde_json::from_str(&stored).unwrap()).unwrap();
+//! assert_eq!(restored, report);
+//! # }
+//! ```
+
+use core::fmt;
+#[cfg(feature = "std")]
+use std::path::PathBuf;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value as Json};
+
+use crate::fix::Fix;
+use crate::prelude::*;
+use crate::schema::{Condition, FieldType};
+use crate::validate::{
+    ErrorCode, Severity, ValidationError, ValidationErrorKind, ValidationReport,
+};
+
+/// A [`ValidationReport`] in the current wire format.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WireReport {
+    /// The version of the format, [`WireReport::FORMAT_VERSION`] when
+    /// written by this release.
+    pub format_version: u32,
+    /// The report's diagnostics, in the order it lists them.
+    pub diagnostics: Vec<WireDiagnostic>,
+}
+
+/// One [`ValidationError`] in the current wire format.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WireDiagnostic {
+    /// The dotted path of the offending key, like [`ValidationError::path`].
+    pub path: String,
+    /// The stable string of the [`ErrorCode`], e.g. `E_TYPE_MISMATCH`.
+    pub code: String,
+    /// The wire name of the [`ValidationErrorKind`], e.g. `type_mismatch`.
+    /// Names are fixed by the format and do not follow renames in the crate.
+    pub kind: String,
+    /// The kind's fields under their wire names, if it has any.
+    #[serde(default, skip_serializing_if = "Map::is_empty")]
+    pub details: Map<String, Json>,
+    /// The rendered message, like [`ValidationError::message`].
+    pub message: String,
+    /// 1-based, like [`ValidationError::span`].
+    pub line: Option<usize>,
+    /// 1-based, like [`ValidationError::span`].
+    pub column: Option<usize>,
+    /// `error`, `warning` or `info`.
+    pub severity: String,
+    /// The file the diagnostic is in, like [`ValidationError::file`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+    /// The [`Fix`] as it serializes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fix: Option<Json>,
+}
+
+impl WireReport {
+    /// The format version [`to_wire`] writes.
+    pub const FORMAT_VERSION: u32 = 2;
+}
+
+/// A version 1 report: the diagnostics as [`ValidationError`] serialized them.
+#[derive(Deserialize)]
+struct WireReportV1 {
+    diagnostics: Vec<WireDiagnosticV1>,
+}
+
+#[derive(Deserialize)]
+struct WireDiagnosticV1 {
+    path: String,
+    kind: Json,
+    code: String,
+    message: String,
+    span: Option<(usize, usize)>,
+    severity: String,
+    #[serde(default)]
+    file: Option<String>,
+    #[serde(default)]
+    fix: Option<Json>,
+}
+
+/// Reasons [`from_wire`] can fail.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum WireError {
+    /// The input has no numeric `format_version`, or does not have the
+    /// shape its version prescribes.
+    Malformed(String),
+    /// The input was written in a format this release does not know, e.g.
+    /// by a newer one.
+    UnsupportedVersion(u32),
+    /// A diagnostic names a code, kind, severity or fix this release does
+    /// not know.
+    InvalidDiagnostic {
+        /// The diagnostic's index in the report.
+        index: usize,
+        /// What is wrong with it.
+        reason: String,
+    },
+}
+
+impl fmt::Display for WireError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WireError::Malformed(reason) => write!(f, "malformed wire report: {reason}"),
+            WireError::UnsupportedVersion(version) => {
+                write!(f, "unsupported wire report format version {version}")
+            }
+            WireError::InvalidDiagnostic { index, reason } => {
+                write!(
+                    f,
+                    "diagnostic {index} of the wire report is invalid: {reason}"
+                )
+            }
+        }
+    }
+}
+
+impl core::error::Error for WireError {}
+
+/// `report` in the current wire format, for storing it.
+pub fn to_wire(report: &ValidationReport) -> WireReport {
+    WireReport {
+        format_version: WireReport::FORMAT_VERSION,
+        diagnostics: report.diagnostics().iter().map(wire_diagnostic).collect(),
+    }
+}
+
+fn wire_diagnostic(error: &ValidationError) -> WireDiagnostic {
+    #[cfg(feature = "std")]
+    let file = error.file.as_ref().map(|file| file.display().to_string());
+    #[cfg(not(feature = "std"))]
+    let file = None;
+    WireDiagnostic {
+        path: error.path.clone(),
+        code: error.code.as_str().to_owned(),
+        kind: kind_name(&error.kind).to_owned(),
+        details: details(&error.kind),
+        message: error.message.clone(),
+        line: error.span.map(|(line, _)| line),
+        column: error.span.map(|(_, column)| column),
+        severity: error.severity.to_string(),
+        file,
+        fix: error
+            .fix
+            .as_ref()
+            .and_then(|fix| serde_json::to_value(fix).ok()),
+    }
+}
+
+/// Read a report written in any supported wire format version, e.g. a
+/// [`WireReport`] parsed from stored JSON.
+pub fn from_wire(value: Json) -> Result<ValidationReport, WireError> {
+    let version = value
+        .get("format_version")
+        .and_then(Json::as_u64)
+        .ok_or_else(|| WireError::Malformed("no numeric `format_version`".to_owned()))?;
+    let diagnostics = match version {
+        1 => {
+            let report: WireReportV1 = parse(value)?;
+            report
+                .diagnostics
+                .into_iter()
+                .enumerate()
+                .map(|(index, wire)| {
+                    let mut details = match wire.kind {
+                        Json::Object(details) => details,
+                        _ => Map::new(),
+                    };
+                    let kind = match details.remove("kind") {
+                        Some(Json::String(kind)) => kind,
+                        _ => String::new(),
+                    };
+                    restore(
+                        index,
+                        Restored {
+                            path: wire.path,
+                            code: wire.code,
+                            kind,
+                            details,
+                            message: wire.message,
+                            span: wire.span,
+                            severity: wire.severity,
+                            file: wire.file,
+                            fix: wire.fix,
+                        },
+                    )
+                })
+                .collect::<Result<_, _>>()?
+        }
+        2 => {
+            let report: WireReport = parse(value)?;
+            report
+                .diagnostics
+                .into_iter()
+                .enumerate()
+                .map(|(index, wire)| {
+                    restore(
+                        index,
+                        Restored {
+                            path: wire.path,
+                            code: wire.code,
+                            kind: wire.kind,
+                            details: wire.details,
+                            message: wire.message,
+                            span: wire.line.zip(wire.column),
+                            severity: wire.severity,
+                            file: wire.file,
+                            fix: wire.fix,
+                        },
+                    )
+                })
+                .collect::<Result<_, _>>()?
+        }
+        version => {
+            return Err(WireError::UnsupportedVersion(
+                u32::try_from(version).unwrap_or(u32::MAX),
+            ))
+        }
+    };
+    Ok(ValidationReport::new(diagnostics))
+}
+
+fn parse<T: DeserializeOwned>(value: Json) -> Result<T, WireError> {
+    serde_json::from_value(value).map_err(|err| WireError::Malformed(err.to_string()))
+}
+
+/// The parts of a diagnostic every format version records.
+struct Restored {
+    path: String,
+    code: String,
+    /// The kind's wire name, as [`kind_name`] gives it.
+    kind: String,
+    /// The kind's fields, as [`details`] gives them.
+    details: Map<String, Json>,
+    message: String,
+    span: Option<(usize, usize)>,
+    severity: String,
+    file: Option<String>,
+    fix: Option<Json>,
+}
+
+fn restore(index: usize, wire: Restored) -> Result<ValidationError, WireError> {
+    let invalid = |reason: String| WireError::InvalidDiagnostic { index, reason };
+    let code = ErrorCode::ALL
+        .iter()
+        .copied()
+        .find(|code| code.as_str() == wire.code)
+        .ok_or_else(|| invalid(format!("unknown code `{}`", wire.code)))?;
+    let kind = decode_kind(&wire.kind, &wire.details).map_err(invalid)?;
+    if kind.error_code() != code {
+        return Err(invalid(format!(
+            "code `{code}` does not match the kind, whose code is `{}`",
+            kind.error_code()
+        )));
+    }
+    let severity = match wire.severity.as_str() {
+        "error" => Severity::Error,
+        "warning" => Severity::Warning,
+        "info" => Severity::Info,
+        other => return Err(invalid(format!("unknown severity `{other}`"))),
+    };
+    let fix = wire
+        .fix
+        .map(serde_json::from_value::<Fix>)
+        .transpose()
+        .map_err(|err| invalid(err.to_string()))?;
+    #[cfg(not(feature = "std"))]
+    let _ = wire.file;
+    Ok(ValidationError {
+        path: wire.path,
+        kind,
+        code,
+        message: wire.message,
+        span: wire.span,
+        severity,
+        #[cfg(feature = "std")]
+        file: wire.file.map(PathBuf::from),
+        fix,
+    })
+}
+
+/// The wire name of `kind`.
+///
+/// These names, and the field names [`details`] and [`decode_kind`] use,
+/// are part of the format: they must stay as they are when a
+/// [`ValidationErrorKind`] variant or field is renamed.
+pub(crate) fn kind_name(kind: &ValidationErrorKind) -> &'static str {
+    use ValidationErrorKind as K;
+    match kind {
+        K::EmptyInput => "empty_input",
+        K::InvalidToml => "invalid_toml",
+        K::InvalidJson => "invalid_json",
+        K::InvalidUtf8 { .. } => "invalid_utf8",
+        K::InputTooLarge { .. } => "input_too_large",
+        K::InvalidSchema => "invalid_schema",
+        K::Io => "io",
+        K::MissingInclude { .. } => "missing_include",
+        K::IncludeOutsideDeployment { .. } => "include_outside_deployment",
+        K::IncludeCycle { .. } => "include_cycle",
+        K::MissingRequired => "missing_required",
+        K::ConditionallyRequired { .. } => "conditionally_required",
+        K::TypeMismatch { .. } => "type_mismatch",
+        K::Coerced { .. } => "coerced",
+        K::NotInEnum { .. } => "not_in_enum",
+        K::OutOfRange { .. } => "out_of_range",
+        K::TooFewItems { .. } => "too_few_items",
+        K::TooManyItems { .. } => "too_many_items",
+        K::Deprecated { .. } => "deprecated",
+        K::FieldNotYetAvailable { .. } => "field_not_yet_available",
+        K::FieldRemoved { .. } => "field_removed",
+        K::ControlCharacter { .. } => "control_character",
+        K::EmptyString => "empty_string",
+        K::MaxDepthExceeded { .. } => "max_depth_exceeded",
+        K::PatternMismatch { .. } => "pattern_mismatch",
+        K::ValidatorFailed { .. } => "validator_failed",
+        K::InvalidDuration => "invalid_duration",
+        K::InvalidByteSize => "invalid_byte_size",
+        K::InvalidEmail => "invalid_email",
+        K::InvalidUrl => "invalid_url",
+        K::InvalidFormat { .. } => "invalid_format",
+        K::InlineCredential => "inline_credential",
+        K::MissingOneOf { .. } => "missing_one_of",
+        K::MultipleExclusive { .. } => "multiple_exclusive",
+        K::IncompletePartialGroup { .. } => "incomplete_partial_group",
+        K::UnknownKey { .. } => "unknown_key",
+        K::DuplicateKey { .. } => "duplicate_key",
+        K::InsecureManagementApi => "insecure_management_api",
+        K::ImplicitSecurityDefault { .. } => "implicit_security_default",
+        K::DuplicateName { .. } => "duplicate_name",
+        K::ListenerAddressConflict { .. } => "listener_address_conflict",
+        K::DuplicateProxyId { .. } => "duplicate_proxy_id",
+        K::DuplicatePeerKey { .. } => "duplicate_peer_key",
+        K::PeerKeyEqualsInterface => "peer_key_equals_interface",
+        K::UnknownMiddlewareType { .. } => "unknown_middleware_type",
+        K::IncompatibleProtocols { .. } => "incompatible_protocols",
+        K::MiddlewareOrderViolation { .. } => "middleware_order_violation",
+        K::UnsupportedMiddlewareVersion { .. } => "unsupported_middleware_version",
+        K::UnknownServiceType { .. } => "unknown_service_type",
+        K::UnknownPipelineReference { .. } => "unknown_pipeline_reference",
+        K::UnknownProvider { .. } => "unknown_provider",
+        K::UnreachablePipeline { .. } => "unreachable_pipeline",
+        K::IncompleteTls { .. } => "incomplete_tls",
+        K::InvalidTlsVersionRange { .. } => "invalid_tls_version_range",
+        K::InvalidRetryPolicy { .. } => "invalid_retry_policy",
+        K::InvalidEgressDestination => "invalid_egress_destination",
+        K::InvalidCorsCombination => "invalid_cors_combination",
+        K::InvalidWeightDistribution { .. } => "invalid_weight_distribution",
+        K::IncompleteEgressAuth { .. } => "incomplete_egress_auth",
+        K::Custom { .. } => "custom",
+    }
+}
+
+/// The fields of `kind` under their wire names.
+fn details(kind: &ValidationErrorKind) -> Map<String, Json> {
+    use ValidationErrorKind as K;
+    let fields: Vec<(&str, Json)> = match kind {
+        K::EmptyInput
+        | K::InvalidToml
+        | K::InvalidJson
+        | K::InvalidSchema
+        | K::Io
+        | K::MissingRequired
+        | K::EmptyString
+        | K::InvalidDuration
+        | K::InvalidByteSize
+        | K::InvalidEmail
+        | K::InvalidUrl
+        | K::InlineCredential
+        | K::InsecureManagementApi
+        | K::PeerKeyEqualsInterface
+        | K::InvalidEgressDestination
+        | K::InvalidCorsCombination => Vec::new(),
+        K::InvalidUtf8 { offset } => vec![("offset", json!(offset))],
+        K::InputTooLarge { limit } => vec![("limit", json!(limit))],
+        K::MissingInclude { include } | K::IncludeOutsideDeployment { include } => {
+            vec![("include", json!(include))]
+        }
+        K::IncludeCycle { chain } => vec![("chain", json!(chain))],
+        K::ConditionallyRequired { condition } => {
+            vec![("condition", encode_condition(condition))]
+        }
+        K::TypeMismatch { expected, found } | K::Coerced { expected, found } => vec![
+            ("expected", encode_field_type(*expected)),
+            ("found", json!(found)),
+        ],
+        K::NotInEnum { allowed, found } => {
+            vec![("allowed", json!(allowed)), ("found", json!(found))]
+        }
+        K::OutOfRange { min, max, actual } => vec![
+            ("min", json!(min)),
+            ("max", json!(max)),
+            ("actual", json!(actual)),
+        ],
+        K::TooFewItems { min, actual } => vec![("min", json!(min)), ("actual", json!(actual))],
+        K::TooManyItems { max, actual } => vec![("max", json!(max)), ("actual", json!(actual))],
+        K::Deprecated { note } => vec![("note", json!(note))],
+        K::FieldNotYetAvailable { since } => vec![("since", json!(since))],
+        K::FieldRemoved { until } => vec![("until", json!(until))],
+        K::ControlCharacter { codepoint } => vec![("codepoint", json!(codepoint))],
+        K::MaxDepthExceeded { max_depth } => vec![("max_depth", json!(max_depth))],
+        K::PatternMismatch { pattern } => vec![("pattern", json!(pattern))],
+        K::ValidatorFailed { validator } => vec![("validator", json!(validator))],
+        K::InvalidFormat { expected } => vec![("expected", json!(expected))],
+        K::MissingOneOf { fields }
+        | K::MultipleExclusive { fields }
+        | K::IncompleteEgressAuth { fields } => vec![("fields", json!(fields))],
+        K::IncompletePartialGroup { missing } => vec![("missing", json!(missing))],
+        K::UnknownKey { suggestion } => vec![("suggestion", json!(suggestion))],
+        K::DuplicateKey { key, first_span } => {
+            vec![("key", json!(key)), ("first_span", json!(first_span))]
+        }
+        K::ImplicitSecurityDefault { default } => vec![("default", json!(default))],
+        K::DuplicateName {
+            name,
+            first_path,
+            first_span,
+        } => vec![
+            ("name", json!(name)),
+            ("first_path", json!(first_path)),
+            ("first_span", json!(first_span)),
+        ],
+        K::ListenerAddressConflict {
+            address,
+            first_listener,
+            first_span,
+        } => vec![
+            ("address", json!(address)),
+            ("first_listener", json!(first_listener)),
+            ("first_span", json!(first_span)),
+        ],
+        K::DuplicateProxyId { id, first_file } => {
+            vec![("id", json!(id)), ("first_file", json!(first_file))]
+        }
+        K::DuplicatePeerKey { first_peer } => vec![("first_peer", json!(first_peer))],
+        K::UnknownMiddlewareType {
+            reference,
+            suggestion,
+        }
+        | K::UnknownServiceType {
+            reference,
+            suggestion,
+        }
+        | K::UnknownPipelineReference {
+            reference,
+            suggestion,
+        }
+        | K::UnknownProvider {
+            reference,
+            suggestion,
+        } => vec![
+            ("reference", json!(reference)),
+            ("suggestion", json!(suggestion)),
+        ],
+        K::IncompatibleProtocols {
+            endpoint,
+            endpoint_protocol,
+            backend,
+            backend_protocol,
+        } => vec![
+            ("endpoint", json!(endpoint)),
+            ("endpoint_protocol", json!(endpoint_protocol)),
+            ("backend", json!(backend)),
+            ("backend_protocol", json!(backend_protocol)),
+        ],
+        K::MiddlewareOrderViolation {
+            middleware,
+            middleware_type,
+            successor,
+            successor_type,
+        } => vec![
+            ("middleware", json!(middleware)),
+            ("middleware_type", json!(middleware_type)),
+            ("successor", json!(successor)),
+            ("successor_type", json!(successor_type)),
+        ],
+        K::UnsupportedMiddlewareVersion {
+            version,
+            middleware_type,
+            supported,
+        } => vec![
+            ("version", json!(version)),
+            ("middleware_type", json!(middleware_type)),
+            ("supported", json!(supported)),
+        ],
+        K::UnreachablePipeline { pipeline } => vec![("pipeline", json!(pipeline))],
+        K::IncompleteTls { missing } => vec![("missing", json!(missing))],
+        K::InvalidTlsVersionRange { min, max } => {
+            vec![("min", json!(min)), ("max", json!(max))]
+        }
+        K::InvalidRetryPolicy {
+            max_retries,
+            initial_backoff_ms,
+            max_backoff_ms,
+        } => vec![
+            ("max_retries", json!(max_retries)),
+            ("initial_backoff_ms", json!(initial_backoff_ms)),
+            ("max_backoff_ms", json!(max_backoff_ms)),
+        ],
+        K::InvalidWeightDistribution { sum } => vec![("sum", json!(sum))],
+        K::Custom { rule } => vec![("rule", json!(rule))],
+    };
+    fields
+        .into_iter()
+        .map(|(name, value)| (name.to_owned(), value))
+        .collect()
+}
+
+/// The kind named `name` with the fields in `details`, the inverse of
+/// [`kind_name`] and [`details`].
+fn decode_kind(name: &str, details: &Map<String, Json>) -> Result<ValidationErrorKind, String> {
+    use ValidationErrorKind as K;
+    let fields = Fields(details);
+    Ok(match name {
+        "empty_input" => K::EmptyInput,
+        "invalid_toml" => K::InvalidToml,
+        "invalid_json" => K::InvalidJson,
+        "invalid_utf8" => K::InvalidUtf8 {
+            offset: fields.get("offset")?,
+        },
+        "input_too_large" => K::InputTooLarge {
+            limit: fields.get("limit")?,
+        },
+        "invalid_schema" => K::InvalidSchema,
+        "io" => K::Io,
+        "missing_include" => K::MissingInclude {
+            include: fields.get("include")?,
+        },
+        "include_outside_deployment" => K::IncludeOutsideDeployment {
+            include: fields.get("include")?,
+        },
+        "include_cycle" => K::IncludeCycle {
+            chain: fields.get("chain")?,
+        },
+        "missing_required" => K::MissingRequired,
+        "conditionally_required" => K::ConditionallyRequired {
+            condition: decode_condition(fields.json("condition"))
+                .ok_or("field `condition` is not a condition")?,
+        },
+        "type_mismatch" => K::TypeMismatch {
+            expected: fields.field_type("expected")?,
+            found: fields.get("found")?,
+        },
+        "coerced" => K::Coerced {
+            expected: fields.field_type("expected")?,
+            found: fields.get("found")?,
+        },
+        "not_in_enum" => K::NotInEnum {
+            allowed: fields.get("allowed")?,
+            found: fields.get("found")?,
+        },
+        "out_of_range" => K::OutOfRange {
+            min: fields.get("min")?,
+            max: fields.get("max")?,
+            actual: fields.get("actual")?,
+        },
+        "too_few_items" => K::TooFewItems {
+            min: fields.get("min")?,
+            actual: fields.get("actual")?,
+        },
+        "too_many_items" => K::TooManyItems {
+            max: fields.get("max")?,
+            actual: fields.get("actual")?,
+        },
+        "deprecated" => K::Deprecated {
+            note: fields.get("note")?,
+        },
+        "field_not_yet_available" => K::FieldNotYetAvailable {
+            since: fields.get("since")?,
+        },
+        "field_removed" => K::FieldRemoved {
+            until: fields.get("until")?,
+        },
+        "control_character" => K::ControlCharacter {
+            codepoint: fields.get("codepoint")?,
+        },
+        "empty_string" => K::EmptyString,
+        "max_depth_exceeded" => K::MaxDepthExceeded {
+            max_depth: fields.get("max_depth")?,
+        },
+        "pattern_mismatch" => K::PatternMismatch {
+            pattern: fields.get("pattern")?,
+        },
+        "validator_failed" => K::ValidatorFailed {
+            validator: fields.get("validator")?,
+        },
+        "invalid_duration" => K::InvalidDuration,
+        "invalid_byte_size" => K::InvalidByteSize,
+        "invalid_email" => K::InvalidEmail,
+        "invalid_url" => K::InvalidUrl,
+        "invalid_format" => K::InvalidFormat {
+            expected: fields.get("expected")?,
+        },
+        "inline_credential" => K::InlineCredential,
+        "missing_one_of" => K::MissingOneOf {
+            fields: fields.get("fields")?,
+        },
+        "multiple_exclusive" => K::MultipleExclusive {
+            fields: fields.get("fields")?,
+        },
+        "incomplete_partial_group" => K::IncompletePartialGroup {
+            missing: fields.get("missing")?,
+        },
+        "unknown_key" => K::UnknownKey {
+            suggestion: fields.get("suggestion")?,
+        },
+        "duplicate_key" => K::DuplicateKey {
+            key: fields.get("key")?,
+            first_span: fields.get("first_span")?,
+        },
+        "insecure_management_api" => K::InsecureManagementApi,
+        "implicit_security_default" => K::ImplicitSecurityDefault {
+            default: fields.get("default")?,
+        },
+        "duplicate_name" => K::DuplicateName {
+            name: fields.get("name")?,
+            first_path: fields.get("first_path")?,
+            first_span: fields.get("first_span")?,
+        },
+        "listener_address_conflict" => K::ListenerAddressConflict {
+            address: fields.get("address")?,
+            first_listener: fields.get("first_listener")?,
+            first_span: fields.get("first_span")?,
+        },
+        "duplicate_proxy_id" => K::DuplicateProxyId {
+            id: fields.get("id")?,
+            first_file: fields.get("first_file")?,
+        },
+        "duplicate_peer_key" => K::DuplicatePeerKey {
+            first_peer: fields.get("first_peer")?,
+        },
+        "peer_key_equals_interface" => K::PeerKeyEqualsInterface,
+        "unknown_middleware_type" => K::UnknownMiddlewareType {
+            reference: fields.get("reference")?,
+            suggestion: fields.get("suggestion")?,
+        },
+        "incompatible_protocols" => K::IncompatibleProtocols {
+            endpoint: fields.get("endpoint")?,
+            endpoint_protocol: fields.get("endpoint_protocol")?,
+            backend: fields.get("backend")?,
+            backend_protocol: fields.get("backend_protocol")?,
+        },
+        "middleware_order_violation" => K::MiddlewareOrderViolation {
+            middleware: fields.get("middleware")?,
+            middleware_type: fields.get("middleware_type")?,
+            successor: fields.get("successor")?,
+            successor_type: fields.get("successor_type")?,
+        },
+        "unsupported_middleware_version" => K::UnsupportedMiddlewareVersion {
+            version: fields.get("version")?,
+            middleware_type: fields.get("middleware_type")?,
+            supported: fields.get("supported")?,
+        },
+        "unknown_service_type" => K::UnknownServiceType {
+            reference: fields.get("reference")?,
+            suggestion: fields.get("suggestion")?,
+        },
+        "unknown_pipeline_reference" => K::UnknownPipelineReference {
+            reference: fields.get("reference")?,
+            suggestion: fields.get("suggestion")?,
+        },
+        "unknown_provider" => K::UnknownProvider {
+            reference: fields.get("reference")?,
+            suggestion: fields.get("suggestion")?,
+        },
+        "unreachable_pipeline" => K::UnreachablePipeline {
+            pipeline: fields.get("pipeline")?,
+        },
+        "incomplete_tls" => K::IncompleteTls {
+            missing: fields.get("missing")?,
+        },
+        "invalid_tls_version_range" => K::InvalidTlsVersionRange {
+            min: fields.get("min")?,
+            max: fields.get("max")?,
+        },
+        "invalid_retry_policy" => K::InvalidRetryPolicy {
+            max_retries: fields.get("max_retries")?,
+            initial_backoff_ms: fields.get("initial_backoff_ms")?,
+            max_backoff_ms: fields.get("max_backoff_ms")?,
+        },
+        "invalid_egress_destination" => K::InvalidEgressDestination,
+        "invalid_cors_combination" => K::InvalidCorsCombination,
+        "invalid_weight_distribution" => K::InvalidWeightDistribution {
+            sum: fields.get("sum")?,
+        },
+        "incomplete_egress_auth" => K::IncompleteEgressAuth {
+            fields: fields.get("fields")?,
+        },
+        "custom" => K::Custom {
+            rule: fields.get("rule")?,
+        },
+        other => return Err(format!("unknown kind `{other}`")),
+    })
+}
+
+/// A diagnostic's `details`, read field by field.
+struct Fields<'a>(&'a Map<String, Json>);
+
+impl Fields<'_> {
+    /// The field `name`, `null` if absent so optional fields may be omitted.
+    fn json(&self, name: &str) -> &Json {
+        self.0.get(name).unwrap_or(&Json::Null)
+    }
+
+    fn get<T: DeserializeOwned>(&self, name: &str) -> Result<T, String> {
+        T::deserialize(self.json(name)).map_err(|err| format!("field `{name}`: {err}"))
+    }
+
+    fn field_type(&self, name: &str) -> Result<FieldType, String> {
+        decode_field_type(self.json(name)).ok_or_else(|| format!("field `{name}` is not a type"))
+    }
+}
+
+fn encode_field_type(ty: FieldType) -> Json {
+    let name = match ty {
+        FieldType::String => "string",
+        FieldType::Integer => "integer",
+        FieldType::Float => "float",
+        FieldType::Boolean => "boolean",
+        FieldType::Array => "array",
+        FieldType::Duration => "duration",
+        FieldType::ByteSize => "byte_size",
+        FieldType::IpAddr => "ip_addr",
+        FieldType::SocketAddr => "socket_addr",
+        FieldType::Cidr => "cidr",
+        FieldType::Email => "email",
+        FieldType::Url => "url",
+        FieldType::Table { pattern } => return json!({ "table": { "pattern": pattern } }),
+    };
+    Json::String(name.to_owned())
+}
+
+fn decode_field_type(json: &Json) -> Option<FieldType> {
+    Some(match json {
+        Json::String(name) => match name.as_str() {
+            "string" => FieldType::String,
+            "integer" => FieldType::Integer,
+            "float" => FieldType::Float,
+            "boolean" => FieldType::Boolean,
+            "array" => FieldType::Array,
+            "duration" => FieldType::Duration,
+            "byte_size" => FieldType::ByteSize,
+            "ip_addr" => FieldType::IpAddr,
+            "socket_addr" => FieldType::SocketAddr,
+            "cidr" => FieldType::Cidr,
+            "email" => FieldType::Email,
+            "url" => FieldType::Url,
+            _ => return None,
+        },
+        Json::Object(object) => FieldType::Table {
+            pattern: object.get("table")?.get("pattern")?.as_bool()?,
+        },
+        _ => return None,
+    })
+}
+
+fn encode_condition(condition: &Condition) -> Json {
+    match condition {
+        Condition::Equals { field, value } => json!({
+            "equals": {
+                "field": field,
+                "value": serde_json::to_value(value).unwrap_or(Json::Null),
+            }
+        }),
+        Condition::Exists { field } => json!({ "exists": { "field": field } }),
+        Condition::Missing { field } => json!({ "missing": { "field": field } }),
+    }
+}
+
+fn decode_condition(json: &Json) -> Option<Condition> {
+    let (test, operands) = json.as_object()?.iter().next()?;
+    let field = operands.get("field")?.as_str()?.to_owned();
+    Some(match test.as_str() {
+        "equals" => Condition::Equals {
+            field,
+            value: toml::Value::deserialize(operands.get("value")?).ok()?,
+        },
+        "exists" => Condition::Exists { field },
+        "missing" => Condition::Missing { field },
+        _ => return None,
+    })
+}
+
+#[cfg(all(test, feature = "schema-config"))]
+mod tests {
+    use super::*;
+    use crate::fix::FixAction;
+    use crate::schema::FieldType;
+    use crate::validate::validate_report;
+    use crate::SchemaKind;
+
+    #[test]
+    fn version_1_reports_are_read() {
+        let stored = r#"{
+            "format_version": 1,
+            "diagnostics": [
+                {
+                    "path": "proxy.id",
+                    "kind": { "kind": "type_mismatch", "expected": "string", "found": "integer" },
+                    "code": "E_TYPE_MISMATCH",
+                    "message": "expected string, found integer",
+                    "span": [2, 6],
+                    "severity": "error",
+                    "file": "config.toml",
+                    "fix": null
+                },
+                {
+                    "path": "proxy.legacy",
+                    "kind": { "kind": "unknown_key", "suggestion": "log_level" },
+                    "code": "E_UNKNOWN_KEY",
+                    "message": "unknown key `legacy`",
+                    "span": [3, 1],
+                    "severity": "warning",
+                    "file": "config.toml",
+                    "fix": { "path": "proxy.legacy", "action": "rename_key", "to": "log_level" }
+                }
+            ]
+        }"#;
+        let report = from_wire(serde_json::from_str(stored).unwrap()).unwrap();
+        let [mismatch, unknown] = report.diagnostics() else {
+            panic!("{report:?}");
+        };
+        assert_eq!(
+            mismatch.kind,
+            ValidationErrorKind::TypeMismatch {
+                expected: FieldType::String,
+                found: "integer".to_owned()
+            }
+        );
+        assert_eq!(mismatch.code, ErrorCode::TypeMismatch);
+        assert_eq!(mismatch.span, Some((2, 6)));
+        assert!(mismatch.is_error());
+        #[cfg(feature = "std")]
+        assert_eq!(mismatch.file.as_deref(), Some("config.toml".as_ref()));
+        assert_eq!(unknown.severity, Severity::Warning);
+        assert_eq!(
+            unknown.fix.as_ref().map(|fix| &fix.action),
+            Some(&FixAction::RenameKey {
+                to: "log_level".to_owned()
+            })
+        );
+
+        let mismatched = stored.replacen("E_TYPE_MISMATCH", "E_UNKNOWN_KEY", 1);
+        assert!(matches!(
+            from_wire(serde_json::from_str(&mismatched).unwrap()),
+            Err(WireError::InvalidDiagnostic { index: 0, .. })
+        ));
+    }
+
+    #[test]
+    fn kinds_are_written_under_their_wire_names() {
+        let kinds = [
+            ValidationErrorKind::ConditionallyRequired {
+                condition: Condition::Equals {
+                    field: "backend".to_owned(),
+                    value: toml::Value::String("s3".to_owned()),
+                },
+            },
+            ValidationErrorKind::TypeMismatch {
+                expected: FieldType::Table { pattern: true },
+                found: "string".to_owned(),
+            },
+        ];
+        let [conditional, mismatch] = kinds.each_ref().map(|kind| {
+            let error = ValidationError::new("storage.bucket", kind.clone(), "");
+            wire_diagnostic(&error)
+        });
+        assert_eq!(conditional.kind, "conditionally_required");
+        assert_eq!(
+            Json::Object(conditional.details.clone()),
+            serde_json::json!({
+                "condition": { "equals": { "field": "backend", "value": "s3" } }
+            })
+        );
+        assert_eq!(mismatch.kind, "type_mismatch");
+        assert_eq!(
+            Json::Object(mismatch.details.clone()),
+            serde_json::json!({ "expected": { "table": { "pattern": true } }, "found": "string" })
+        );
+        for (wire, kind) in [conditional, mismatch].iter().zip(&kinds) {
+            assert_eq!(decode_kind(&wire.kind, &wire.details).as_ref(), Ok(kind));
+        }
+        assert_eq!(
+            decode_kind("renamed", &Map::new()),
+            Err("unknown kind `renamed`".to_owned())
+        );
+    }
+
+    #[test]
+    fn reports_round_trip_through_the_current_format() {
+        let config = "[proxy]\nid = 7\nlegacy = true\n\n[management]\nenabled = true\n";
+        let report = validate_report(SchemaKind::Config, config);
+        let wire = to_wire(&report);
+        assert_eq!(wire.format_version, WireReport::FORMAT_VERSION);
+        let json = serde_json::to_value(&wire).unwrap();
+        assert_eq!(json["diagnostics"][0]["kind"], "type_mismatch");
+        assert_eq!(json["diagnostics"][0]["details"]["found"], "integer");
+        let management = &json["diagnostics"][2];
+        assert_eq!(management["kind"], "insecure_management_api");
+        assert!(management.get("details").is_none());
+        assert_eq!(from_wire(json), Ok(report));
+
+        assert_eq!(
+            from_wire(serde_json::json!({ "format_version": 3, "diagnostics": [] })),
+            Err(WireError::UnsupportedVersion(3))
+        );
+        assert!(matches!(
+            from_wire(serde_json::json!({ "diagnostics": [] })),
+            Err(WireError::Malformed(_))
+        ));
+    }
+}
@@ -0,0 +1,50 @@
+use std::path::Path;
+use std::process::{Command, Output};
+
+fn harmony_validate(args: &[&str]) -> Output {
+    let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+    Command::new(env!("CARGO_BIN_EXE_harmony-validate"))
+        .current_dir(fixtures)
+        .args(args)
+        .output()
+        .expect("harmony-validate runs")
+}
+
+#[test]
+fn valid_deployment_exits_successfully() {
+    let output = harmony_validate(&["valid"]);
+    assert_eq!(output.status.code(), Some(0), "{output:?}");
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "2 files checked: 0 errors, 0 warnings\n"
+    );
+}
+
+#[test]
+fn errors_exit_non_zero() {
+    let output = harmony_validate(&["invalid"]);
+    assert_eq!(output.status.code(), Some(1), "{output:?}");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("--> invalid/config.toml:2:6"), "{stdout}");
+    assert!(
+        stdout.contains("--> invalid/pipelines/default.toml:2:11"),
+        "{stdout}"
+    );
+    assert!(stdout.ends_with("2 files checked: 2 errors, 0 warnings\n"));
+
+    let output = harmony_validate(&["--format", "json", "invalid/config.toml"]);
+    assert_eq!(output.status.code(), Some(1), "{output:?}");
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(json[0]["file"], "invalid/config.toml");
+    assert_eq!(json[0]["code"], "E_PATTERN_MISMATCH");
+}
+
+#[test]
+fn unknown_files_need_a_schema() {
+    let output = harmony_validate(&["valid/pipelines/default.toml", "--schema", "config"]);
+    assert_eq!(output.status.code(), Some(1), "{output:?}");
+
+    let output = harmony_validate(&["../../Cargo.toml"]);
+    assert_eq!(output.status.code(), Some(2), "{output:?}");
+    assert!(String::from_utf8_lossy(&output.stderr).contains("pass --schema"));
+}
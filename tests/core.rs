@@ -0,0 +1,61 @@
+//! The validation API that builds without `std`. Run
+//! `cargo test --no-default-features --test core` to check it against the
+//! `no_std` build of the crate.
+
+use harmony_dsl::{parse_schema, validate_with_schema, ValidationErrorKind};
+
+const SCHEMA: &str = r#"
+[schema]
+version = "1.0.0"
+
+[[table]]
+name = "job"
+required = true
+
+[[table.field]]
+name = "name"
+type = "string"
+pattern = "^[a-z]+$"
+
+[[table.field]]
+name = "retries"
+type = "integer"
+max = 5
+"#;
+
+#[test]
+fn caller_schemas_validate() {
+    assert!(parse_schema(SCHEMA).is_ok());
+    assert_eq!(
+        validate_with_schema(SCHEMA, "[job]\nname = \"nightly\"\nretries = 3\n"),
+        Ok(())
+    );
+
+    let errors =
+        validate_with_schema(SCHEMA, "[job]\nname = \"Nightly\"\nretries = 9\n").unwrap_err();
+    assert_eq!(errors.len(), 2, "{errors:?}");
+    assert_eq!(errors[0].path, "job.name");
+    assert!(matches!(
+        errors[0].kind,
+        ValidationErrorKind::PatternMismatch { .. }
+    ));
+    assert_eq!(errors[1].span, Some((3, 11)));
+    assert_eq!(
+        errors[1].to_string(),
+        "3:11: job.retries: 9 is out of range; expected a value at most 5"
+    );
+}
+
+#[cfg(feature = "schema-config")]
+#[test]
+fn bundled_schemas_validate() {
+    assert_eq!(
+        harmony_dsl::validate_config("[proxy]\nid = \"gw\"\n"),
+        Ok(())
+    );
+    let errors = harmony_dsl::validate_config("[proxy]\nid = 7\n").unwrap_err();
+    assert_eq!(
+        errors[0].message("en"),
+        "`proxy.id` must be of type string, found integer"
+    );
+}
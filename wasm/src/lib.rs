@@ -0,0 +1,5 @@
+//! The [`harmony_dsl::wasm`] exports as a `cdylib`, for
+//! `wasm-pack build wasm`. `harmony-dsl` itself is only an `rlib`, since a
+//! `cdylib` cannot be built without `std`.
+
+pub use harmony_dsl::wasm::*;